@@ -1,11 +1,20 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 
-use utreexo::{MemoryAccumulator, MemoryForest, Proof, Prover, Utreexo};
+use utreexo::{Blake3, Hasher, MemoryAccumulator, MemoryForest, Proof, Prover, Utreexo};
 
 const INITIAL_COUNT: usize = 10_000_000;
 
+/// Number of leaves in forests built by index benchmarks
+const BUILD_COUNT: usize = 1_000_000;
+
+/// Number of leaves which fills index of leaves up to its maximum load (3/4 of `2^20` slots)
+const FULL_COUNT: usize = 3 * (1 << 18) - 1;
+
+/// Number of lookups in every iteration of load benchmarks
+const LOOKUP_COUNT: usize = 10_000;
+
 pub fn criterion_benchmark(c: &mut Criterion) {
-    let hashes = gen_hashes();
+    let hashes = gen_hashes(INITIAL_COUNT);
     let accumulator = gen_accumulator(&hashes);
     let forest = gen_forest(&hashes);
     let proofs = gen_proofs(&forest, &hashes);
@@ -17,6 +26,119 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     bench_forest_delete(c, gen_forest(&hashes), &proofs);
 }
 
+/// Compares building a forest while its index of leaves grows (and is rehashed at every doubling)
+/// with building it into a pre-sized index
+pub fn index_benchmark(c: &mut Criterion) {
+    let hashes = gen_hashes(BUILD_COUNT);
+
+    let mut group = c.benchmark_group("forest build");
+    group.sample_size(10);
+
+    group.bench_function("insert (growing index)", |b| {
+        b.iter(|| {
+            let mut forest = MemoryForest::new();
+
+            for hash in hashes.iter() {
+                forest.insert(black_box(hash));
+            }
+
+            forest
+        })
+    });
+
+    group.bench_function("insert (with_capacity)", |b| {
+        b.iter(|| {
+            let mut forest = MemoryForest::with_capacity(BUILD_COUNT);
+
+            for hash in hashes.iter() {
+                forest.insert(black_box(hash));
+            }
+
+            forest
+        })
+    });
+
+    group.bench_function("from_leaves", |b| {
+        b.iter(|| MemoryForest::from_leaves(black_box(&hashes)))
+    });
+
+    group.bench_function("from_leaves + insert (growing index)", |b| {
+        b.iter_batched(
+            || MemoryForest::from_leaves(&hashes[..BUILD_COUNT / 2]),
+            |mut forest| {
+                for hash in hashes[BUILD_COUNT / 2..].iter() {
+                    forest.insert(black_box(hash));
+                }
+
+                forest
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("from_leaves + insert (reserve)", |b| {
+        b.iter_batched(
+            || {
+                let mut forest = MemoryForest::from_leaves(&hashes[..BUILD_COUNT / 2]);
+                forest.reserve(BUILD_COUNT / 2);
+                forest
+            },
+            |mut forest| {
+                for hash in hashes[BUILD_COUNT / 2..].iter() {
+                    forest.insert(black_box(hash));
+                }
+
+                forest
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+/// Compares lookups in an index of leaves filled up to its maximum load with lookups in an index
+/// twice as large (i.e., the cost of resizing only when tables are 3/4 full)
+pub fn load_benchmark(c: &mut Criterion) {
+    let values = gen_hashes(FULL_COUNT + LOOKUP_COUNT);
+    let (present, absent) = values.split_at(FULL_COUNT);
+
+    let present: Vec<_> = present[..LOOKUP_COUNT]
+        .iter()
+        .map(|value| Blake3.hash_leaf(value))
+        .collect();
+    let absent: Vec<_> = absent.iter().map(|value| Blake3.hash_leaf(value)).collect();
+
+    let full = MemoryForest::from_leaves(&values[..FULL_COUNT]);
+
+    let mut sparse = MemoryForest::with_capacity(2 * FULL_COUNT);
+    for value in values[..FULL_COUNT].iter() {
+        sparse.insert(value);
+    }
+
+    let mut group = c.benchmark_group("index load");
+
+    for (name, forest) in [("3/4 full", &full), ("3/8 full", &sparse)].iter() {
+        group.bench_function(format!("contains_hash (present, {})", name), |b| {
+            b.iter(|| {
+                for hash in present.iter() {
+                    assert!(forest.contains_hash(black_box(hash)));
+                }
+            })
+        });
+
+        group.bench_function(format!("contains_hash (absent, {})", name), |b| {
+            b.iter(|| {
+                for hash in absent.iter() {
+                    assert!(!forest.contains_hash(black_box(hash)));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
 fn bench_accumulator_insert(c: &mut Criterion, mut accumulator: MemoryAccumulator) {
     let hash: [u8; 32] = rand::random();
 
@@ -65,46 +187,35 @@ fn bench_forest_prove(c: &mut Criterion, forest: &MemoryForest, hashes: &[[u8; 3
     });
 }
 
-fn gen_hashes() -> Vec<[u8; 32]> {
-    let mut hashes = Vec::with_capacity(INITIAL_COUNT);
+fn gen_hashes(count: usize) -> Vec<[u8; 32]> {
+    let mut hashes = Vec::with_capacity(count);
 
-    for _ in 0..INITIAL_COUNT {
+    for _ in 0..count {
         let hash: [u8; 32] = rand::random();
-        hashes.push(hash.into());
+        hashes.push(hash);
     }
 
     hashes
 }
 
 fn gen_accumulator(hashes: &[[u8; 32]]) -> MemoryAccumulator {
-    let mut accumulator = MemoryAccumulator::new();
-
-    for hash in hashes {
-        accumulator.insert(*hash);
-    }
-
-    accumulator
+    MemoryAccumulator::from_leaves(hashes)
 }
 
 fn gen_forest(hashes: &[[u8; 32]]) -> MemoryForest {
-    let mut forest = MemoryForest::new();
-
-    for hash in hashes {
-        forest.insert(*hash)
-    }
-
-    forest
+    MemoryForest::from_leaves(hashes)
 }
 
 fn gen_proofs(forest: &MemoryForest, hashes: &[[u8; 32]]) -> Vec<Proof> {
     let mut proofs = Vec::with_capacity(INITIAL_COUNT);
 
     for hash in hashes {
-        proofs.push(forest.prove(hash).unwrap());
+        proofs.push(forest.prove(hash).unwrap().unwrap());
     }
 
     proofs
 }
 
 criterion_group!(benches, criterion_benchmark);
-criterion_main!(benches);
+criterion_group!(index_benches, index_benchmark, load_benchmark);
+criterion_main!(benches, index_benches);
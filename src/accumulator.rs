@@ -1,14 +1,48 @@
-use alloc::vec::Vec;
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{convert::TryFrom, mem::size_of};
 
 #[cfg(feature = "serde-1")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::{hash_intermediate, hash_leaf, Hash, Proof, Utreexo};
+use crate::{
+    bulk,
+    encoding::{
+        check_last_present, num_present, split_salt, write_header, write_roots, Reader,
+        ACCUMULATOR_MAGIC, FOREST_MAGIC,
+    },
+    estimate::MemoryUsage,
+    forest::num_heights,
+    metrics::{count, Counter},
+    partial::delete_leaf,
+    BaggingOrder, Blake3, BlockChanges, Checkpoint, ForestStats, Hash, Hasher, KeyedBlake3,
+    LeafPosition, MemoryForest, Proof, RootProof, RootWitness, SaltedBlake3, UpdateData, Utreexo,
+    UtreexoError,
+};
 
 /// Hash based in-memory accumulator
-#[derive(Debug, Default, Clone, Eq, PartialEq)]
+///
+/// With `zeroize` feature, root hashes are wiped when accumulator is dropped (or explicitly using
+/// `Zeroize`, which leaves an empty accumulator).
+#[derive(Debug, Default, Clone)]
 #[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
-pub struct MemoryAccumulator(Vec<Option<Hash>>);
+pub struct MemoryAccumulator<H = Blake3> {
+    /// Root hashes of merkle trees in forest
+    roots: Vec<Option<Hash>>,
+    /// Hasher used for calculating leaf and intermediate hashes
+    hasher: H,
+}
+
+impl<H: PartialEq> PartialEq for MemoryAccumulator<H> {
+    fn eq(&self, other: &Self) -> bool {
+        // Deletions may leave empty positions after the tallest tree, which are not part of state
+        self.roots[..num_present(&self.roots)] == other.roots[..num_present(&other.roots)]
+            && self.hasher == other.hasher
+    }
+}
+
+impl<H: Eq> Eq for MemoryAccumulator<H> {}
 
 impl MemoryAccumulator {
     /// Creates a new instance of memory accumulator
@@ -16,35 +50,548 @@ impl MemoryAccumulator {
         Default::default()
     }
 
+    /// Creates a new instance of memory accumulator which can hold given number of leaves without
+    /// reallocating root hashes
+    pub fn with_capacity(num_leaves: usize) -> Self {
+        Self::with_capacity_and_hasher(num_leaves, Blake3)
+    }
+
+    /// Creates an accumulator containing given values (see
+    /// [`MemoryAccumulator::from_leaves_with_hasher`])
+    pub fn from_leaves<T: AsRef<[u8]> + Sync>(leaf_values: &[T]) -> Self {
+        Self::from_leaves_with_hasher(leaf_values, Blake3)
+    }
+
+    /// Creates an accumulator from given root hashes (see
+    /// [`MemoryAccumulator::from_roots_with_hasher`])
+    pub fn from_roots(roots: Vec<Option<Hash>>) -> Result<Self, UtreexoError> {
+        Self::from_roots_with_hasher(roots, Blake3)
+    }
+
+    /// Parses accumulator from its canonical binary encoding (see
+    /// [`MemoryAccumulator::from_bytes_with_hasher`] for layout)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        Self::from_bytes_with_hasher(bytes, Blake3)
+    }
+
+    /// Creates accumulator from canonical binary encoding of a forest (see
+    /// [`MemoryAccumulator::from_forest_bytes_with_hasher`])
+    pub fn from_forest_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        Self::from_forest_bytes_with_hasher(bytes, Blake3)
+    }
+}
+
+impl MemoryAccumulator<KeyedBlake3> {
+    /// Creates a new instance of memory accumulator which uses keyed blake3 hashing with given key
+    pub fn new_keyed(key: [u8; blake3::KEY_LEN]) -> Self {
+        Self::with_hasher(KeyedBlake3::new(key))
+    }
+}
+
+impl MemoryAccumulator<SaltedBlake3> {
+    /// Creates a new instance of memory accumulator which mixes given salt into leaf hashes (see
+    /// [`SaltedBlake3`])
+    pub fn new_salted(salt: [u8; blake3::KEY_LEN]) -> Self {
+        Self::with_hasher(SaltedBlake3::new(salt))
+    }
+
+    /// Returns salt mixed into leaf hashes
+    pub fn salt(&self) -> &[u8; blake3::KEY_LEN] {
+        self.hasher.salt()
+    }
+
+    /// Returns binary encoding of accumulator along with its salt (`salt (32 bytes) || canonical
+    /// binary encoding of accumulator`)
+    pub fn to_salted_bytes(&self) -> Vec<u8> {
+        [&self.salt()[..], &self.to_bytes()].concat()
+    }
+
+    /// Parses accumulator from its binary encoding along with its salt (see
+    /// [`MemoryAccumulator::to_salted_bytes`])
+    pub fn from_salted_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        let (salt, state) = split_salt(bytes)?;
+        Self::from_bytes_with_hasher(state, SaltedBlake3::new(salt))
+    }
+}
+
+impl<H: Hasher> MemoryAccumulator<H> {
+    /// Creates a new instance of memory accumulator which uses given hasher
+    pub fn with_hasher(hasher: H) -> Self {
+        Self {
+            roots: Default::default(),
+            hasher,
+        }
+    }
+
+    /// Creates a new instance of memory accumulator which uses given hasher and can hold given
+    /// number of leaves without reallocating root hashes
+    pub fn with_capacity_and_hasher(num_leaves: usize, hasher: H) -> Self {
+        Self {
+            roots: Vec::with_capacity(num_heights(num_leaves)),
+            hasher,
+        }
+    }
+
+    /// Creates an accumulator, which uses given hasher, from given root hashes of merkle trees
+    /// (indexed by height, e.g., obtained from a trusted checkpoint or
+    /// [`MemoryAccumulator::root_hashes`]). Root hashes are not verified in any way.
+    ///
+    /// Returns `TooManyRoots` error if there are more roots than the number of possible heights of
+    /// merkle trees.
+    pub fn from_roots_with_hasher(
+        roots: Vec<Option<Hash>>,
+        hasher: H,
+    ) -> Result<Self, UtreexoError> {
+        let max_num_roots = Proof::MAX_HEIGHT + 1;
+
+        if roots.len() > max_num_roots {
+            return Err(UtreexoError::TooManyRoots {
+                num_roots: roots.len(),
+                max_num_roots,
+            });
+        }
+
+        Ok(Self { roots, hasher })
+    }
+
+    /// Creates an accumulator containing given values which uses given hasher. Resulting
+    /// accumulator is same as inserting the values one by one, but root hash of every tree is
+    /// calculated bottom-up in one go (with `rayon` feature, leaves are hashed and subtrees are
+    /// built in parallel).
+    pub fn from_leaves_with_hasher<T: AsRef<[u8]> + Sync>(leaf_values: &[T], hasher: H) -> Self
+    where
+        H: Sync,
+    {
+        let leaf_hashes = bulk::hash_leaves(&hasher, leaf_values);
+        let subtrees = bulk::subtrees(leaf_hashes.len());
+
+        let mut roots = Vec::new();
+
+        for (height, range) in subtrees {
+            let root_hash = bulk::build_root(&hasher, &leaf_hashes[range]);
+
+            roots.resize(roots.len().max(height + 1), None);
+            roots[height] = Some(root_hash);
+        }
+
+        Self { roots, hasher }
+    }
+
+    /// Inserts given values in accumulator. Resulting accumulator is same as inserting the values
+    /// one by one, but new leaves are combined level by level (pairs of new subtrees are hashed
+    /// together before being merged with existing roots), and with `rayon` feature, leaves are
+    /// hashed in parallel.
+    ///
+    /// Every intermediate hash calculated by sequential inserts is a node of the resulting forest,
+    /// so, both approaches calculate the same number of intermediate hashes. Batch insert avoids
+    /// walking the carry chain of roots for every value.
+    ///
+    /// New leaves always come after the existing leaves (positions freed by
+    /// [`MemoryAccumulator::delete_batch`] are not reused).
+    pub fn insert_batch<T: AsRef<[u8]> + Sync>(&mut self, leaf_values: &[T])
+    where
+        H: Sync,
+    {
+        let leaf_hashes = bulk::hash_leaves(&self.hasher, leaf_values);
+        self.insert_hash_batch(leaf_hashes)
+    }
+
+    /// Inserts given leaf hashes (e.g., calculated using [`hash_leaves`](crate::hash_leaves)) in
+    /// accumulator in the same way as [`MemoryAccumulator::insert_batch`]
+    pub fn insert_hash_batch(&mut self, leaf_hashes: Vec<Hash>) {
+        count(Counter::Insert, leaf_hashes.len() as u64);
+
+        // Roots of new subtrees of current height (in order of insertion)
+        let mut level = leaf_hashes;
+        let mut height = 0;
+
+        while !level.is_empty() {
+            if height == self.roots.len() {
+                self.roots.push(None);
+            }
+
+            // Existing root is older than all the new subtrees, so, it becomes the leftmost one
+            if let Some(root_hash) = self.roots[height].take() {
+                level.insert(0, root_hash);
+            }
+
+            if level.len() % 2 == 1 {
+                self.roots[height] = level.pop();
+            }
+
+            level = level
+                .chunks(2)
+                .map(|pair| self.hasher.hash_intermediate(&pair[0], &pair[1]))
+                .collect();
+            height += 1;
+        }
+    }
+
+    /// Verifies inclusion proofs of many values at once. Returns `true` only if every proof is
+    /// valid for current root hashes.
+    ///
+    /// Proofs of leaves in the same merkle tree share all the nodes above the level where their
+    /// paths meet. Proofs are combined level by level, so, every shared node is calculated only
+    /// once, and a sibling hash which is also calculated from another proof (or given by another
+    /// proof) must be the same.
+    pub fn verify_batch(&self, proofs: &[Proof]) -> bool {
+        match batch_roots(&self.hasher, proofs) {
+            Some(roots) => roots
+                .iter()
+                .all(|(height, root_hash)| self.roots.get(*height) == Some(&Some(*root_hash))),
+            None => false,
+        }
+    }
+
+    /// Verifies proofs as a batch (see [`MemoryAccumulator::verify_batch`]) and deletes all the
+    /// values. Unlike deletions in [`MemoryAccumulator::apply_block`], every proof should be valid
+    /// for current root hashes (e.g., a batch of proofs generated for a block), and values are
+    /// deleted in given order (the remaining proofs are updated after every deletion).
+    ///
+    /// Returns positions freed by deleted leaves (positions before deletion, in order of proofs).
+    /// Deletion moves sibling subtrees of a deleted leaf to lower heights instead of leaving a
+    /// hole, so, freed positions are never filled later: values inserted afterwards (e.g., with
+    /// [`MemoryAccumulator::insert_batch`] in the same block) are always added after all the
+    /// remaining leaves, which keeps shape of forest determined only by the sequence of updates.
+    /// Implementations which fill holes with new leaves can use the positions to map their leaves.
+    ///
+    /// Returns `InvalidDeletion` error with index of the first invalid (or repeated) proof and
+    /// leaves accumulator unchanged if proofs are not valid.
+    pub fn delete_batch(&mut self, proofs: &[Proof]) -> Result<Vec<LeafPosition>, UtreexoError> {
+        let mut positions = Vec::with_capacity(proofs.len());
+
+        for (index, proof) in proofs.iter().enumerate() {
+            let position = LeafPosition {
+                height: proof.path.height(),
+                index: proof.path.leaf_index() as usize,
+            };
+
+            if positions.contains(&position) {
+                return Err(UtreexoError::InvalidDeletion { index });
+            }
+
+            positions.push(position);
+        }
+
+        if !self.verify_batch(proofs) {
+            // Proofs which are all valid on their own cannot disagree with each other
+            let index = proofs
+                .iter()
+                .position(|proof| !self.verify(proof))
+                .unwrap_or(0);
+
+            return Err(UtreexoError::InvalidDeletion { index });
+        }
+
+        count(Counter::Delete, proofs.len() as u64);
+
+        // Proofs are deleted from the end of reversed list, so that the remaining proofs can be
+        // updated in place
+        let mut remaining: Vec<Proof> = proofs.iter().rev().cloned().collect();
+
+        while let Some(proof) = remaining.pop() {
+            delete_leaf(&self.hasher, &mut self.roots, &mut remaining, &proof);
+        }
+
+        Ok(positions)
+    }
+
     /// Returns the root hashes of all the merkle trees in forest
     pub fn root_hashes(&self) -> &[Option<Hash>] {
-        &self.0
+        &self.roots
+    }
+
+    /// Returns a checkpoint of current state of accumulator (see [`Checkpoint`])
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            roots: self.roots.clone(),
+            trees: None,
+            index: None,
+        }
+    }
+
+    /// Restores state of accumulator from given checkpoint (created by either an accumulator or a
+    /// forest)
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.roots = checkpoint.roots;
+    }
+
+    /// Applies a block to accumulator, i.e., deletes leaves with given proofs (in order, every
+    /// proof should be valid after all the previous deletions) and then inserts given values.
+    /// Returns new root hashes along with update data for remote clients and undo data of the
+    /// block.
+    ///
+    /// Returns `InvalidDeletion` error (and leaves accumulator unchanged) if any of the proofs is
+    /// not valid.
+    pub fn apply_block<T: AsRef<[u8]>>(
+        &mut self,
+        adds: &[T],
+        dels: &[Proof],
+    ) -> Result<BlockChanges, UtreexoError> {
+        let undo = self.checkpoint();
+
+        for (index, proof) in dels.iter().enumerate() {
+            if !self.delete(proof) {
+                self.restore(undo);
+                return Err(UtreexoError::InvalidDeletion { index });
+            }
+        }
+
+        let additions: Vec<Hash> = adds
+            .iter()
+            .map(|leaf_value| self.hasher.hash_leaf(leaf_value.as_ref()))
+            .collect();
+
+        for leaf_hash in additions.iter() {
+            self.insert_hash(*leaf_hash);
+        }
+
+        Ok(BlockChanges {
+            roots: self.roots.clone(),
+            update: UpdateData::new(undo.roots.clone(), dels.to_vec(), additions),
+            undo,
+        })
+    }
+
+    /// Returns summary of shape of accumulator (number of leaves, heights of trees, etc.)
+    pub fn stats(&self) -> ForestStats {
+        ForestStats::from_heights(self.roots.iter().map(Option::is_some))
+    }
+
+    /// Returns heap memory currently used by accumulator
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            nodes: self.roots.capacity() * size_of::<Option<Hash>>(),
+            ..Default::default()
+        }
+    }
+
+    /// Releases unused capacity of root hashes
+    pub fn compact(&mut self) {
+        self.roots.shrink_to_fit();
+    }
+
+    /// Removes all the roots from accumulator (hasher is kept)
+    pub fn clear(&mut self) {
+        self.roots.clear();
+    }
+
+    /// Returns compact root witness of accumulator which can be used for verifying inclusion proofs
+    pub fn root_witness(&self) -> RootWitness {
+        RootWitness::from_roots(self.roots.iter().copied())
+    }
+
+    /// Returns a single hash committing to all the roots of accumulator (see
+    /// [`RootWitness::commitment`]). Accumulators and forests with the same leaves and hasher have
+    /// the same root commitment.
+    pub fn root_commitment(&self) -> Hash {
+        self.root_witness().commitment(&self.hasher)
+    }
+
+    /// Returns a single hash committing to all the roots of accumulator, with roots bagged in given
+    /// order (see [`RootWitness::commitment_with_order`])
+    pub fn root_commitment_with_order(&self, order: BaggingOrder) -> Hash {
+        self.root_witness()
+            .commitment_with_order(&self.hasher, order)
+    }
+
+    /// Returns proof of inclusion of root hash of merkle tree of given height in root commitment of
+    /// accumulator (see [`RootWitness::prove_root`]), if there is a tree of given height
+    pub fn prove_root(&self, height: usize) -> Option<RootProof> {
+        self.root_witness().prove_root(&self.hasher, height)
+    }
+
+    /// Returns proof of inclusion of root hash of merkle tree of given height in root commitment of
+    /// accumulator with roots bagged in given order (see [`RootWitness::prove_root_with_order`]),
+    /// if there is a tree of given height
+    pub fn prove_root_with_order(&self, height: usize, order: BaggingOrder) -> Option<RootProof> {
+        self.root_witness()
+            .prove_root_with_order(&self.hasher, height, order)
+    }
+
+    /// Returns canonical binary encoding of accumulator (hasher is not encoded). Accumulators with
+    /// the same root hashes always have the same encoding, independent of how they were built.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(5 + 1 + self.roots.len() * (1 + Hash::LEN));
+
+        write_header(&mut bytes, ACCUMULATOR_MAGIC);
+        write_roots(&mut bytes, &self.roots);
+
+        bytes
+    }
+
+    /// Parses accumulator, which uses given hasher, from its canonical binary encoding
+    ///
+    /// Layout: `b"UTRA" || version (1 byte) || number of roots (1 byte) || roots`, where each root
+    /// is either `0x00` (no tree of that height) or `0x01 || root hash (32 bytes)`. Last root
+    /// should be present. Returns `UnsupportedVersion` error if state was encoded with a different
+    /// format version.
+    pub fn from_bytes_with_hasher(bytes: &[u8], hasher: H) -> Result<Self, UtreexoError> {
+        let mut reader = Reader::new(bytes);
+        reader.read_header(ACCUMULATOR_MAGIC)?;
+
+        let roots = reader.read_roots()?;
+
+        reader.finish()?;
+
+        Ok(Self { roots, hasher })
+    }
+
+    /// Creates accumulator, which uses given hasher, from canonical binary encoding of a forest
+    /// (see [`MemoryForest::from_bytes_with_hasher`](crate::MemoryForest::from_bytes_with_hasher)
+    /// for layout). Only root hashes are read, i.e., the last node of every tree, and all the other
+    /// nodes are skipped without parsing or allocating them. Number of leaves can be obtained from
+    /// [`MemoryAccumulator::root_witness`].
+    pub fn from_forest_bytes_with_hasher(bytes: &[u8], hasher: H) -> Result<Self, UtreexoError> {
+        let mut reader = Reader::new(bytes);
+        reader.read_header(FOREST_MAGIC)?;
+
+        let num_trees = usize::from(reader.read_u8()?);
+
+        if num_trees > Proof::MAX_HEIGHT + 1 {
+            return Err(UtreexoError::InvalidEncoding("Too many trees"));
+        }
+
+        let roots = (0..num_trees)
+            .map(|height| {
+                if !reader.read_presence()? {
+                    return Ok(None);
+                }
+
+                // A tree of height `h` contains `2^(h + 1) - 1` nodes and root is the last one
+                let nodes_len = 1usize
+                    .checked_shl((height + 1) as u32)
+                    .and_then(|num| (num - 1).checked_mul(Hash::LEN))
+                    .unwrap_or(usize::MAX);
+                let nodes = reader.read_bytes(nodes_len)?;
+
+                Hash::try_from(&nodes[nodes.len() - Hash::LEN..]).map(Some)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        check_last_present(&roots)?;
+        reader.finish()?;
+
+        Ok(Self { roots, hasher })
     }
 
     /// Verifies inclusion proof of a value in accumulator
     fn verify(&self, proof: &Proof) -> bool {
         let height = proof.path.height();
 
-        if self.0.len() < (height + 1) {
+        if self.roots.len() < (height + 1) {
             return false;
         }
 
-        if let Some(ref root_hash) = self.0[height] {
-            proof.verify(*root_hash)
+        if let Some(ref root_hash) = self.roots[height] {
+            proof.verify(&self.hasher, *root_hash)
         } else {
             false
         }
     }
 }
 
-impl Utreexo for MemoryAccumulator {
-    fn insert<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
-        let mut new_hash = hash_leaf(leaf_value);
+/// Calculates root hashes of merkle trees (indexed by height) from given proofs, combining proofs
+/// of leaves in the same tree level by level, so that every node is calculated only once. Returns
+/// `None` if a proof is malformed or proofs disagree on a node (i.e., on a leaf hash at the same
+/// position or on a sibling hash).
+fn batch_roots<H: Hasher>(hasher: &H, proofs: &[Proof]) -> Option<BTreeMap<usize, Hash>> {
+    let mut trees: BTreeMap<usize, Vec<&Proof>> = BTreeMap::new();
+
+    for proof in proofs {
+        if proof.sibling_hashes.len() != proof.path.height() {
+            return None;
+        }
+
+        trees.entry(proof.path.height()).or_default().push(proof);
+    }
+
+    let mut roots = BTreeMap::new();
+
+    for (height, proofs) in trees {
+        // Nodes of current level calculated from proofs (indexed by their position in level)
+        let mut nodes = BTreeMap::new();
+
+        for proof in proofs.iter() {
+            let leaf_hash = nodes
+                .entry(proof.path.leaf_index())
+                .or_insert(proof.leaf_hash);
+
+            if *leaf_hash != proof.leaf_hash {
+                return None;
+            }
+        }
+
+        for level in 0..height {
+            // Sibling hashes given by proofs at current level (indexed by their position in level)
+            let mut siblings = BTreeMap::new();
+
+            for proof in proofs.iter() {
+                let position = (proof.path.leaf_index() >> level) ^ 1;
+                let sibling_hash = proof.sibling_hashes[level];
+
+                let sibling = siblings.entry(position).or_insert(sibling_hash);
+                let node = nodes.get(&position).unwrap_or(&sibling_hash);
+
+                if *sibling != sibling_hash || *node != sibling_hash {
+                    return None;
+                }
+            }
 
-        for hash in self.0.iter_mut() {
+            let mut parents = BTreeMap::new();
+
+            for (position, hash) in nodes.iter() {
+                parents.entry(position >> 1).or_insert_with(|| {
+                    // Every node is on the path of at least one proof, so, its sibling is either
+                    // calculated or given by that proof
+                    let sibling_hash = nodes
+                        .get(&(position ^ 1))
+                        .or_else(|| siblings.get(&(position ^ 1)))
+                        .expect("Expected sibling of a node to be present in proofs");
+
+                    if position & 1 == 0 {
+                        hasher.hash_intermediate(hash, sibling_hash)
+                    } else {
+                        hasher.hash_intermediate(sibling_hash, hash)
+                    }
+                });
+            }
+
+            nodes = parents;
+        }
+
+        roots.insert(height, nodes.remove(&0)?);
+    }
+
+    Some(roots)
+}
+
+impl<H: Hasher + Clone> From<&MemoryForest<H>> for MemoryAccumulator<H> {
+    /// Creates an accumulator with root hashes (and hasher) of given forest
+    fn from(forest: &MemoryForest<H>) -> Self {
+        Self {
+            roots: forest.roots(),
+            hasher: forest.hasher().clone(),
+        }
+    }
+}
+
+impl<H: Hasher> Utreexo for MemoryAccumulator<H> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_hash(leaf_hash);
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        count(Counter::Insert, 1);
+
+        let mut new_hash = leaf_hash;
+
+        for hash in self.roots.iter_mut() {
             match hash {
                 Some(ref old_hash) => {
-                    new_hash = hash_intermediate(old_hash, &new_hash);
+                    count(Counter::Merge, 1);
+                    new_hash = self.hasher.hash_intermediate(old_hash, &new_hash);
                     *hash = None;
                 }
                 None => {
@@ -54,7 +601,7 @@ impl Utreexo for MemoryAccumulator {
             }
         }
 
-        self.0.push(Some(new_hash));
+        self.roots.push(Some(new_hash));
     }
 
     fn delete(&mut self, proof: &Proof) -> bool {
@@ -65,30 +612,61 @@ impl Utreexo for MemoryAccumulator {
 
         let height = proof.path.height();
 
+        count(Counter::Delete, 1);
+        count(Counter::Split, (height > 0).into());
+
         let mut new_hash = None;
 
         for (hash, sibling_hash) in self
-            .0
+            .roots
             .iter_mut()
             .take(height)
             .zip(proof.sibling_hashes.iter())
         {
             if let Some(ref mut new_hash) = new_hash {
-                *new_hash = hash_intermediate(sibling_hash, new_hash);
+                count(Counter::Merge, 1);
+                *new_hash = self.hasher.hash_intermediate(sibling_hash, new_hash);
             } else if hash.is_none() {
                 *hash = Some(*sibling_hash)
             } else {
+                count(Counter::Merge, 1);
                 // `unwrap()` is safe here because `None` condition was checked earlier
-                new_hash = Some(hash_intermediate(sibling_hash, &hash.unwrap()));
+                new_hash = Some(self.hasher.hash_intermediate(sibling_hash, &hash.unwrap()));
                 *hash = None;
             }
         }
 
-        self.0[height] = new_hash;
+        self.roots[height] = new_hash;
         true
     }
+
+    fn num_leaves(&self) -> u64 {
+        self.roots
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| root.is_some())
+            .map(|(height, _)| 1 << height)
+            .sum()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H> Zeroize for MemoryAccumulator<H> {
+    fn zeroize(&mut self) {
+        self.roots.zeroize();
+    }
 }
 
+#[cfg(feature = "zeroize")]
+impl<H> Drop for MemoryAccumulator<H> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H> ZeroizeOnDrop for MemoryAccumulator<H> {}
+
 #[cfg(test)]
 mod tests {
     // To test accumulator, we need forest to generate incusion proofs
@@ -124,103 +702,503 @@ mod tests {
         accumulator.insert([9; 32]);
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_none());
-        assert!(accumulator.0[1].is_some());
-        assert!(accumulator.0[2].is_none());
-        assert!(accumulator.0[3].is_some());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_none());
+        assert!(accumulator.roots[1].is_some());
+        assert!(accumulator.roots[2].is_none());
+        assert!(accumulator.roots[3].is_some());
+        assert_eq!(10, accumulator.num_leaves());
 
         // Delete a leaf
-        let proof = forest.prove(&[0; 32]);
+        let proof = forest.prove([0; 32]).unwrap();
         assert!(proof.is_some());
         let proof = proof.unwrap();
         assert!(forest.delete(&proof));
         assert!(accumulator.delete(&proof));
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_some());
-        assert!(accumulator.0[1].is_none());
-        assert!(accumulator.0[2].is_none());
-        assert!(accumulator.0[3].is_some());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_some());
+        assert!(accumulator.roots[1].is_none());
+        assert!(accumulator.roots[2].is_none());
+        assert!(accumulator.roots[3].is_some());
 
         // Delete a leaf
-        let proof = forest.prove(&[1; 32]);
+        let proof = forest.prove([1; 32]).unwrap();
         assert!(proof.is_some());
         let proof = proof.unwrap();
         assert!(forest.delete(&proof));
         assert!(accumulator.delete(&proof));
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_none());
-        assert!(accumulator.0[1].is_none());
-        assert!(accumulator.0[2].is_none());
-        assert!(accumulator.0[3].is_some());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_none());
+        assert!(accumulator.roots[1].is_none());
+        assert!(accumulator.roots[2].is_none());
+        assert!(accumulator.roots[3].is_some());
 
         // Delete a leaf
-        let proof = forest.prove(&[2; 32]);
+        let proof = forest.prove([2; 32]).unwrap();
         assert!(proof.is_some());
         let proof = proof.unwrap();
         assert!(forest.delete(&proof));
         assert!(accumulator.delete(&proof));
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_some());
-        assert!(accumulator.0[1].is_some());
-        assert!(accumulator.0[2].is_some());
-        assert!(accumulator.0[3].is_none());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_some());
+        assert!(accumulator.roots[1].is_some());
+        assert!(accumulator.roots[2].is_some());
+        assert!(accumulator.roots[3].is_none());
 
         // Delete a leaf
-        let proof = forest.prove(&[3; 32]);
+        let proof = forest.prove([3; 32]).unwrap();
         assert!(proof.is_some());
         let proof = proof.unwrap();
         assert!(forest.delete(&proof));
         assert!(accumulator.delete(&proof));
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_none());
-        assert!(accumulator.0[1].is_some());
-        assert!(accumulator.0[2].is_some());
-        assert!(accumulator.0[3].is_none());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_none());
+        assert!(accumulator.roots[1].is_some());
+        assert!(accumulator.roots[2].is_some());
+        assert!(accumulator.roots[3].is_none());
 
         // Add a leaf
         forest.insert([0; 32]);
         accumulator.insert([0; 32]);
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_some());
-        assert!(accumulator.0[1].is_some());
-        assert!(accumulator.0[2].is_some());
-        assert!(accumulator.0[3].is_none());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_some());
+        assert!(accumulator.roots[1].is_some());
+        assert!(accumulator.roots[2].is_some());
+        assert!(accumulator.roots[3].is_none());
 
         // Delete a leaf
-        let proof = forest.prove(&[0; 32]);
+        let proof = forest.prove([0; 32]).unwrap();
         assert!(proof.is_some());
         let proof = proof.unwrap();
         assert!(forest.delete(&proof));
         assert!(accumulator.delete(&proof));
 
         // Checking distribution of trees in merkle forest
-        assert_eq!(4, accumulator.0.len());
-        assert!(accumulator.0[0].is_none());
-        assert!(accumulator.0[1].is_some());
-        assert!(accumulator.0[2].is_some());
-        assert!(accumulator.0[3].is_none());
+        assert_eq!(4, accumulator.roots.len());
+        assert!(accumulator.roots[0].is_none());
+        assert!(accumulator.roots[1].is_some());
+        assert!(accumulator.roots[2].is_some());
+        assert!(accumulator.roots[3].is_none());
 
         // Checking all the root hashes of trees in merkle forest and accumulator
-        for (hash, tree) in accumulator.root_hashes().iter().zip(forest.trees().iter()) {
-            match hash {
-                None => assert!(tree.is_none()),
-                Some(ref hash) => {
-                    assert!(tree.is_some());
-                    let tree_hash = tree.as_ref().unwrap().root_hash();
-                    assert_eq!(hash, tree_hash);
-                }
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+    }
+
+    #[test]
+    fn check_accumulator_insert_hash() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            accumulator.insert_value([value; 32]);
+            forest.insert_hash(Blake3.hash_leaf(&[value; 32]));
+        }
+
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+
+        let leaf_hash = Blake3.hash_leaf(&[5; 32]);
+        accumulator.insert_hash(leaf_hash);
+        forest.insert_value([5; 32]);
+
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+
+        let proof = forest.prove([5; 32]).unwrap().unwrap();
+        assert_eq!(&leaf_hash, proof.leaf_hash());
+        assert!(accumulator.verify(&proof));
+    }
+
+    #[test]
+    fn check_accumulator_clear() {
+        let mut accumulator = MemoryAccumulator::new_keyed([7; 32]);
+
+        for value in 0..5u8 {
+            accumulator.insert([value; 32]);
+        }
+
+        accumulator.clear();
+
+        assert!(accumulator.is_empty());
+        assert_eq!(MemoryAccumulator::new_keyed([7; 32]), accumulator);
+    }
+
+    #[test]
+    fn check_accumulator_from_forest() {
+        let mut forest = MemoryForest::new_keyed([7; 32]);
+
+        for value in 0..11u8 {
+            forest.insert([value; 32]);
+        }
+
+        let proof = forest.prove([3; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+
+        let mut accumulator = MemoryAccumulator::from(&forest);
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+
+        forest.insert([20; 32]);
+        accumulator.insert([20; 32]);
+
+        let proof = forest.prove([5; 32]).unwrap().unwrap();
+        assert!(accumulator.delete(&proof));
+    }
+
+    #[test]
+    fn check_accumulator_from_roots() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut accumulator = MemoryAccumulator::from_roots(forest.roots()).unwrap();
+        assert_eq!(6, accumulator.num_leaves());
+
+        let proof = forest.prove([1; 32]).unwrap().unwrap();
+        assert!(accumulator.delete(&proof));
+
+        let max_num_roots = Proof::MAX_HEIGHT + 1;
+
+        assert!(MemoryAccumulator::from_roots(vec![None; max_num_roots]).is_ok());
+        assert_eq!(
+            Err(UtreexoError::TooManyRoots {
+                num_roots: max_num_roots + 1,
+                max_num_roots
+            }),
+            MemoryAccumulator::from_roots(vec![None; max_num_roots + 1])
+        );
+    }
+
+    #[test]
+    fn check_keyed_accumulator() {
+        let mut accumulator = MemoryAccumulator::new_keyed([7; 32]);
+        let mut forest = MemoryForest::new_keyed([7; 32]);
+        let mut unkeyed_forest = MemoryForest::new();
+
+        for value in 0..4u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+            unkeyed_forest.insert([value; 32]);
+        }
+
+        // Proofs generated with a different key should not be accepted
+        let proof = unkeyed_forest.prove([0; 32]).unwrap().unwrap();
+        assert!(!accumulator.delete(&proof));
+
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert!(accumulator.delete(&proof));
+    }
+
+    #[test]
+    fn check_salted_accumulator() {
+        let mut accumulator = MemoryAccumulator::new_salted([7; 32]);
+        let mut forest = MemoryForest::new_salted([7; 32]);
+
+        for value in 0..5u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+        let values: Vec<[u8; 32]> = (0..5u8).map(|value| [value; 32]).collect();
+        assert_ne!(MemoryForest::from_leaves(&values).roots(), forest.roots());
+
+        // Salt is a part of serialized state
+        let bytes = accumulator.to_salted_bytes();
+        assert_eq!(&[7; 32][..], &bytes[..32]);
+        assert_eq!(
+            Ok(&accumulator),
+            MemoryAccumulator::from_salted_bytes(&bytes).as_ref()
+        );
+
+        let restored = MemoryForest::from_salted_bytes(&forest.to_salted_bytes()).unwrap();
+        assert_eq!(&[7; 32], restored.salt());
+        assert_eq!(forest, restored);
+
+        let proof = restored.prove([3; 32]).unwrap().unwrap();
+        assert!(accumulator.delete(&proof));
+
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unexpected end of input")),
+            MemoryAccumulator::from_salted_bytes(&[7; 31])
+        );
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn check_accumulator_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        for value in 0..3u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        let mut leaf_hash = Blake3.hash_leaf(&[1; 32]);
+        leaf_hash.zeroize();
+        assert_eq!(leaf_hash, [0; 32]);
+
+        let mut proof = forest.prove([1; 32]).unwrap().unwrap();
+        proof.zeroize();
+        assert_eq!(
+            Ok(proof),
+            Proof::from_parts([0; 32].into(), Vec::new(), Vec::new())
+        );
+
+        accumulator.zeroize();
+        assert_eq!(MemoryAccumulator::new(), accumulator);
+    }
+
+    #[test]
+    fn check_accumulator_from_leaves() {
+        let values: Vec<[u8; 2]> = (0..13u8).map(|value| [value, 0]).collect();
+
+        let mut expected = MemoryAccumulator::new();
+
+        for value in values.iter() {
+            expected.insert(value);
+        }
+
+        assert_eq!(expected, MemoryAccumulator::from_leaves(&values));
+    }
+
+    #[test]
+    fn check_accumulator_insert_batch() {
+        let mut expected = MemoryAccumulator::new();
+        let mut accumulator = MemoryAccumulator::new();
+
+        for batch in [5u8, 0, 1, 6, 19].iter() {
+            let values: Vec<[u8; 2]> = (0..*batch).map(|value| [value, *batch]).collect();
+
+            for value in values.iter() {
+                expected.insert(value);
             }
+
+            accumulator.insert_batch(&values);
+            assert_eq!(expected, accumulator);
+        }
+    }
+
+    #[test]
+    fn check_accumulator_verify_batch() {
+        let values: Vec<[u8; 1]> = (0..13u8).map(|value| [value]).collect();
+
+        let forest = MemoryForest::from_leaves(&values);
+        let accumulator = MemoryAccumulator::from_leaves(&values);
+
+        let proofs: Vec<Proof> = [1u8, 2, 3, 6, 12]
+            .iter()
+            .map(|value| forest.prove([*value]).unwrap().unwrap())
+            .collect();
+
+        assert!(accumulator.verify_batch(&proofs));
+        assert!(accumulator.verify_batch(&[]));
+        assert!(!MemoryAccumulator::new().verify_batch(&proofs));
+
+        // Sibling hash of leaf 1 is leaf 0, which is not in batch
+        let mut tampered = proofs.clone();
+        tampered[0].sibling_hashes[0] = Hash::from([9; 32]);
+        assert!(!accumulator.verify_batch(&tampered));
+
+        // Sibling hash of leaf 2 is leaf 3, which is calculated from proof of leaf 3 (so, the
+        // tampered sibling hash is never used for calculating root hash)
+        let mut tampered = proofs.clone();
+        tampered[1].sibling_hashes[0] = Hash::from([9; 32]);
+        assert!(!accumulator.verify_batch(&tampered));
+
+        // Proofs of different leaves at the same position
+        let mut tampered = proofs.clone();
+        tampered[3].leaf_hash = Hash::from([9; 32]);
+        tampered.push(proofs[3].clone());
+        assert!(!accumulator.verify_batch(&tampered));
+    }
+
+    #[test]
+    fn check_accumulator_delete_batch() {
+        let values: Vec<[u8; 1]> = (0..13u8).map(|value| [value]).collect();
+        let deleted = [6u8, 1, 12, 2, 3];
+
+        let mut forest = MemoryForest::from_leaves(&values);
+        let accumulator = MemoryAccumulator::from_leaves(&values);
+
+        let proofs: Vec<Proof> = deleted
+            .iter()
+            .map(|value| forest.prove([*value]).unwrap().unwrap())
+            .collect();
+
+        let mut batch = accumulator.clone();
+        let freed = batch.delete_batch(&proofs).unwrap();
+
+        // Leaves 1, 2, 3 and 6 are in tree of height 3 and leaf 12 is the tree of height 0
+        assert_eq!(
+            vec![(3, 6), (3, 1), (0, 0), (3, 2), (3, 3)],
+            freed
+                .iter()
+                .map(|position| (position.height, position.index))
+                .collect::<Vec<_>>()
+        );
+
+        // Values are deleted in given order
+        for value in deleted.iter() {
+            let proof = forest.prove([*value]).unwrap().unwrap();
+            assert!(forest.delete(&proof));
+        }
+        assert_eq!(MemoryAccumulator::from(&forest), batch);
+
+        // New leaves are added after the remaining leaves (freed positions are not filled)
+        let adds = [[13], [14]];
+
+        batch.insert_batch(&adds);
+        for value in adds.iter() {
+            forest.insert(value);
         }
+        assert_eq!(MemoryAccumulator::from(&forest), batch);
+        assert_eq!(10, batch.num_leaves());
+
+        let mut repeated = proofs.clone();
+        repeated.push(proofs[2].clone());
+
+        let mut unchanged = accumulator.clone();
+        assert_eq!(
+            Err(UtreexoError::InvalidDeletion { index: 5 }),
+            unchanged.delete_batch(&repeated)
+        );
+        assert_eq!(accumulator, unchanged);
+
+        let mut tampered = proofs;
+        tampered[1].sibling_hashes[0] = Hash::from([9; 32]);
+        assert_eq!(
+            Err(UtreexoError::InvalidDeletion { index: 1 }),
+            unchanged.delete_batch(&tampered)
+        );
+        assert_eq!(accumulator, unchanged);
+    }
+
+    #[test]
+    fn check_accumulator_with_capacity() {
+        let values: Vec<[u8; 32]> = (0..100u8).map(|value| [value; 32]).collect();
+        let mut accumulator = MemoryAccumulator::with_capacity(values.len());
+
+        for value in values.iter() {
+            accumulator.insert(value);
+        }
+
+        assert_eq!(7, accumulator.roots.capacity());
+        assert_eq!(MemoryAccumulator::from_leaves(&values), accumulator);
+    }
+
+    #[test]
+    fn check_accumulator_checkpoint() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        let checkpoint = accumulator.checkpoint();
+        assert_eq!(5, checkpoint.num_leaves());
+        assert!(!checkpoint.has_trees());
+
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert!(accumulator.delete(&proof));
+        accumulator.insert([9; 32]);
+
+        accumulator.restore(checkpoint);
+        assert!(accumulator.delete(&proof));
+
+        accumulator.restore(forest.checkpoint());
+        assert!(accumulator.delete(&proof));
+    }
+
+    #[test]
+    fn check_accumulator_bytes() {
+        let mut accumulator = MemoryAccumulator::new();
+
+        for value in 0..5u8 {
+            accumulator.insert([value; 32]);
+        }
+
+        let bytes = accumulator.to_bytes();
+
+        assert_eq!(5 + 1 + 3 + 2 * 32, bytes.len());
+        assert_eq!(b"UTRA\x01\x03\x01", &bytes[..7]);
+        assert_eq!(Ok(accumulator), MemoryAccumulator::from_bytes(&bytes));
+
+        let mut invalid = bytes;
+        invalid[6] = 2;
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Invalid presence marker")),
+            MemoryAccumulator::from_bytes(&invalid)
+        );
+
+        // State reached through deletions is encoded in the same way as the state built directly
+        let mut forest = MemoryForest::new();
+        let mut accumulator = MemoryAccumulator::new();
+
+        for value in 1..3u8 {
+            forest.insert([value; 32]);
+            accumulator.insert([value; 32]);
+        }
+
+        assert!(accumulator.delete(&forest.prove([2; 32]).unwrap().unwrap()));
+        assert_eq!(2, accumulator.roots.len());
+
+        let mut expected = MemoryAccumulator::new();
+        expected.insert([1; 32]);
+
+        assert_eq!(expected.to_bytes(), accumulator.to_bytes());
+        assert_eq!(expected, accumulator);
+        assert_eq!(
+            Ok(accumulator),
+            MemoryAccumulator::from_bytes(&expected.to_bytes())
+        );
+
+        let mut invalid = expected.to_bytes();
+        invalid[5] = 2;
+        invalid.push(0);
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Last root should be present")),
+            MemoryAccumulator::from_bytes(&invalid)
+        );
+    }
+
+    #[test]
+    fn check_accumulator_from_forest_bytes() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        for value in 0..11u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        let bytes = forest.to_bytes();
+
+        assert_eq!(
+            Ok(accumulator),
+            MemoryAccumulator::from_forest_bytes(&bytes)
+        );
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unexpected end of input")),
+            MemoryAccumulator::from_forest_bytes(&bytes[..bytes.len() - 1])
+        );
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Invalid magic bytes")),
+            MemoryAccumulator::from_forest_bytes(&MemoryAccumulator::new().to_bytes())
+        );
     }
 }
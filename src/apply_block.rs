@@ -0,0 +1,50 @@
+use crate::{
+    BlockChanges, Checkpoint, Hasher, MemoryAccumulator, MemoryForest, Proof, Utreexo, UtreexoError,
+};
+
+/// Trait for accumulators (and forests) which can apply whole blocks and undo them (see
+/// [`ReorgManager`](crate::ReorgManager))
+pub trait ApplyBlock: Utreexo {
+    /// Deletes leaves with given proofs (in order, every proof should be valid after all the
+    /// previous deletions) and then inserts given values. Returns new root hashes along with update
+    /// data for remote clients and undo data of the block. Returns `InvalidDeletion` error (and
+    /// leaves state unchanged) if any of the proofs is not valid.
+    fn apply_block<T: AsRef<[u8]>>(
+        &mut self,
+        adds: &[T],
+        dels: &[Proof],
+    ) -> Result<BlockChanges, UtreexoError>;
+
+    /// Undoes a block by restoring undo data returned while applying it (i.e.,
+    /// [`BlockChanges::undo`]). Returns an error if undo data cannot be restored.
+    fn undo_block(&mut self, undo: Checkpoint) -> Result<(), UtreexoError>;
+}
+
+impl<H: Hasher> ApplyBlock for MemoryAccumulator<H> {
+    fn apply_block<T: AsRef<[u8]>>(
+        &mut self,
+        adds: &[T],
+        dels: &[Proof],
+    ) -> Result<BlockChanges, UtreexoError> {
+        MemoryAccumulator::apply_block(self, adds, dels)
+    }
+
+    fn undo_block(&mut self, undo: Checkpoint) -> Result<(), UtreexoError> {
+        self.restore(undo);
+        Ok(())
+    }
+}
+
+impl<H: Hasher> ApplyBlock for MemoryForest<H> {
+    fn apply_block<T: AsRef<[u8]>>(
+        &mut self,
+        adds: &[T],
+        dels: &[Proof],
+    ) -> Result<BlockChanges, UtreexoError> {
+        MemoryForest::apply_block(self, adds, dels)
+    }
+
+    fn undo_block(&mut self, undo: Checkpoint) -> Result<(), UtreexoError> {
+        self.restore(undo)
+    }
+}
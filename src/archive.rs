@@ -0,0 +1,179 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    Blake3, ForestSnapshot, Hash, Hasher, MemoryForest, Proof, Prover, Utreexo, UtreexoError,
+};
+
+/// Merkle forest which also keeps its state as of recorded block heights, so, it can generate and
+/// verify proofs against the roots of any recorded block height, not just the tip (e.g., for
+/// answering "was this leaf present at block height `h`" queries)
+///
+/// State of every recorded block height is kept as a [`ForestSnapshot`], which shares unchanged
+/// trees with the forest and other snapshots. So, the memory used by history is proportional to the
+/// number of nodes changed between recorded heights (along with a copy of index of leaves for every
+/// recorded height). Old history can be dropped using [`ArchiveForest::prune_below`].
+#[derive(Debug, Default, Clone)]
+pub struct ArchiveForest<H = Blake3> {
+    /// Merkle forest at the tip
+    forest: MemoryForest<H>,
+    /// Snapshots of forest indexed by block height
+    history: BTreeMap<u64, ForestSnapshot<H>>,
+}
+
+impl ArchiveForest {
+    /// Creates a new instance of archive forest
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<H: Hasher + Clone> ArchiveForest<H> {
+    /// Creates a new instance of archive forest which uses given hasher
+    pub fn with_hasher(hasher: H) -> Self {
+        Self {
+            forest: MemoryForest::with_hasher(hasher),
+            history: Default::default(),
+        }
+    }
+
+    /// Returns merkle forest at the tip
+    pub fn forest(&self) -> &MemoryForest<H> {
+        &self.forest
+    }
+
+    /// Records current state of forest as the state of given block height (replacing any existing
+    /// state recorded for the same height)
+    pub fn record(&mut self, block_height: u64) {
+        self.history.insert(block_height, self.forest.snapshot());
+    }
+
+    /// Returns all the recorded block heights (in increasing order)
+    pub fn recorded_heights(&self) -> impl Iterator<Item = u64> + '_ {
+        self.history.keys().copied()
+    }
+
+    /// Returns snapshot of forest as of given block height, if recorded
+    pub fn snapshot_at(&self, block_height: u64) -> Option<&ForestSnapshot<H>> {
+        self.history.get(&block_height)
+    }
+
+    /// Returns root hashes of all the trees in merkle forest as of given block height (indexed by
+    /// height), if recorded
+    pub fn roots_at(&self, block_height: u64) -> Option<Vec<Option<Hash>>> {
+        self.snapshot_at(block_height).map(ForestSnapshot::roots)
+    }
+
+    /// Generates inclusion proof of a value against the roots as of given block height. Returns
+    /// `None` if the value was not present at that height and `UnrecordedBlockHeight` error if the
+    /// state of given block height was not recorded.
+    pub fn prove_at_block<T: AsRef<[u8]>>(
+        &self,
+        block_height: u64,
+        leaf_value: T,
+    ) -> Result<Option<Proof>, UtreexoError> {
+        self.recorded(block_height)?.prove_value(leaf_value)
+    }
+
+    /// Verifies inclusion proof of a value against the roots as of given block height. Returns
+    /// `UnrecordedBlockHeight` error if the state of given block height was not recorded.
+    pub fn verify_at_block(&self, block_height: u64, proof: &Proof) -> Result<bool, UtreexoError> {
+        let roots = self.recorded(block_height)?.roots();
+        Ok(proof.verify_roots(self.forest.hasher(), &roots))
+    }
+
+    /// Removes state of all the block heights below given block height
+    pub fn prune_below(&mut self, block_height: u64) {
+        self.history = self.history.split_off(&block_height);
+    }
+
+    /// Returns snapshot of forest as of given block height or `UnrecordedBlockHeight` error
+    fn recorded(&self, block_height: u64) -> Result<&ForestSnapshot<H>, UtreexoError> {
+        self.snapshot_at(block_height)
+            .ok_or(UtreexoError::UnrecordedBlockHeight { block_height })
+    }
+}
+
+impl<H: Hasher + Clone> Utreexo for ArchiveForest<H> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        self.forest.insert_value(leaf_value)
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.forest.insert_hash(leaf_hash)
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        self.forest.delete(proof)
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.forest.num_leaves()
+    }
+}
+
+impl<H: Hasher + Clone> Prover for ArchiveForest<H> {
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        self.forest.prove_value(leaf_value)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.forest.prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        self.forest.prove_batch(leaf_hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_archive_forest() {
+        let mut archive = ArchiveForest::new();
+
+        for block_height in 0..4u8 {
+            for value in 0..5u8 {
+                archive.insert([block_height, value]);
+            }
+
+            let proof = archive.prove([block_height, 0]).unwrap().unwrap();
+            assert!(archive.delete(&proof));
+
+            archive.record(u64::from(block_height));
+        }
+
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            archive.recorded_heights().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(archive.forest().roots()), archive.roots_at(3));
+
+        // Leaf inserted in the first block and deleted after the last recorded block
+        let proof = archive.prove([0, 1]).unwrap().unwrap();
+        assert!(archive.delete(&proof));
+        assert!(archive.prove([0, 1]).unwrap().is_none());
+
+        let proof = archive.prove_at_block(0, [0, 1]).unwrap().unwrap();
+        assert_eq!(Ok(true), archive.verify_at_block(0, &proof));
+        assert_eq!(Ok(false), archive.verify_at_block(3, &proof));
+
+        // Leaves deleted in (or inserted after) a block are not present as of that block
+        assert!(archive.prove_at_block(1, [1, 0]).unwrap().is_none());
+        assert!(archive.prove_at_block(0, [1, 1]).unwrap().is_none());
+
+        let proof = archive.prove_at_block(2, [2, 3]).unwrap().unwrap();
+        assert_eq!(Ok(true), archive.verify_at_block(2, &proof));
+
+        archive.prune_below(2);
+        assert_eq!(
+            Err(UtreexoError::UnrecordedBlockHeight { block_height: 1 }),
+            archive.prove_at_block(1, [0, 1])
+        );
+        assert_eq!(
+            Err(UtreexoError::UnrecordedBlockHeight { block_height: 4 }),
+            archive.verify_at_block(4, &proof)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Command line tool for working with forest state and proof files (requires `cli` feature)
+//!
+//! State files contain canonical binary encoding of a [`MemoryForest`] and proof files contain
+//! canonical binary encoding of a [`Proof`]. Leaf values are given as strings (their UTF-8 bytes
+//! are hashed) or, with `--hash`, as hex encoded leaf hashes.
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use clap::{Parser, Subcommand};
+use utreexo::{Blake3, Hash, Hasher, MemoryForest, Proof, Prover, Utreexo};
+
+/// Inspect and modify utreexo forest state files
+#[derive(Debug, Parser)]
+#[command(name = "utreexo")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Creates a new state file with an empty forest
+    Create {
+        /// Path of state file
+        state: PathBuf,
+        /// Overwrite state file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Inserts leaves in forest
+    Insert {
+        /// Path of state file
+        state: PathBuf,
+        /// Leaf values (or hex encoded leaf hashes with `--hash`)
+        #[arg(required = true)]
+        leaves: Vec<String>,
+        /// Treat leaves as hex encoded leaf hashes
+        #[arg(long)]
+        hash: bool,
+    },
+    /// Deletes leaves using their proof files
+    Delete {
+        /// Path of state file
+        state: PathBuf,
+        /// Paths of proof files (applied in order)
+        #[arg(required = true)]
+        proofs: Vec<PathBuf>,
+    },
+    /// Writes proof of a leaf to a proof file
+    Prove {
+        /// Path of state file
+        state: PathBuf,
+        /// Leaf value (or hex encoded leaf hash with `--hash`)
+        leaf: String,
+        /// Path of proof file to write
+        proof: PathBuf,
+        /// Treat leaf as hex encoded leaf hash
+        #[arg(long)]
+        hash: bool,
+    },
+    /// Verifies a proof file against roots of forest (exits with status 1 if proof is not valid)
+    Verify {
+        /// Path of state file
+        state: PathBuf,
+        /// Path of proof file
+        proof: PathBuf,
+    },
+    /// Prints root hashes of merkle trees in forest
+    Roots {
+        /// Path of state file
+        state: PathBuf,
+    },
+    /// Prints merkle trees in forest as ASCII trees
+    Render {
+        /// Path of state file
+        state: PathBuf,
+        /// Number of bytes of every node hash to print
+        #[arg(long, default_value_t = 4)]
+        hash_len: usize,
+    },
+    /// Prints summary of shape and memory usage of forest
+    Stats {
+        /// Path of state file
+        state: PathBuf,
+    },
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse().command) {
+        eprintln!("Error: {}", err);
+        process::exit(2);
+    }
+}
+
+fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Create { state, force } => {
+            if state.exists() && !force {
+                return Err(format!("{} already exists", state.display()).into());
+            }
+
+            write_forest(&state, &MemoryForest::new())?;
+        }
+        Command::Insert {
+            state,
+            leaves,
+            hash,
+        } => {
+            let mut forest = read_forest(&state)?;
+
+            for leaf in leaves {
+                let leaf_hash = leaf_hash(&leaf, hash)?;
+
+                if forest.contains_hash(&leaf_hash) {
+                    return Err(format!("Leaf {} is already present", leaf_hash).into());
+                }
+
+                forest.insert_hash(leaf_hash);
+            }
+
+            write_forest(&state, &forest)?;
+        }
+        Command::Delete { state, proofs } => {
+            let mut forest = read_forest(&state)?;
+
+            for path in proofs {
+                let proof = Proof::from_bytes(&fs::read(&path)?)?;
+
+                if !forest.delete(&proof) {
+                    return Err(format!("Proof in {} is not valid", path.display()).into());
+                }
+            }
+
+            write_forest(&state, &forest)?;
+        }
+        Command::Prove {
+            state,
+            leaf,
+            proof,
+            hash,
+        } => {
+            let forest = read_forest(&state)?;
+            let leaf_hash = leaf_hash(&leaf, hash)?;
+
+            match forest.prove_hash(&leaf_hash)? {
+                Some(leaf_proof) => fs::write(&proof, leaf_proof.to_bytes())?,
+                None => return Err(format!("Leaf {} is not present", leaf_hash).into()),
+            }
+        }
+        Command::Verify { state, proof } => {
+            let forest = read_forest(&state)?;
+            let proof = Proof::from_bytes(&fs::read(&proof)?)?;
+
+            if forest.root_witness().verify(&Blake3, &proof) {
+                println!("valid");
+            } else {
+                println!("invalid");
+                process::exit(1);
+            }
+        }
+        Command::Roots { state } => {
+            let forest = read_forest(&state)?;
+
+            for (height, root) in forest.roots().iter().enumerate() {
+                if let Some(root_hash) = root {
+                    println!("{} {}", height, root_hash);
+                }
+            }
+        }
+        Command::Render { state, hash_len } => {
+            print!("{}", read_forest(&state)?.render(hash_len));
+        }
+        Command::Stats { state } => {
+            let forest = read_forest(&state)?;
+            let stats = forest.stats();
+
+            println!("leaves: {}", stats.num_leaves);
+            println!("trees: {}", stats.num_trees);
+            println!("nodes: {}", stats.num_nodes);
+            match stats.tallest_tree {
+                Some(height) => println!("tallest tree: {}", height),
+                None => println!("tallest tree: -"),
+            }
+            println!("memory: {} bytes", forest.memory_usage().total());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads forest from given state file (intermediate hashes are verified)
+fn read_forest(path: &Path) -> Result<MemoryForest, Box<dyn Error>> {
+    Ok(MemoryForest::from_untrusted_bytes(&fs::read(path)?)?)
+}
+
+/// Writes forest to given state file
+fn write_forest(path: &Path, forest: &MemoryForest) -> Result<(), Box<dyn Error>> {
+    Ok(fs::write(path, forest.to_bytes())?)
+}
+
+/// Returns hash of given leaf value, or parses it as a hex encoded leaf hash if `is_hash` is `true`
+fn leaf_hash(leaf: &str, is_hash: bool) -> Result<Hash, Box<dyn Error>> {
+    if is_hash {
+        Ok(leaf.parse()?)
+    } else {
+        Ok(Blake3.hash_leaf(leaf.as_bytes()))
+    }
+}
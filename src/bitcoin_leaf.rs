@@ -0,0 +1,552 @@
+//! Leaf hashes of bitcoin UTXOs as computed by utreexod (requires `bitcoin-leaf` feature)
+//!
+//! Every UTXO is committed to the accumulator by hashing its [`LeafData`], serialized as:
+//!
+//! `block hash (32 bytes) || txid (32 bytes) || vout (4 bytes) || header code (4 bytes) || amount
+//! (8 bytes) || length of scriptPubKey (compact size) || scriptPubKey`
+//!
+//! where header code is `block height << 1 | coinbase flag`, hashes are in their internal byte
+//! order (i.e., reversed compared to how they are usually displayed) and all the integers are
+//! little endian (as in bitcoin's own serialization).
+//!
+//! Leaf hash is `SHA512_256(tag || tag || serialized leaf data)`, where `tag` is
+//! `SHA512_256("UtreexoV1")`, and intermediate hashes are `SHA512_256(left || right)`. Use
+//! [`UtreexodHasher`] as hasher of an accumulator (or a forest) to get the same root hashes as
+//! utreexod:
+//!
+//! ```
+//! use utreexo::{
+//!     bitcoin_leaf::{LeafData, OutPoint, UtreexodHasher},
+//!     MemoryAccumulator, Utreexo,
+//! };
+//!
+//! let leaf_data = LeafData {
+//!     block_hash: [1; 32],
+//!     outpoint: OutPoint {
+//!         txid: [2; 32],
+//!         vout: 0,
+//!     },
+//!     height: 100,
+//!     is_coinbase: false,
+//!     amount: 50_000,
+//!     script_pubkey: vec![0x51],
+//! };
+//!
+//! let mut accumulator = MemoryAccumulator::with_hasher(UtreexodHasher);
+//! accumulator.insert_hash(leaf_data.leaf_hash());
+//! ```
+//!
+//! With `bitcoin` feature, leaf data can be created from `rust-bitcoin` types and leaves added and
+//! spent by a block can be derived using [`block_leaves`].
+#[cfg(feature = "bitcoin")]
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+#[cfg(feature = "bitcoin")]
+use bitcoin::{consensus::Encodable, hashes::Hash as _, Block, BlockHash, TxOut};
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512_256};
+
+use crate::{
+    metrics::{count, Counter},
+    Hash, Hasher, LeafEncode,
+};
+
+/// Name of tag (its hash is prepended twice to leaf data) used for domain separation of leaf hashes
+const LEAF_TAG: &[u8] = b"UtreexoV1";
+
+/// Maximum size of a script which can be spent (larger scripts are never added to accumulator)
+#[cfg(feature = "bitcoin")]
+const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Reference to an output of a bitcoin transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct OutPoint {
+    /// Id of transaction (in internal byte order)
+    pub txid: [u8; 32],
+    /// Index of output in transaction
+    pub vout: u32,
+}
+
+/// Data of an unspent transaction output committed to the accumulator
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct LeafData {
+    /// Hash of block containing the transaction (in internal byte order)
+    pub block_hash: [u8; 32],
+    /// Output being committed
+    pub outpoint: OutPoint,
+    /// Height of block containing the transaction (must be less than `2^31`)
+    pub height: u32,
+    /// Whether the transaction is a coinbase transaction
+    pub is_coinbase: bool,
+    /// Value of output in satoshis
+    pub amount: u64,
+    /// Locking script of output
+    pub script_pubkey: Vec<u8>,
+}
+
+impl LeafData {
+    /// Returns serialization of leaf data (see [module documentation](self) for layout)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(89 + self.script_pubkey.len());
+
+        bytes.extend_from_slice(&self.block_hash);
+        bytes.extend_from_slice(&self.outpoint.txid);
+        bytes.extend_from_slice(&self.outpoint.vout.to_le_bytes());
+        bytes.extend_from_slice(&self.header_code().to_le_bytes());
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        write_compact_size(&mut bytes, self.script_pubkey.len() as u64);
+        bytes.extend_from_slice(&self.script_pubkey);
+
+        bytes
+    }
+
+    /// Returns leaf hash of leaf data (same as `UtreexodHasher.hash_leaf(&leaf_data.to_bytes())`)
+    pub fn leaf_hash(&self) -> Hash {
+        UtreexodHasher.hash_leaf(&self.to_bytes())
+    }
+
+    /// Returns block height and coinbase flag packed in a single integer
+    fn header_code(&self) -> u32 {
+        (self.height << 1) | u32::from(self.is_coinbase)
+    }
+
+    /// Creates leaf data of an output created in block with given hash and height
+    #[cfg(feature = "bitcoin")]
+    pub fn from_output(
+        block_hash: BlockHash,
+        height: u32,
+        is_coinbase: bool,
+        outpoint: bitcoin::OutPoint,
+        output: &TxOut,
+    ) -> Self {
+        Self {
+            block_hash: block_hash.to_byte_array(),
+            outpoint: outpoint.into(),
+            height,
+            is_coinbase,
+            amount: output.value.to_sat(),
+            script_pubkey: output.script_pubkey.to_bytes(),
+        }
+    }
+}
+
+impl LeafEncode for LeafData {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_bytes());
+    }
+}
+
+#[cfg(feature = "bitcoin")]
+impl From<bitcoin::OutPoint> for OutPoint {
+    fn from(outpoint: bitcoin::OutPoint) -> Self {
+        Self {
+            txid: outpoint.txid.to_byte_array(),
+            vout: outpoint.vout,
+        }
+    }
+}
+
+/// Outpoints are encoded using consensus encoding (`txid || vout`)
+#[cfg(feature = "bitcoin")]
+impl LeafEncode for bitcoin::OutPoint {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        self.consensus_encode(bytes)
+            .expect("Writing to a vector should never fail");
+    }
+}
+
+/// Outputs are encoded using consensus encoding (`amount || length of scriptPubKey ||
+/// scriptPubKey`)
+#[cfg(feature = "bitcoin")]
+impl LeafEncode for TxOut {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        self.consensus_encode(bytes)
+            .expect("Writing to a vector should never fail");
+    }
+}
+
+/// Leaves added to and spent from accumulator by a block
+#[cfg(feature = "bitcoin")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlockLeaves {
+    /// Leaf data of outputs created by block (in order of their appearance in block)
+    pub additions: Vec<LeafData>,
+    /// Outputs spent by block (in order of their appearance in block). Leaf data (and proofs) of
+    /// these outputs are not present in block and should be looked up in UTXO set.
+    pub deletions: Vec<bitcoin::OutPoint>,
+}
+
+#[cfg(feature = "bitcoin")]
+impl BlockLeaves {
+    /// Returns leaf hashes of outputs created by block
+    pub fn addition_hashes(&self) -> Vec<Hash> {
+        self.additions.iter().map(LeafData::leaf_hash).collect()
+    }
+}
+
+/// Returns leaves added to and spent from accumulator by given block at given height (same as
+/// utreexod)
+///
+/// Outputs which can never be spent (`OP_RETURN` outputs and outputs with scripts larger than
+/// `10_000` bytes) are not added to accumulator, and outputs spent in the same block in which they
+/// are created are neither added nor deleted.
+#[cfg(feature = "bitcoin")]
+pub fn block_leaves(block: &Block, height: u32) -> BlockLeaves {
+    let block_hash = block.block_hash();
+
+    let spent = block
+        .txdata
+        .iter()
+        .filter(|tx| !tx.is_coinbase())
+        .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+        .collect::<BTreeSet<_>>();
+
+    let mut created = BTreeSet::new();
+    let mut additions = Vec::new();
+
+    for tx in block.txdata.iter() {
+        let txid = tx.compute_txid();
+
+        for (vout, output) in tx.output.iter().enumerate() {
+            let outpoint = bitcoin::OutPoint::new(txid, vout as u32);
+            created.insert(outpoint);
+
+            if is_unspendable(output) || spent.contains(&outpoint) {
+                continue;
+            }
+
+            additions.push(LeafData::from_output(
+                block_hash,
+                height,
+                tx.is_coinbase(),
+                outpoint,
+                output,
+            ));
+        }
+    }
+
+    let deletions = block
+        .txdata
+        .iter()
+        .filter(|tx| !tx.is_coinbase())
+        .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+        .filter(|outpoint| !created.contains(outpoint))
+        .collect();
+
+    BlockLeaves {
+        additions,
+        deletions,
+    }
+}
+
+/// Returns true if output can never be spent
+#[cfg(feature = "bitcoin")]
+fn is_unspendable(output: &TxOut) -> bool {
+    output.script_pubkey.is_op_return() || output.script_pubkey.len() > MAX_SCRIPT_SIZE
+}
+
+/// Hasher compatible with utreexod, where leaf values are serialized [`LeafData`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct UtreexodHasher;
+
+impl Hasher for UtreexodHasher {
+    fn hash_leaf(&self, value: &[u8]) -> Hash {
+        count(Counter::Hash, 1);
+
+        let tag = Sha512_256::digest(LEAF_TAG);
+
+        let mut hasher = Sha512_256::new();
+        hasher.update(tag);
+        hasher.update(tag);
+        hasher.update(value);
+
+        sha512_256_output(hasher)
+    }
+
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hasher = Sha512_256::new();
+        hasher.update(left.as_bytes());
+        hasher.update(right.as_bytes());
+
+        sha512_256_output(hasher)
+    }
+}
+
+fn sha512_256_output(hasher: Sha512_256) -> Hash {
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&hasher.finalize());
+    bytes.into()
+}
+
+/// Writes bitcoin's variable length encoding of an integer
+fn write_compact_size(bytes: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => bytes.push(value as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            bytes.push(0xff);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryAccumulator, MemoryForest, Prover, Utreexo};
+
+    fn leaf_data(vout: u32, script_len: usize) -> LeafData {
+        LeafData {
+            block_hash: [1; 32],
+            outpoint: OutPoint {
+                txid: [2; 32],
+                vout,
+            },
+            height: 3,
+            is_coinbase: true,
+            amount: 0x0102,
+            script_pubkey: vec![0x51; script_len],
+        }
+    }
+
+    #[test]
+    fn check_bitcoin_leaf_data() {
+        let bytes = leaf_data(4, 2).to_bytes();
+
+        assert_eq!(&[1; 32][..], &bytes[..32]);
+        assert_eq!(&[2; 32][..], &bytes[32..64]);
+        assert_eq!(&[4, 0, 0, 0], &bytes[64..68]);
+        assert_eq!(&[7, 0, 0, 0], &bytes[68..72]);
+        assert_eq!(&[2, 1, 0, 0, 0, 0, 0, 0], &bytes[72..80]);
+        assert_eq!(&[2, 0x51, 0x51], &bytes[80..]);
+
+        let bytes = leaf_data(4, 0x1234).to_bytes();
+        assert_eq!(&[0xfd, 0x34, 0x12], &bytes[80..83]);
+        assert_eq!(83 + 0x1234, bytes.len());
+
+        let mut compact_size = Vec::new();
+        write_compact_size(&mut compact_size, 0x1_0000);
+        write_compact_size(&mut compact_size, 0x1_0000_0000);
+        assert_eq!(
+            vec![0xfe, 0, 0, 1, 0, 0xff, 0, 0, 0, 0, 1, 0, 0, 0],
+            compact_size
+        );
+    }
+
+    #[test]
+    fn check_utreexod_hasher() {
+        let tag = Sha512_256::digest(b"UtreexoV1");
+        let leaf = leaf_data(0, 25);
+
+        let expected = Sha512_256::new()
+            .chain_update(tag)
+            .chain_update(tag)
+            .chain_update(leaf.to_bytes())
+            .finalize();
+        assert_eq!(&expected[..], leaf.leaf_hash().as_bytes());
+
+        let mut forest = MemoryForest::with_hasher(UtreexodHasher);
+        let mut accumulator = MemoryAccumulator::with_hasher(UtreexodHasher);
+
+        for vout in 0..3 {
+            forest.insert(leaf_data(vout, 25).to_bytes());
+            accumulator.insert_hash(leaf_data(vout, 25).leaf_hash());
+        }
+
+        let left = leaf_data(0, 25).leaf_hash();
+        let right = leaf_data(1, 25).leaf_hash();
+        let expected = Sha512_256::new()
+            .chain_update(left.as_bytes())
+            .chain_update(right.as_bytes())
+            .finalize();
+        assert_eq!(&expected[..], forest.roots()[1].unwrap().as_bytes());
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+
+        let proof = forest.prove_hash(&right).unwrap().unwrap();
+        assert!(accumulator.delete(&proof));
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Returns hash given in display byte order (i.e., reversed) in internal byte order
+    fn from_display_hex(hex: &str) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&from_hex(hex));
+        bytes.reverse();
+        bytes
+    }
+
+    /// Leaf data of coinbase output of bitcoin mainnet genesis block
+    fn genesis_leaf_data() -> LeafData {
+        LeafData {
+            block_hash: from_display_hex(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            ),
+            outpoint: OutPoint {
+                txid: from_display_hex(
+                    "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+                ),
+                vout: 0,
+            },
+            height: 0,
+            is_coinbase: true,
+            amount: 5_000_000_000,
+            script_pubkey: from_hex(
+                "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc\
+                 3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+            ),
+        }
+    }
+
+    #[test]
+    fn check_utreexod_hasher_spec_vectors() {
+        // SHA-512/256 test vector from FIPS 180-4 examples
+        assert_eq!(
+            &from_hex("53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23")[..],
+            &Sha512_256::digest(b"abc")[..]
+        );
+
+        // Expected hashes are computed independently of this crate (with Python's `hashlib`) from
+        // the layout in module documentation, using the output of mainnet genesis block. They are
+        // not exported from utreexod, so, they only check that the hasher follows that layout (not
+        // that the layout matches utreexod).
+        let leaf = genesis_leaf_data();
+        assert_eq!(148, leaf.to_bytes().len());
+        assert_eq!(
+            "ed4e7c0c48daa2d100ba2f7adbfc04845de6bf296710773ea21d6367ec29ff36",
+            hex_string(&Sha512_256::digest(LEAF_TAG))
+        );
+
+        let leaf_hash = leaf.leaf_hash();
+        assert_eq!(
+            "4007810736de2a66a8f192e08ca1d37783c1d35ea1b86ef3382827f63184243b",
+            leaf_hash.to_string()
+        );
+        assert_eq!(
+            "0987e81f08b9832f67ee71f4ae7b4118400cbf8d5e29b2aef9b63a83d9ba1f91",
+            UtreexodHasher
+                .hash_intermediate(&leaf_hash, &leaf_hash)
+                .to_string()
+        );
+    }
+
+    fn hex_string(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[cfg(feature = "bitcoin")]
+    #[test]
+    fn check_genesis_leaf_data() {
+        use bitcoin::{constants::genesis_block, Network};
+
+        let block = genesis_block(Network::Bitcoin);
+        let coinbase = &block.txdata[0];
+        let outpoint = bitcoin::OutPoint::new(coinbase.compute_txid(), 0);
+
+        assert_eq!(
+            genesis_leaf_data(),
+            LeafData::from_output(block.block_hash(), 0, true, outpoint, &coinbase.output[0])
+        );
+    }
+
+    #[cfg(feature = "bitcoin")]
+    #[test]
+    fn check_block_leaves() {
+        use bitcoin::{
+            absolute::LockTime, block, transaction::Version, Amount, CompactTarget, ScriptBuf,
+            Sequence, Transaction, TxIn, TxMerkleNode, Witness,
+        };
+
+        let output = |sats: u64, script: &[u8]| TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+        };
+        let input = |previous_output: bitcoin::OutPoint| TxIn {
+            previous_output,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let transaction = |input: Vec<TxIn>, output: Vec<TxOut>| Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input,
+            output,
+        };
+
+        let coinbase = transaction(
+            vec![input(bitcoin::OutPoint::null())],
+            vec![output(50, &[0x51]), output(0, &[0x6a, 0x01, 0x00])],
+        );
+        let external = bitcoin::OutPoint::new(coinbase.compute_txid(), 7);
+        let spend = transaction(
+            vec![
+                input(bitcoin::OutPoint::new(coinbase.compute_txid(), 0)),
+                input(external),
+            ],
+            vec![output(20, &[0x52]), output(30, &[0x53])],
+        );
+
+        let block = Block {
+            header: block::Header {
+                version: block::Version::ONE,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0),
+                nonce: 0,
+            },
+            txdata: vec![coinbase, spend.clone()],
+        };
+
+        let leaves = block_leaves(&block, 5);
+        let spend_txid = spend.compute_txid();
+
+        // Coinbase outputs are either unspendable or spent in same block
+        assert_eq!(vec![external], leaves.deletions);
+        assert_eq!(2, leaves.additions.len());
+
+        let leaf = &leaves.additions[1];
+        assert_eq!(block.block_hash().to_byte_array(), leaf.block_hash);
+        assert_eq!(
+            OutPoint {
+                txid: spend_txid.to_byte_array(),
+                vout: 1
+            },
+            leaf.outpoint
+        );
+        assert_eq!((5, false, 30), (leaf.height, leaf.is_coinbase, leaf.amount));
+        assert_eq!(vec![0x53], leaf.script_pubkey);
+        assert_eq!(leaf.leaf_hash(), leaves.addition_hashes()[1]);
+        assert_eq!(leaf.to_bytes(), leaf.to_leaf_bytes());
+
+        let outpoint = bitcoin::OutPoint::new(spend_txid, 1);
+        assert_eq!(
+            [&spend_txid.to_byte_array()[..], &[1, 0, 0, 0]].concat(),
+            outpoint.to_leaf_bytes()
+        );
+        assert_eq!(
+            vec![30, 0, 0, 0, 0, 0, 0, 0, 1, 0x53],
+            spend.output[1].to_leaf_bytes()
+        );
+    }
+}
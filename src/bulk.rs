@@ -0,0 +1,181 @@
+//! Bottom-up construction of perfect merkle trees from a list of leaves
+//!
+//! Subtrees are independent of each other, so, with `rayon` feature, the two halves of every large
+//! enough subtree are built in parallel.
+use alloc::vec::Vec;
+use core::ops::Range;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{Hash, Hasher};
+
+/// Subtrees with fewer leaves than this are always built sequentially
+#[cfg(feature = "rayon")]
+const PARALLEL_THRESHOLD: usize = 1024;
+
+/// Number of leaves hashed by a single task (so that hashers can reuse their state between leaves
+/// of a chunk)
+#[cfg(feature = "rayon")]
+const LEAF_CHUNK_SIZE: usize = 256;
+
+/// Calculates leaf hashes of given values (in chunks, in parallel, with `rayon` feature)
+pub(crate) fn hash_leaves<H, T>(hasher: &H, leaf_values: &[T]) -> Vec<Hash>
+where
+    H: Hasher + Sync,
+    T: AsRef<[u8]> + Sync,
+{
+    #[cfg(feature = "rayon")]
+    return leaf_values
+        .par_chunks(LEAF_CHUNK_SIZE)
+        .flat_map_iter(|chunk| hasher.hash_leaves(chunk))
+        .collect();
+
+    #[cfg(not(feature = "rayon"))]
+    return hasher.hash_leaves(leaf_values);
+}
+
+/// Returns height and range of leaves of every tree in a forest with given number of leaves (in
+/// the order of insertion of leaves, i.e., tallest tree first)
+pub(crate) fn subtrees(num_leaves: usize) -> Vec<(usize, Range<usize>)> {
+    let mut start = 0;
+
+    (0..usize::BITS as usize)
+        .rev()
+        .filter(|height| num_leaves & (1 << height) != 0)
+        .map(|height| {
+            let end = start + (1 << height);
+            let range = start..end;
+            start = end;
+
+            (height, range)
+        })
+        .collect()
+}
+
+/// Writes all the nodes of a perfect merkle tree with given leaf hashes (in post-order) to `nodes`
+/// and returns the root hash. `nodes` must contain exactly `2 * leaf_hashes.len() - 1` elements.
+pub(crate) fn build_nodes<H: Hasher + Sync>(
+    hasher: &H,
+    leaf_hashes: &[Hash],
+    nodes: &mut [Hash],
+) -> Hash {
+    if leaf_hashes.len() == 1 {
+        nodes[0] = leaf_hashes[0];
+        return leaf_hashes[0];
+    }
+
+    let half = leaf_hashes.len() / 2;
+    let (left_leaves, right_leaves) = leaf_hashes.split_at(half);
+    let (left_nodes, rest) = nodes.split_at_mut(2 * half - 1);
+    let (right_nodes, root) = rest.split_at_mut(2 * half - 1);
+
+    let (left_hash, right_hash) = join(
+        leaf_hashes.len(),
+        || build_nodes(hasher, left_leaves, left_nodes),
+        || build_nodes(hasher, right_leaves, right_nodes),
+    );
+
+    root[0] = hasher.hash_intermediate(&left_hash, &right_hash);
+    root[0]
+}
+
+/// Returns root hash of a perfect merkle tree with given leaf hashes
+pub(crate) fn build_root<H: Hasher + Sync>(hasher: &H, leaf_hashes: &[Hash]) -> Hash {
+    if leaf_hashes.len() == 1 {
+        return leaf_hashes[0];
+    }
+
+    let (left_leaves, right_leaves) = leaf_hashes.split_at(leaf_hashes.len() / 2);
+
+    let (left_hash, right_hash) = join(
+        leaf_hashes.len(),
+        || build_root(hasher, left_leaves),
+        || build_root(hasher, right_leaves),
+    );
+
+    hasher.hash_intermediate(&left_hash, &right_hash)
+}
+
+/// Runs both closures (in parallel if subtree with given number of leaves is large enough)
+#[cfg(feature = "rayon")]
+fn join<A, B>(num_leaves: usize, left: A, right: B) -> (Hash, Hash)
+where
+    A: FnOnce() -> Hash + Send,
+    B: FnOnce() -> Hash + Send,
+{
+    if num_leaves >= PARALLEL_THRESHOLD {
+        rayon::join(left, right)
+    } else {
+        (left(), right())
+    }
+}
+
+/// Runs both closures
+#[cfg(not(feature = "rayon"))]
+fn join<A, B>(_num_leaves: usize, left: A, right: B) -> (Hash, Hash)
+where
+    A: FnOnce() -> Hash + Send,
+    B: FnOnce() -> Hash + Send,
+{
+    (left(), right())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hash_intermediate, hash_leaf, hash_leaves, Blake3, KeyedBlake3, MemoryAccumulator,
+    };
+
+    #[test]
+    fn check_hash_leaves() {
+        // Large enough to be hashed in multiple chunks with `rayon` feature
+        let values: Vec<Vec<u8>> = (0..1000u32)
+            .map(|value| value.to_le_bytes()[..(value % 5) as usize].to_vec())
+            .collect();
+
+        let expected: Vec<Hash> = values.iter().map(hash_leaf).collect();
+        assert_eq!(expected, hash_leaves(&values));
+
+        let keyed = KeyedBlake3::new([7; 32]);
+        let expected: Vec<Hash> = values.iter().map(|value| keyed.hash_leaf(value)).collect();
+        assert_eq!(expected, super::hash_leaves(&keyed, &values));
+
+        let mut accumulator = MemoryAccumulator::new();
+        accumulator.insert_hash_batch(hash_leaves(&values));
+        assert_eq!(MemoryAccumulator::from_leaves(&values), accumulator);
+    }
+
+    #[test]
+    fn check_subtrees() {
+        assert_eq!(vec![(2, 0..4), (0, 4..5)], subtrees(5));
+        assert!(subtrees(0).is_empty());
+    }
+
+    #[test]
+    fn check_build_nodes() {
+        let leaf_hashes: Vec<Hash> = (0..4u8).map(|value| hash_leaf([value])).collect();
+        let mut nodes = vec![Hash::from([0; 32]); 7];
+
+        let root_hash = build_nodes(&Blake3, &leaf_hashes, &mut nodes);
+
+        let left = hash_intermediate(&leaf_hashes[0], &leaf_hashes[1]);
+        let right = hash_intermediate(&leaf_hashes[2], &leaf_hashes[3]);
+
+        assert_eq!(
+            vec![
+                leaf_hashes[0],
+                leaf_hashes[1],
+                left,
+                leaf_hashes[2],
+                leaf_hashes[3],
+                right,
+                root_hash
+            ],
+            nodes
+        );
+        assert_eq!(hash_intermediate(&left, &right), root_hash);
+        assert_eq!(root_hash, build_root(&Blake3, &leaf_hashes));
+    }
+}
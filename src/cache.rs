@@ -0,0 +1,210 @@
+use alloc::vec::Vec;
+
+use crate::Hash;
+
+/// Trait for deciding which leaves a [`PartialForest`](crate::PartialForest) remembers (on
+/// insertion) and which remembered leaves it forgets first (on memory pressure, see
+/// [`PartialForest::shrink_to`](crate::PartialForest::shrink_to))
+///
+/// Policy is notified about every remembered leaf (whether it is remembered because of policy's
+/// decision or explicitly) and every leaf which stops being remembered (because it is deleted,
+/// forgotten or evicted).
+pub trait CachePolicy {
+    /// Decides whether a newly inserted leaf is remembered. `ttl` is the time-to-live hint of leaf
+    /// (i.e., expected number of blocks until the leaf is deleted), if it is inserted with one.
+    fn on_insert(&mut self, leaf_hash: &Hash, ttl: Option<u64>) -> bool;
+
+    /// Called when a leaf starts being remembered
+    fn on_remember(&mut self, leaf_hash: &Hash, ttl: Option<u64>);
+
+    /// Called when a remembered leaf is accessed (see
+    /// [`PartialForest::touch`](crate::PartialForest::touch))
+    fn on_access(&mut self, _leaf_hash: &Hash) {}
+
+    /// Called when a leaf stops being remembered
+    fn on_forget(&mut self, leaf_hash: &Hash);
+
+    /// Chooses a remembered leaf to forget (and stops tracking it), or returns `None` if no leaf
+    /// should be forgotten
+    fn evict(&mut self) -> Option<Hash>;
+}
+
+/// Cache policy which remembers leaves expected to be deleted within a horizon (i.e., leaves
+/// inserted with a TTL less than or equal to the horizon) and evicts the leaf expected to be
+/// deleted last
+///
+/// This is the "clairvoyant" caching strategy from the utreexo paper: leaves which are deleted soon
+/// are the ones whose proofs would otherwise have to be fetched soon. Leaves remembered without a
+/// TTL hint (e.g., explicitly remembered leaves of a wallet) are never evicted. Without a horizon,
+/// leaves are never remembered on insertion.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TtlPolicy {
+    /// Leaves inserted with a TTL within horizon are remembered
+    horizon: Option<u64>,
+    /// Number of insertions so far (used as clock for calculating expiry of leaves)
+    clock: u64,
+    /// Expected deletion time of remembered leaves with a TTL hint
+    expiries: Vec<(Hash, u64)>,
+}
+
+impl TtlPolicy {
+    /// Creates a new TTL policy with given horizon
+    pub fn new(horizon: Option<u64>) -> Self {
+        Self {
+            horizon,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the TTL horizon
+    pub fn horizon(&self) -> Option<u64> {
+        self.horizon
+    }
+
+    /// Sets the TTL horizon (only affects leaves inserted afterwards)
+    pub fn set_horizon(&mut self, horizon: Option<u64>) {
+        self.horizon = horizon;
+    }
+}
+
+impl CachePolicy for TtlPolicy {
+    fn on_insert(&mut self, _leaf_hash: &Hash, ttl: Option<u64>) -> bool {
+        self.clock += 1;
+
+        match (self.horizon, ttl) {
+            (Some(horizon), Some(ttl)) => ttl <= horizon,
+            _ => false,
+        }
+    }
+
+    fn on_remember(&mut self, leaf_hash: &Hash, ttl: Option<u64>) {
+        if let Some(ttl) = ttl {
+            self.expiries
+                .push((*leaf_hash, self.clock.saturating_add(ttl)));
+        }
+    }
+
+    fn on_forget(&mut self, leaf_hash: &Hash) {
+        self.expiries.retain(|(hash, _)| hash != leaf_hash);
+    }
+
+    fn evict(&mut self) -> Option<Hash> {
+        let (index, _) = self
+            .expiries
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, expiry))| *expiry)?;
+
+        Some(self.expiries.remove(index).0)
+    }
+}
+
+/// Cache policy which remembers every inserted leaf and evicts the least recently used one (a leaf
+/// is used when it is remembered or accessed)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LruPolicy {
+    /// Remembered leaves (least recently used first)
+    recency: Vec<Hash>,
+}
+
+impl LruPolicy {
+    /// Creates a new LRU policy
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl CachePolicy for LruPolicy {
+    fn on_insert(&mut self, _leaf_hash: &Hash, _ttl: Option<u64>) -> bool {
+        true
+    }
+
+    fn on_remember(&mut self, leaf_hash: &Hash, _ttl: Option<u64>) {
+        self.recency.push(*leaf_hash);
+    }
+
+    fn on_access(&mut self, leaf_hash: &Hash) {
+        if let Some(index) = self.recency.iter().position(|hash| hash == leaf_hash) {
+            let leaf_hash = self.recency.remove(index);
+            self.recency.push(leaf_hash);
+        }
+    }
+
+    fn on_forget(&mut self, leaf_hash: &Hash) {
+        self.recency.retain(|hash| hash != leaf_hash);
+    }
+
+    fn evict(&mut self) -> Option<Hash> {
+        if self.recency.is_empty() {
+            None
+        } else {
+            Some(self.recency.remove(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake3, Hasher, MemoryForest, PartialForest, Prover, Utreexo};
+
+    #[test]
+    fn check_lru_policy() {
+        let mut forest = MemoryForest::new();
+        let mut partial = PartialForest::with_policy(LruPolicy::new());
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+            partial.insert([value; 32]);
+        }
+
+        assert_eq!(6, partial.remembered().count());
+
+        // Deleted leaves are no longer tracked by policy (so, they are never evicted)
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(partial.delete(&proof));
+
+        assert!(partial.touch(&Blake3.hash_leaf(&[1; 32])));
+        assert!(!partial.touch(&Blake3.hash_leaf(&[0; 32])));
+
+        let forgotten: Vec<Hash> = (2..5u8)
+            .map(|value| Blake3.hash_leaf(&[value; 32]))
+            .collect();
+        assert_eq!(forgotten, partial.shrink_to(2));
+
+        let proof = partial.prove([1; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(partial.delete(&proof));
+        assert_eq!(1, partial.policy().recency.len());
+
+        // Explicit flag overrides policy
+        partial.insert_with_memory([6; 32], false);
+        assert!(!partial.is_remembered(&Blake3.hash_leaf(&[6; 32])));
+        assert_eq!(1, partial.policy().recency.len());
+    }
+
+    #[test]
+    fn check_ttl_policy() {
+        let mut partial = PartialForest::with_policy(TtlPolicy::new(Some(10)));
+
+        partial.insert([0; 32]);
+        partial.insert_and_remember([1; 32]);
+
+        for (value, ttl) in [(2u8, 5u64), (3, 11), (4, 9), (5, 1)].iter() {
+            partial.insert_with_ttl([*value; 32], *ttl);
+        }
+
+        assert_eq!(4, partial.remembered().count());
+
+        // Leaf expected to be deleted last is evicted first and leaves remembered without TTL are
+        // never evicted
+        let expected = vec![
+            Blake3.hash_leaf(&[4; 32]),
+            Blake3.hash_leaf(&[2; 32]),
+            Blake3.hash_leaf(&[5; 32]),
+        ];
+        assert_eq!(expected, partial.shrink_to(0));
+        assert!(partial.prove([1; 32]).unwrap().is_some());
+    }
+}
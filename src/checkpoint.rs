@@ -0,0 +1,198 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+use crate::{leaf_index::LeafIndex, Hash, Tree};
+
+/// Snapshot of state of an accumulator or a forest (e.g. at a block boundary) which can be restored
+/// later using `restore()`
+///
+/// Checkpoints of accumulators contain only root hashes, while checkpoints of forests also contain
+/// full merkle trees. So, a forest can only be restored from a forest's checkpoint, while an
+/// accumulator can be restored from either. Note that hasher is not a part of checkpoint, so, a
+/// checkpoint should only be restored in an accumulator (or forest) which uses the same hasher.
+///
+/// Checkpoints of forests also contain index of leaves, so, restoring them does not rebuild the
+/// index. Chunks of index are shared with the forest until the forest updates them, so, a
+/// checkpoint kept for many blocks holds on to old copies of every chunk updated since then.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// Root hashes of merkle trees in forest
+    pub(crate) roots: Vec<Option<Hash>>,
+    /// Merkle trees in forest (only present in checkpoints of forests, shared with the forest)
+    pub(crate) trees: Option<Vec<Option<Arc<Tree>>>>,
+    /// Index of leaves in forest (only present in checkpoints of forests, chunks of index are
+    /// shared with the forest)
+    pub(crate) index: Option<LeafIndex>,
+}
+
+impl PartialEq for Checkpoint {
+    fn eq(&self, other: &Self) -> bool {
+        // Index of leaves is derived from trees
+        self.roots == other.roots && self.trees == other.trees
+    }
+}
+
+impl Eq for Checkpoint {}
+
+impl Checkpoint {
+    /// Returns the root hashes of all the merkle trees in forest
+    pub fn root_hashes(&self) -> &[Option<Hash>] {
+        &self.roots
+    }
+
+    /// Returns the number of leaves in accumulator (or forest) at the time of checkpoint
+    pub fn num_leaves(&self) -> u64 {
+        self.roots
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| root.is_some())
+            .map(|(height, _)| 1 << height)
+            .sum()
+    }
+
+    /// Returns `true` if checkpoint contains full merkle trees, i.e., it can be used to restore a
+    /// forest
+    pub fn has_trees(&self) -> bool {
+        self.trees.is_some()
+    }
+}
+
+/// Bounded store of checkpoints indexed by block height, which can be used for handling reorgs
+///
+/// Store keeps at most `capacity` checkpoints (older ones are pruned first) and, optionally, only
+/// accepts checkpoints at heights which are multiples of an interval (i.e., one per `K` blocks).
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    /// Checkpoints indexed by block height
+    checkpoints: BTreeMap<u64, Checkpoint>,
+    /// Maximum number of checkpoints to keep
+    capacity: usize,
+    /// Only checkpoints at heights which are multiples of interval are stored
+    interval: u64,
+}
+
+impl CheckpointStore {
+    /// Creates a new checkpoint store which keeps last `capacity` checkpoints
+    pub fn new(capacity: usize) -> Self {
+        Self::with_interval(capacity, 1)
+    }
+
+    /// Creates a new checkpoint store which keeps last `capacity` checkpoints and only stores one
+    /// checkpoint every `interval` blocks (i.e., at heights which are multiples of `interval`)
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `interval` is zero.
+    pub fn with_interval(capacity: usize, interval: u64) -> Self {
+        assert!(
+            interval > 0,
+            "Checkpoint interval should be greater than zero"
+        );
+
+        Self {
+            checkpoints: Default::default(),
+            capacity,
+            interval,
+        }
+    }
+
+    /// Returns the number of checkpoints in store
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Returns `true` if there are no checkpoints in store
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    /// Adds checkpoint of given block height to store (replacing any existing checkpoint of the
+    /// same height) and prunes the oldest checkpoints if store is full. Returns `false` if the
+    /// checkpoint was not stored because height is not a multiple of interval.
+    pub fn insert(&mut self, height: u64, checkpoint: Checkpoint) -> bool {
+        // `u64::is_multiple_of` needs Rust 1.87 (crate does not declare a minimum Rust version)
+        #[allow(clippy::manual_is_multiple_of)]
+        if height % self.interval != 0 {
+            return false;
+        }
+
+        self.checkpoints.insert(height, checkpoint);
+
+        while self.checkpoints.len() > self.capacity {
+            let oldest = *self
+                .checkpoints
+                .keys()
+                .next()
+                .expect("Expected at least one checkpoint in a non-empty store");
+            self.checkpoints.remove(&oldest);
+        }
+
+        true
+    }
+
+    /// Returns checkpoint of given block height, if present
+    pub fn get(&self, height: u64) -> Option<&Checkpoint> {
+        self.checkpoints.get(&height)
+    }
+
+    /// Returns the latest checkpoint (along with its height) at or below given block height. This
+    /// is the checkpoint to restore when a reorg invalidates all the blocks above `height`.
+    pub fn latest_at_or_below(&self, height: u64) -> Option<(u64, &Checkpoint)> {
+        self.checkpoints
+            .range(..=height)
+            .next_back()
+            .map(|(height, checkpoint)| (*height, checkpoint))
+    }
+
+    /// Returns the latest checkpoint (along with its height)
+    pub fn latest(&self) -> Option<(u64, &Checkpoint)> {
+        self.latest_at_or_below(u64::MAX)
+    }
+
+    /// Removes all the checkpoints above given block height (e.g. after a reorg)
+    pub fn remove_above(&mut self, height: u64) {
+        if let Some(above) = height.checked_add(1) {
+            self.checkpoints.split_off(&above);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryAccumulator, Utreexo};
+
+    #[test]
+    fn check_checkpoint_store() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut store = CheckpointStore::with_interval(2, 10);
+
+        for height in 0..=30u64 {
+            accumulator.insert(height.to_le_bytes());
+            store.insert(height, accumulator.checkpoint());
+        }
+
+        assert_eq!(2, store.len());
+        assert!(store.get(10).is_none());
+        assert_eq!(Some(21), store.get(20).map(Checkpoint::num_leaves));
+        assert_eq!(
+            Some(31),
+            store
+                .latest()
+                .map(|(_, checkpoint)| checkpoint.num_leaves())
+        );
+        assert_eq!(
+            Some(20),
+            store.latest_at_or_below(29).map(|(height, _)| height)
+        );
+        assert!(store.latest_at_or_below(19).is_none());
+
+        store.remove_above(25);
+        assert_eq!(1, store.len());
+        assert_eq!(Some(20), store.latest().map(|(height, _)| height));
+
+        let mut store = CheckpointStore::new(3);
+        assert!(store.is_empty());
+        assert!(store.insert(7, accumulator.checkpoint()));
+        assert_eq!(Some(7), store.latest().map(|(height, _)| height));
+    }
+}
@@ -0,0 +1,371 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::convert::TryFrom;
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Serialize};
+
+use crate::{encoding::Reader, Blake3, Hash, Hasher, MemoryForest, Proof, Tree, UtreexoError};
+
+/// Part of a forest snapshot which can be verified on its own against root hashes of forest
+///
+/// A chunk contains all the nodes of a subtree of a merkle tree in forest along with sibling hashes
+/// from root of subtree to root of the tree (i.e., a proof of subtree). So, chunks can be
+/// downloaded from different (untrusted) peers in parallel, verified on arrival and assembled in
+/// any order (see [`SnapshotAssembler`]).
+///
+/// Every merkle tree of height `h` is split into `2^(h - c)` chunks of height `c` (or a single
+/// chunk if `h <= c`), where `c` is the chunk height (see [`MemoryForest::chunks`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SnapshotChunk {
+    /// Height of merkle tree containing the chunk
+    tree_height: usize,
+    /// Height of subtree
+    height: usize,
+    /// Position of subtree among subtrees of same height in merkle tree (from left)
+    index: usize,
+    /// Nodes of subtree
+    subtree: Tree,
+    /// Sibling hashes from root of subtree to root of merkle tree
+    sibling_hashes: Vec<Hash>,
+}
+
+impl SnapshotChunk {
+    /// Creates `index`th chunk of given height (capped at the height of tree) of a merkle tree of
+    /// given height, if present
+    pub(crate) fn from_tree(
+        tree: &Tree,
+        tree_height: usize,
+        chunk_height: usize,
+        index: usize,
+    ) -> Option<Self> {
+        let height = chunk_height.min(tree_height);
+        let subtree = tree.subtree_at(height, index)?;
+
+        // Sibling hashes above subtree are same for all the leaves of subtree
+        let first_leaf = index << height;
+        let leaf_hash = tree.leaf(first_leaf)?;
+        let proof = tree.prove_at(leaf_hash, first_leaf).ok()?;
+
+        Some(Self {
+            tree_height,
+            height,
+            index,
+            subtree,
+            sibling_hashes: proof.sibling_hashes[height..].to_vec(),
+        })
+    }
+
+    /// Returns height of merkle tree containing the chunk
+    pub fn tree_height(&self) -> usize {
+        self.tree_height
+    }
+
+    /// Returns height of subtree in the chunk
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns position of subtree among subtrees of same height in merkle tree (from left)
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Verifies that the chunk is a consistent subtree of merkle tree with given root hashes
+    /// (indexed by height)
+    pub fn verify<H: Hasher>(&self, hasher: &H, roots: &[Option<Hash>]) -> bool {
+        let proof_height = match self.tree_height.checked_sub(self.height) {
+            Some(proof_height) if proof_height <= Proof::MAX_HEIGHT => proof_height,
+            _ => return false,
+        };
+
+        if self.sibling_hashes.len() != proof_height
+            || self.index.checked_shr(proof_height as u32).unwrap_or(0) != 0
+            || self.subtree.check_consistency(hasher, self.height).is_err()
+        {
+            return false;
+        }
+
+        let mut hash = *self.subtree.root_hash();
+
+        for (level, sibling_hash) in self.sibling_hashes.iter().enumerate() {
+            hash = if (self.index >> level) & 1 == 1 {
+                hasher.hash_intermediate(sibling_hash, &hash)
+            } else {
+                hasher.hash_intermediate(&hash, sibling_hash)
+            };
+        }
+
+        roots.get(self.tree_height) == Some(&Some(hash))
+    }
+
+    /// Returns canonical binary encoding of chunk (see [`SnapshotChunk::from_bytes`] for layout)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let nodes = self.subtree.nodes();
+        let mut bytes =
+            Vec::with_capacity(10 + (nodes.len() + self.sibling_hashes.len()) * Hash::LEN);
+
+        // Heights of trees always fit in a byte because they cannot exceed `Proof::MAX_HEIGHT`
+        bytes.push(self.tree_height as u8);
+        bytes.push(self.height as u8);
+        bytes.extend_from_slice(&(self.index as u64).to_be_bytes());
+
+        for hash in nodes.iter().chain(self.sibling_hashes.iter()) {
+            bytes.extend_from_slice(hash.as_bytes());
+        }
+
+        bytes
+    }
+
+    /// Parses chunk from its canonical binary encoding
+    ///
+    /// Layout: `tree height (1 byte) || height (1 byte) || index (8 bytes, big endian) || nodes ||
+    /// sibling hashes`, where nodes are exactly `2^(height + 1) - 1` node hashes of subtree in
+    /// post-order and sibling hashes are `tree height - height` hashes from root of subtree to root
+    /// of merkle tree.
+    ///
+    /// Chunk is not verified while parsing (see [`SnapshotChunk::verify`]).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        let mut reader = Reader::new(bytes);
+
+        let tree_height = usize::from(reader.read_u8()?);
+        let height = usize::from(reader.read_u8()?);
+
+        if tree_height > Proof::MAX_HEIGHT || height > tree_height {
+            return Err(UtreexoError::InvalidEncoding("Invalid height of chunk"));
+        }
+
+        let index = <[u8; 8]>::try_from(reader.read_bytes(8)?)
+            .ok()
+            .map(u64::from_be_bytes)
+            .and_then(|index| usize::try_from(index).ok())
+            .filter(|index| {
+                index
+                    .checked_shr((tree_height - height) as u32)
+                    .unwrap_or(0)
+                    == 0
+            })
+            .ok_or(UtreexoError::InvalidEncoding("Invalid index of chunk"))?;
+
+        let num_nodes = 1usize
+            .checked_shl((height + 1) as u32)
+            .map_or(usize::MAX, |num| num - 1);
+        let nodes = reader.read_hashes(num_nodes)?;
+        let sibling_hashes = reader.read_hashes(tree_height - height)?;
+
+        reader.finish()?;
+
+        Ok(Self {
+            tree_height,
+            height,
+            index,
+            subtree: Tree::from_nodes(height, nodes)
+                .expect("Expected exactly 2^(h + 1) - 1 nodes in subtree of height h"),
+            sibling_hashes,
+        })
+    }
+}
+
+/// Assembles a forest from [`SnapshotChunk`]s verified against trusted root hashes
+///
+/// Chunks can be added in any order and every chunk is verified before it is accepted, so, a
+/// download can be resumed by requesting only the chunks which are still missing (see
+/// [`SnapshotAssembler::missing`]).
+#[derive(Debug, Clone)]
+pub struct SnapshotAssembler<H = Blake3> {
+    /// Trusted root hashes of merkle trees in forest (indexed by height)
+    roots: Vec<Option<Hash>>,
+    /// Height of chunks
+    chunk_height: usize,
+    /// Hasher used for verifying chunks
+    hasher: H,
+    /// Verified subtrees indexed by height of merkle tree and index of chunk
+    subtrees: BTreeMap<(usize, usize), Tree>,
+}
+
+impl SnapshotAssembler {
+    /// Creates a new assembler of a forest with given trusted root hashes (indexed by height) from
+    /// chunks of given height
+    ///
+    /// Returns `TooManyRoots` error if there are more roots than the number of possible heights of
+    /// merkle trees.
+    pub fn new(roots: Vec<Option<Hash>>, chunk_height: usize) -> Result<Self, UtreexoError> {
+        Self::with_hasher(roots, chunk_height, Blake3)
+    }
+}
+
+impl<H: Hasher> SnapshotAssembler<H> {
+    /// Creates a new assembler of a forest, which uses given hasher, with given trusted root hashes
+    /// (indexed by height) from chunks of given height (see [`SnapshotAssembler::new`])
+    pub fn with_hasher(
+        roots: Vec<Option<Hash>>,
+        chunk_height: usize,
+        hasher: H,
+    ) -> Result<Self, UtreexoError> {
+        let max_num_roots = Proof::MAX_HEIGHT + 1;
+
+        if roots.len() > max_num_roots {
+            return Err(UtreexoError::TooManyRoots {
+                num_roots: roots.len(),
+                max_num_roots,
+            });
+        }
+
+        Ok(Self {
+            roots,
+            chunk_height,
+            hasher,
+            subtrees: Default::default(),
+        })
+    }
+
+    /// Verifies and adds a chunk. Returns `InvalidChunk` error if the chunk does not have expected
+    /// height or if it is not a subtree of merkle trees with trusted root hashes.
+    pub fn add_chunk(&mut self, chunk: SnapshotChunk) -> Result<(), UtreexoError> {
+        if chunk.height != self.chunk_height.min(chunk.tree_height)
+            || !chunk.verify(&self.hasher, &self.roots)
+        {
+            return Err(UtreexoError::InvalidChunk {
+                tree_height: chunk.tree_height,
+                index: chunk.index,
+            });
+        }
+
+        self.subtrees
+            .insert((chunk.tree_height, chunk.index), chunk.subtree);
+
+        Ok(())
+    }
+
+    /// Returns height of merkle tree and index of all the chunks which are not added yet
+    pub fn missing(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.roots
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| root.is_some())
+            .flat_map(move |(tree_height, _)| {
+                (0..num_chunks(tree_height, self.chunk_height))
+                    .map(move |index| (tree_height, index))
+            })
+            .filter(move |key| !self.subtrees.contains_key(key))
+    }
+
+    /// Returns `true` if all the chunks are added
+    pub fn is_complete(&self) -> bool {
+        self.missing().next().is_none()
+    }
+
+    /// Assembles forest from all the chunks. Returns `MissingChunk` error if any chunk is not added
+    /// yet.
+    pub fn finish(self) -> Result<MemoryForest<H>, UtreexoError> {
+        if let Some((tree_height, index)) = self.missing().next() {
+            return Err(UtreexoError::MissingChunk { tree_height, index });
+        }
+
+        // Subtrees are ordered by height of tree and index of chunk, and only chunks of trees with
+        // trusted root hashes are added
+        let Self {
+            roots,
+            chunk_height,
+            hasher,
+            subtrees,
+        } = self;
+        let mut subtrees = subtrees.into_values();
+
+        let trees = roots
+            .iter()
+            .enumerate()
+            .map(|(tree_height, root)| {
+                root.map(|_| {
+                    let num_chunks = num_chunks(tree_height, chunk_height);
+                    let tree = Tree::from_subtrees(&hasher, subtrees.by_ref().take(num_chunks));
+
+                    Arc::new(tree)
+                })
+            })
+            .collect();
+
+        Ok(MemoryForest::from_trees(trees, hasher))
+    }
+}
+
+/// Returns the number of chunks of given height in a merkle tree of given height
+pub(crate) fn num_chunks(tree_height: usize, chunk_height: usize) -> usize {
+    1 << (tree_height - chunk_height.min(tree_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Utreexo;
+
+    #[test]
+    fn check_snapshot_chunks() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..13u8 {
+            forest.insert([value; 32]);
+        }
+
+        // Trees of heights 0, 2 and 3 are split into 1, 2 and 4 chunks
+        let chunks: Vec<SnapshotChunk> = forest.chunks(1).collect();
+        assert_eq!(7, chunks.len());
+        assert_eq!(Some(&chunks[6]), forest.chunk(3, 1, 3).as_ref());
+        assert!(forest.chunk(3, 1, 4).is_none());
+        assert!(forest.chunk(1, 1, 0).is_none());
+
+        let roots = forest.roots();
+
+        for chunk in chunks.iter() {
+            assert!(chunk.verify(&Blake3, &roots));
+            assert_eq!(
+                Ok(chunk),
+                SnapshotChunk::from_bytes(&chunk.to_bytes()).as_ref()
+            );
+        }
+
+        let mut tampered = chunks[4].clone();
+        tampered.index = 2;
+        assert!(!tampered.verify(&Blake3, &roots));
+
+        let mut other = forest.clone();
+        other.insert([13; 32]);
+
+        let mut assembler = SnapshotAssembler::new(roots.clone(), 1).unwrap();
+
+        assert_eq!(
+            Err(UtreexoError::InvalidChunk {
+                tree_height: 1,
+                index: 0
+            }),
+            assembler.add_chunk(other.chunk(1, 1, 0).unwrap())
+        );
+        assert_eq!(
+            Err(UtreexoError::InvalidChunk {
+                tree_height: 3,
+                index: 0
+            }),
+            assembler.add_chunk(forest.chunk(3, 2, 0).unwrap())
+        );
+
+        for chunk in chunks[1..].iter().rev() {
+            assert_eq!(Ok(()), assembler.add_chunk(chunk.clone()));
+        }
+
+        assert_eq!(vec![(0, 0)], assembler.missing().collect::<Vec<_>>());
+        assert_eq!(
+            Err(UtreexoError::MissingChunk {
+                tree_height: 0,
+                index: 0
+            }),
+            assembler.clone().finish()
+        );
+
+        assert_eq!(Ok(()), assembler.add_chunk(chunks[0].clone()));
+        assert!(assembler.is_complete());
+        assert_eq!(Ok(forest), assembler.finish());
+    }
+}
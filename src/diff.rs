@@ -0,0 +1,72 @@
+use alloc::vec::Vec;
+
+use crate::Hash;
+
+/// Difference between two forests (see [`MemoryForest::diff`](crate::MemoryForest::diff))
+///
+/// Merkle trees with same root hash contain same leaves, so, only leaves of trees with differing
+/// root hashes are compared. Two nodes with divergent state can reconcile by exchanging just these
+/// leaves (and proofs of the leaves to be deleted) instead of full snapshots.
+///
+/// Leaves are listed tree by tree, starting with the shortest tree.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ForestDiff {
+    /// Leaf hashes present only in the first forest
+    pub only_in_self: Vec<Hash>,
+    /// Leaf hashes present only in the second forest
+    pub only_in_other: Vec<Hash>,
+    /// Heights of merkle trees whose root hashes differ (including heights at which only one of
+    /// the forests has a tree), in increasing order
+    pub differing_roots: Vec<usize>,
+}
+
+impl ForestDiff {
+    /// Returns `true` if both forests have same root hashes
+    ///
+    /// Forests with same leaves inserted in different order have differing root hashes even
+    /// though there are no leaves present only on one side.
+    pub fn is_empty(&self) -> bool {
+        self.differing_roots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Blake3, Hasher, MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_forest_diff() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..10u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut other = forest.clone();
+        assert!(forest.diff(&other).is_empty());
+
+        forest.insert([10; 32]);
+
+        let proof = other.prove([9; 32]).unwrap().unwrap();
+        assert!(other.delete(&proof));
+        other.insert([11; 32]);
+        other.insert([12; 32]);
+
+        // Both forests have trees of heights 0, 1 and 3, and tree of height 3 is shared
+        let diff = forest.diff(&other);
+        assert_eq!(vec![0, 1], diff.differing_roots);
+        assert_eq!(
+            vec![Blake3.hash_leaf(&[10; 32]), Blake3.hash_leaf(&[9; 32])],
+            diff.only_in_self
+        );
+        assert_eq!(
+            vec![Blake3.hash_leaf(&[12; 32]), Blake3.hash_leaf(&[11; 32])],
+            diff.only_in_other
+        );
+
+        let reverse = other.diff(&forest);
+        assert_eq!(diff.only_in_self, reverse.only_in_other);
+        assert_eq!(diff.only_in_other, reverse.only_in_self);
+        assert!(!reverse.is_empty());
+    }
+}
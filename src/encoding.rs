@@ -0,0 +1,297 @@
+//! Canonical binary encoding of proofs, accumulators and forests
+//!
+//! All the encodings are deterministic, i.e., equal values always produce equal bytes and there is
+//! exactly one valid encoding of every value. The layouts are:
+//!
+//! - Proof: `height (1 byte) || path (ceil(height / 8) bytes) || leaf hash (32 bytes) ||
+//!   sibling hashes (height * 32 bytes)`. Path contains one bit per level, from leaf to root,
+//!   packed starting from the most significant bit of first byte, where `1` means that the sibling
+//!   is on the left. Unused bits of last byte must be `0`.
+//! - Accumulator: `header || number of roots (1 byte) || roots`, where each root is either `0x00`
+//!   (no tree of that height) or `0x01 || root hash (32 bytes)`.
+//! - Forest: `header || number of trees (1 byte) || trees`, where each tree is either `0x00` (no
+//!   tree of that height) or `0x01 || nodes`. Tree at position `i` contains exactly `2^(i + 1) - 1`
+//!   node hashes in post-order (see [`Tree`](crate::Tree)). Leaves are not encoded separately
+//!   because they are derived from nodes.
+//! - Snapshot chunk: `tree height (1 byte) || height (1 byte) || index (8 bytes, big endian) ||
+//!   nodes || sibling hashes`, where nodes are `2^(height + 1) - 1` node hashes of subtree in
+//!   post-order and sibling hashes are `tree height - height` hashes from root of subtree to root
+//!   of merkle tree.
+//! - Salted accumulator or forest (using [`SaltedBlake3`](crate::SaltedBlake3)): `salt (32 bytes)
+//!   || accumulator or forest`.
+//!
+//! Roots of accumulators and trees of forests are written up to the tallest tree (deletions may
+//! leave empty positions after it), so, the last encoded root (or tree) is always present.
+//!
+//! State of accumulators and forests starts with a header: `magic (4 bytes) || version (1 byte)`,
+//! where magic is `b"UTRA"` for accumulators and `b"UTRF"` for forests. Decoding rejects unknown
+//! versions so that snapshots written by a newer format are never misinterpreted.
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::{Hash, Proof, UtreexoError};
+
+/// Magic bytes of encoded accumulator state
+pub(crate) const ACCUMULATOR_MAGIC: [u8; 4] = *b"UTRA";
+
+/// Magic bytes of encoded forest state
+pub(crate) const FOREST_MAGIC: [u8; 4] = *b"UTRF";
+
+/// Magic bytes of encoded wire message
+pub(crate) const WIRE_MAGIC: [u8; 4] = *b"UTRW";
+
+/// Current version of accumulator and forest state encoding
+pub(crate) const VERSION: u8 = 1;
+
+/// Marker byte for an empty position in accumulator or forest
+pub(crate) const ABSENT: u8 = 0;
+
+/// Marker byte for an occupied position in accumulator or forest
+pub(crate) const PRESENT: u8 = 1;
+
+/// Number of hashes written to (or read from) a stream at once
+#[cfg(feature = "std")]
+pub(crate) const STREAM_CHUNK_LEN: usize = 1024;
+
+/// Converts a decoding error to an I/O error with `InvalidData` kind
+#[cfg(feature = "std")]
+pub(crate) fn invalid_data(error: UtreexoError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Splits salt (first 32 bytes) from encoded state of a salted accumulator or forest
+pub(crate) fn split_salt(bytes: &[u8]) -> Result<([u8; 32], &[u8]), UtreexoError> {
+    if bytes.len() < 32 {
+        return Err(UtreexoError::InvalidEncoding("Unexpected end of input"));
+    }
+
+    let (salt, state) = bytes.split_at(32);
+    Ok((
+        salt.try_into().expect("Salt should be exactly 32 bytes"),
+        state,
+    ))
+}
+
+/// Writes header (magic bytes and current version) of encoded state
+pub(crate) fn write_header(bytes: &mut Vec<u8>, magic: [u8; 4]) {
+    bytes.extend_from_slice(&magic);
+    bytes.push(VERSION);
+}
+
+/// Returns the number of roots (or trees) up to and including the tallest tree, i.e., the number of
+/// positions written to canonical encoding
+pub(crate) fn num_present<T>(roots: &[Option<T>]) -> usize {
+    roots
+        .iter()
+        .rposition(Option::is_some)
+        .map_or(0, |height| height + 1)
+}
+
+/// Writes number of roots followed by roots, where each root is either `ABSENT` or `PRESENT`
+/// followed by root hash. Empty positions after the tallest tree are not written.
+pub(crate) fn write_roots(bytes: &mut Vec<u8>, roots: &[Option<Hash>]) {
+    let roots = &roots[..num_present(roots)];
+
+    // Number of roots always fits in a byte because height of a tree cannot exceed
+    // `Proof::MAX_HEIGHT`
+    bytes.push(roots.len() as u8);
+
+    for root in roots.iter() {
+        match root {
+            None => bytes.push(ABSENT),
+            Some(root_hash) => {
+                bytes.push(PRESENT);
+                bytes.extend_from_slice(root_hash.as_bytes());
+            }
+        }
+    }
+}
+
+/// Returns `InvalidEncoding` error if the last decoded root (or tree) is absent, i.e., if the
+/// encoding is not canonical
+pub(crate) fn check_last_present<T>(roots: &[Option<T>]) -> Result<(), UtreexoError> {
+    match roots.last() {
+        Some(None) => Err(UtreexoError::InvalidEncoding("Last root should be present")),
+        _ => Ok(()),
+    }
+}
+
+/// Writes length of given bytes (4 bytes, big endian) followed by the bytes
+pub(crate) fn write_prefixed(bytes: &mut Vec<u8>, value: &[u8]) {
+    bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(value);
+}
+
+/// Reader over encoded bytes which returns `InvalidEncoding` error on malformed input
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a new reader over given bytes
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Reads given number of bytes
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], UtreexoError> {
+        if self.bytes.len() < len {
+            return Err(UtreexoError::InvalidEncoding("Unexpected end of input"));
+        }
+
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(bytes)
+    }
+
+    /// Reads one byte
+    pub(crate) fn read_u8(&mut self) -> Result<u8, UtreexoError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Reads a big endian `u32`
+    pub(crate) fn read_u32(&mut self) -> Result<u32, UtreexoError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes(
+            bytes.try_into().expect("Expected exactly 4 bytes"),
+        ))
+    }
+
+    /// Reads a big endian `u64`
+    pub(crate) fn read_u64(&mut self) -> Result<u64, UtreexoError> {
+        let bytes = self.read_bytes(8)?;
+        Ok(u64::from_be_bytes(
+            bytes.try_into().expect("Expected exactly 8 bytes"),
+        ))
+    }
+
+    /// Reads bytes prefixed with their length (see [`write_prefixed`])
+    pub(crate) fn read_prefixed(&mut self) -> Result<&'a [u8], UtreexoError> {
+        let len = self.read_u32()?;
+        self.read_bytes(usize::try_from(len).unwrap_or(usize::MAX))
+    }
+
+    /// Reads a hash
+    pub(crate) fn read_hash(&mut self) -> Result<Hash, UtreexoError> {
+        Hash::try_from(self.read_bytes(Hash::LEN)?)
+    }
+
+    /// Reads given number of hashes
+    pub(crate) fn read_hashes(&mut self, len: usize) -> Result<Vec<Hash>, UtreexoError> {
+        // Length is checked before allocating so that malformed input cannot cause huge allocations
+        if self.bytes.len() / Hash::LEN < len {
+            return Err(UtreexoError::InvalidEncoding("Unexpected end of input"));
+        }
+
+        (0..len).map(|_| self.read_hash()).collect()
+    }
+
+    /// Reads a marker byte and returns `true` if it is `PRESENT` and `false` if it is `ABSENT`
+    pub(crate) fn read_presence(&mut self) -> Result<bool, UtreexoError> {
+        match self.read_u8()? {
+            ABSENT => Ok(false),
+            PRESENT => Ok(true),
+            _ => Err(UtreexoError::InvalidEncoding("Invalid presence marker")),
+        }
+    }
+
+    /// Reads roots written by [`write_roots`]
+    pub(crate) fn read_roots(&mut self) -> Result<Vec<Option<Hash>>, UtreexoError> {
+        let num_roots = usize::from(self.read_u8()?);
+
+        if num_roots > Proof::MAX_HEIGHT + 1 {
+            return Err(UtreexoError::InvalidEncoding("Too many roots"));
+        }
+
+        let roots = (0..num_roots)
+            .map(|_| {
+                if self.read_presence()? {
+                    self.read_hash().map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        check_last_present(&roots)?;
+
+        Ok(roots)
+    }
+
+    /// Reads header of encoded state and checks magic bytes and version
+    pub(crate) fn read_header(&mut self, magic: [u8; 4]) -> Result<(), UtreexoError> {
+        if self.read_bytes(magic.len())? != magic {
+            return Err(UtreexoError::InvalidEncoding("Invalid magic bytes"));
+        }
+
+        match self.read_u8()? {
+            VERSION => Ok(()),
+            version => Err(UtreexoError::UnsupportedVersion {
+                version,
+                supported: VERSION,
+            }),
+        }
+    }
+
+    /// Returns an error if there are any unread bytes
+    pub(crate) fn finish(self) -> Result<(), UtreexoError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(UtreexoError::InvalidEncoding(
+                "Trailing bytes after encoded value",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_reader() {
+        let mut bytes = vec![1, 0, 2];
+        bytes.extend_from_slice(&[3; 32]);
+
+        let mut reader = Reader::new(&bytes);
+
+        assert_eq!(Ok(true), reader.read_presence());
+        assert_eq!(Ok(false), reader.read_presence());
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Invalid presence marker")),
+            reader.read_presence()
+        );
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unexpected end of input")),
+            reader.read_hashes(2)
+        );
+        assert_eq!(Ok(vec![Hash::from([3; 32])]), reader.read_hashes(1));
+        assert_eq!(Ok(()), reader.finish());
+    }
+
+    #[test]
+    fn check_header() {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, FOREST_MAGIC);
+
+        assert_eq!(b"UTRF\x01", &bytes[..]);
+        assert_eq!(Ok(()), Reader::new(&bytes).read_header(FOREST_MAGIC));
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Invalid magic bytes")),
+            Reader::new(&bytes).read_header(ACCUMULATOR_MAGIC)
+        );
+
+        bytes[4] = 2;
+        assert_eq!(
+            Err(UtreexoError::UnsupportedVersion {
+                version: 2,
+                supported: VERSION
+            }),
+            Reader::new(&bytes).read_header(FOREST_MAGIC)
+        );
+    }
+}
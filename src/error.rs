@@ -0,0 +1,175 @@
+#[cfg(feature = "json")]
+use alloc::string::String;
+use core::fmt;
+
+/// Errors returned by accumulator and forest operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UtreexoError {
+    /// Internal state of a merkle tree is corrupted (e.g., a deserialized tree does not contain a
+    /// node at an index derived from its number of leaves)
+    CorruptState {
+        /// Index of node which could not be accessed
+        index: usize,
+        /// Height of merkle tree
+        height: usize,
+    },
+    /// Checkpoint does not contain full merkle trees (i.e., it was created by an accumulator) and
+    /// cannot be restored in a forest
+    CheckpointWithoutTrees,
+    /// Proof of a leaf deleted by a block is not valid (after applying all the previous deletions
+    /// of the block)
+    InvalidDeletion {
+        /// Index of proof in deletions of block
+        index: usize,
+    },
+    /// Snapshot chunk is not a subtree of merkle trees with trusted root hashes (or it does not
+    /// have expected height)
+    InvalidChunk {
+        /// Height of merkle tree containing the chunk
+        tree_height: usize,
+        /// Index of chunk in merkle tree
+        index: usize,
+    },
+    /// Binary encoding of a proof, accumulator or forest is invalid
+    InvalidEncoding(&'static str),
+    /// Hash is not a valid encoding of 32 bytes (either raw or hex encoded)
+    InvalidHash,
+    /// Snapshot chunk is not added yet
+    MissingChunk {
+        /// Height of merkle tree containing the chunk
+        tree_height: usize,
+        /// Index of chunk in merkle tree
+        index: usize,
+    },
+    /// Height of path in proof does not match the number of sibling hashes
+    ProofLengthMismatch {
+        /// Height of path in proof
+        height: usize,
+        /// Number of sibling hashes in proof
+        num_sibling_hashes: usize,
+    },
+    /// Height of proof is greater than the maximum allowed height
+    ProofTooHigh {
+        /// Height of path in proof
+        height: usize,
+        /// Maximum allowed height
+        max_height: usize,
+    },
+    /// Deletion in operation log could not be replayed because its proof is not valid for the
+    /// state of accumulator at that point of log
+    ReplayFailed {
+        /// Index of operation in log
+        index: usize,
+    },
+    /// Root hash of merkle tree recomputed from leaves does not match the trusted root hash (or a
+    /// tree is missing on one side)
+    RootMismatch {
+        /// Height of merkle tree
+        height: usize,
+    },
+    /// Number of roots is greater than the maximum number of merkle trees in a forest (one for each
+    /// height up to `Proof::MAX_HEIGHT`)
+    TooManyRoots {
+        /// Number of roots
+        num_roots: usize,
+        /// Maximum allowed number of roots
+        max_num_roots: usize,
+    },
+    /// State of forest was not recorded at given block height (or it was pruned)
+    UnrecordedBlockHeight {
+        /// Block height
+        block_height: u64,
+    },
+    /// Encoded state was written with a format version which is not supported by this version of
+    /// the crate
+    UnsupportedVersion {
+        /// Version found in encoded state
+        version: u8,
+        /// Version supported by this version of the crate
+        supported: u8,
+    },
+    /// JSON representation of proof is invalid
+    #[cfg(feature = "json")]
+    InvalidJson(String),
+}
+
+impl fmt::Display for UtreexoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtreexoError::CorruptState { index, height } => write!(
+                f,
+                "Corrupt state: merkle tree of height {} has no node at index {}",
+                height, index
+            ),
+            UtreexoError::CheckpointWithoutTrees => write!(
+                f,
+                "Checkpoint does not contain merkle trees and cannot be restored in a forest"
+            ),
+            UtreexoError::InvalidDeletion { index } => {
+                write!(
+                    f,
+                    "Proof of deletion at index {} in block is not valid",
+                    index
+                )
+            }
+            UtreexoError::InvalidChunk { tree_height, index } => write!(
+                f,
+                "Chunk at index {} of merkle tree of height {} is not valid",
+                index, tree_height
+            ),
+            UtreexoError::InvalidEncoding(message) => write!(f, "Invalid encoding: {}", message),
+            UtreexoError::InvalidHash => write!(f, "Hash is not a valid encoding of 32 bytes"),
+            UtreexoError::MissingChunk { tree_height, index } => write!(
+                f,
+                "Chunk at index {} of merkle tree of height {} is missing",
+                index, tree_height
+            ),
+            UtreexoError::ProofLengthMismatch {
+                height,
+                num_sibling_hashes,
+            } => write!(
+                f,
+                "Height of path in proof ({}) does not match the number of sibling hashes ({})",
+                height, num_sibling_hashes
+            ),
+            UtreexoError::ProofTooHigh { height, max_height } => write!(
+                f,
+                "Height of proof ({}) is greater than maximum allowed height ({})",
+                height, max_height
+            ),
+            UtreexoError::ReplayFailed { index } => write!(
+                f,
+                "Deletion at index {} of operation log could not be replayed",
+                index
+            ),
+            UtreexoError::RootMismatch { height } => write!(
+                f,
+                "Recomputed root hash of merkle tree of height {} does not match trusted root hash",
+                height
+            ),
+            UtreexoError::TooManyRoots {
+                num_roots,
+                max_num_roots,
+            } => write!(
+                f,
+                "Number of roots ({}) is greater than maximum allowed number of roots ({})",
+                num_roots, max_num_roots
+            ),
+            UtreexoError::UnrecordedBlockHeight { block_height } => write!(
+                f,
+                "State of forest was not recorded at block height {}",
+                block_height
+            ),
+            UtreexoError::UnsupportedVersion { version, supported } => write!(
+                f,
+                "Unsupported encoding version {} (supported version: {})",
+                version, supported
+            ),
+            #[cfg(feature = "json")]
+            UtreexoError::InvalidJson(message) => write!(f, "Invalid JSON proof: {}", message),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UtreexoError {}
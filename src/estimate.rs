@@ -0,0 +1,157 @@
+//! Estimates of memory usage, proof sizes and hashing costs for capacity planning
+//!
+//! All the estimates are derived from the data structures used by [`MemoryForest`] and
+//! [`MemoryAccumulator`] and from the canonical binary encoding of [`Proof`].
+//!
+//! [`MemoryForest`]: crate::MemoryForest
+//! [`MemoryAccumulator`]: crate::MemoryAccumulator
+//! [`Proof`]: crate::Proof
+use core::{mem::size_of, ops::Add};
+
+use crate::{leaf_index::LeafIndex, Hash};
+
+/// Heap memory (in bytes) currently used by an accumulator or a forest (see
+/// [`MemoryForest::memory_usage`](crate::MemoryForest::memory_usage))
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Node hashes of merkle trees (or root hashes of an accumulator)
+    pub nodes: usize,
+    /// Index of leaves used for proof generation
+    pub leaf_index: usize,
+    /// Bookkeeping of trees (list of trees, reference counts and headers of node vectors)
+    pub overhead: usize,
+}
+
+impl MemoryUsage {
+    /// Returns total heap memory used
+    pub fn total(&self) -> usize {
+        self.nodes + self.leaf_index + self.overhead
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            nodes: self.nodes + other.nodes,
+            leaf_index: self.leaf_index + other.leaf_index,
+            overhead: self.overhead + other.overhead,
+        }
+    }
+}
+
+/// Returns the number of merkle tree nodes stored in a forest with given number of leaves
+///
+/// A forest contains one tree for every set bit in the number of leaves and a tree with `2^h`
+/// leaves contains `2^(h + 1) - 1` nodes. So, a forest with `n` leaves contains `2n - popcount(n)`
+/// nodes.
+pub fn forest_nodes(leaf_count: u64) -> u64 {
+    2 * leaf_count - u64::from(leaf_count.count_ones())
+}
+
+/// Returns approximate heap memory (in bytes) used by a `MemoryForest` with given number of leaves
+///
+/// This includes all the node hashes and the index of leaves used for proof generation (16 bytes
+/// per slot, and the index is kept at most 3/4 full), but ignores allocator overhead and spare
+/// capacity of vectors.
+pub fn forest_memory(leaf_count: u64) -> u64 {
+    let hash_len = size_of::<Hash>() as u64;
+
+    let nodes = forest_nodes(leaf_count) * hash_len;
+    let index = LeafIndex::capacity_for(leaf_count as usize) as u64 * 16;
+
+    nodes + index
+}
+
+/// Returns the maximum size (in bytes) of canonical binary encoding of an inclusion proof (see
+/// [`Proof::to_bytes`](crate::Proof::to_bytes)) in a forest with given number of leaves
+pub fn proof_size(leaf_count: u64) -> u64 {
+    match max_height(leaf_count) {
+        None => 0,
+        Some(height) => 1 + height.div_ceil(8) + (height + 1) * size_of::<Hash>() as u64,
+    }
+}
+
+/// Returns the maximum number of hash computations needed by a `MemoryAccumulator` with given
+/// number of leaves to process a block with given number of additions and deletions
+///
+/// Additions cost one leaf hash each, plus one intermediate hash for every merge of trees (exact,
+/// because it only depends on the number of leaves). Deletions cost at most `2h` intermediate
+/// hashes each (`h` for verifying the proof and `h` for updating roots), where `h` is the height
+/// of tallest tree after all the additions.
+pub fn hashes_per_block(leaf_count: u64, adds: u64, deletes: u64) -> u64 {
+    let final_count = leaf_count + adds;
+
+    // Inserting a leaf merges as many trees as the number of trailing ones in the number of
+    // leaves. Summing it over all the additions telescopes to the following expression.
+    let merges = u64::from(leaf_count.count_ones()) + adds - u64::from(final_count.count_ones());
+
+    let delete_hashes = max_height(final_count).map_or(0, |height| 2 * height * deletes);
+
+    adds + merges + delete_hashes
+}
+
+/// Returns height of the tallest tree in a forest with given number of leaves
+fn max_height(leaf_count: u64) -> Option<u64> {
+    if leaf_count == 0 {
+        None
+    } else {
+        Some(u64::from(63 - leaf_count.leading_zeros()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::{Blake3, Hasher, MemoryAccumulator, MemoryForest, Prover, Utreexo};
+
+    /// Hasher which counts the number of hash computations
+    struct CountingHasher<'a>(&'a Cell<u64>);
+
+    impl<'a> Hasher for CountingHasher<'a> {
+        fn hash_leaf(&self, value: &[u8]) -> Hash {
+            self.0.set(self.0.get() + 1);
+            Blake3.hash_leaf(value)
+        }
+
+        fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+            self.0.set(self.0.get() + 1);
+            Blake3.hash_intermediate(left, right)
+        }
+    }
+
+    #[test]
+    fn check_estimates() {
+        let mut forest = MemoryForest::new();
+
+        let hash_count = Cell::new(0);
+        let mut accumulator = MemoryAccumulator::with_hasher(CountingHasher(&hash_count));
+
+        for value in 0..11u8 {
+            forest.insert([value; 32]);
+            accumulator.insert([value; 32]);
+        }
+
+        let num_nodes: usize = forest.trees().flatten().map(|tree| tree.num_nodes()).sum();
+        assert_eq!(num_nodes as u64, forest_nodes(11));
+        assert!(forest_memory(11) > forest_nodes(11) * 32);
+
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert_eq!(proof.to_bytes().len() as u64, proof_size(11));
+        assert_eq!(0, proof_size(0));
+
+        assert_eq!(hashes_per_block(0, 11, 0), hash_count.get());
+
+        hash_count.set(0);
+        assert!(accumulator.delete(&proof));
+        assert!(hash_count.get() <= hashes_per_block(11, 0, 1));
+
+        let usage = forest.memory_usage();
+        assert_eq!(forest_memory(11), (usage.nodes + usage.leaf_index) as u64);
+        assert!(usage.total() > usage.nodes + usage.leaf_index);
+        assert_eq!(0, accumulator.memory_usage().leaf_index);
+    }
+}
@@ -1,14 +1,262 @@
-use alloc::vec::Vec;
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+#[cfg(any(feature = "std", feature = "serde-1"))]
+use core::convert::TryFrom;
+use core::{
+    fmt::Write as _,
+    mem::{self, size_of},
+    ops::Range,
+};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
 
 #[cfg(feature = "serde-1")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::{hash_leaf, merge, Direction, Proof, Prover, Tree, Utreexo};
+#[cfg(feature = "std")]
+use crate::encoding::{invalid_data, STREAM_CHUNK_LEN};
+use crate::{
+    bulk,
+    chunk::num_chunks,
+    encoding::{
+        check_last_present, num_present, split_salt, write_header, Reader, ABSENT, FOREST_MAGIC,
+        PRESENT,
+    },
+    estimate::MemoryUsage,
+    leaf_index::LeafIndex,
+    merge,
+    metrics::{count, Counter},
+    render::{render_tree, write_dot_tree},
+    BaggingOrder, Blake3, BlockChanges, Checkpoint, ForestDiff, ForestStats, Hash, Hasher,
+    KeyedBlake3, Proof, Prover, RootProof, RootWitness, SaltedBlake3, SnapshotChunk, Transaction,
+    Tree, TreeRef, UpdateData, Utreexo, UtreexoError,
+};
 
 /// Merkle forest
-#[derive(Debug, Default, Clone, Eq, PartialEq)]
+///
+/// Trees are reference counted and never mutated in place (insertions and deletions always create
+/// new trees). So, cloning a forest (or creating a checkpoint) only copies pointers to trees and
+/// the clones share all the nodes until they diverge.
+///
+/// Forest also maintains an index from every leaf hash to its position in forest (leaves are
+/// numbered in order of insertion, starting with leaves of the tallest tree), so, generating proofs
+/// (and checking membership) does not depend on the number of trees.
+///
+/// A leaf hash can be inserted more than once. Every copy is a separate leaf (with its own
+/// position), so, proofs are generated for one of the copies and each copy has to be deleted
+/// separately.
+///
+/// With `serde-1` feature, deserialized forests are validated in the same way as
+/// [`MemoryForest::verify_consistency`], i.e., a forest containing a tree whose number of nodes
+/// does not match its height or whose intermediate hashes do not match its leaves is rejected.
+///
+/// With `zeroize` feature, trees and index of leaves are wiped when they are dropped (or
+/// explicitly using `Zeroize`, which leaves an empty forest). Trees and chunks of index shared with
+/// clones, checkpoints or snapshots are wiped when the last of them is dropped. Buffers released
+/// when an internal vector grows (e.g., while a tree is built) are not wiped.
+#[derive(Debug, Default, Clone)]
 #[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
-pub struct MemoryForest(Vec<Option<Tree>>);
+#[cfg_attr(
+    feature = "serde-1",
+    serde(
+        try_from = "RawForest<H>",
+        bound(deserialize = "H: Hasher + Deserialize<'de>")
+    )
+)]
+pub struct MemoryForest<H = Blake3> {
+    /// Merkle trees in forest
+    trees: Vec<Option<Arc<Tree>>>,
+    /// Hasher used for calculating leaf and intermediate hashes
+    hasher: H,
+    /// Index of leaves in forest (this is derived from trees, so, it is never serialized). Chunks
+    /// of index are shared with snapshots and each chunk is copied on the first update of that
+    /// chunk after a snapshot is created.
+    #[cfg_attr(feature = "serde-1", serde(skip))]
+    index: LeafIndex,
+}
+
+impl<H: PartialEq> PartialEq for MemoryForest<H> {
+    fn eq(&self, other: &Self) -> bool {
+        // Index of leaves is derived from trees (and its layout depends on order of updates) and
+        // deletions may leave empty positions after the tallest tree, which are not part of state
+        self.trees[..num_present(&self.trees)] == other.trees[..num_present(&other.trees)]
+            && self.hasher == other.hasher
+    }
+}
+
+impl<H: Eq> Eq for MemoryForest<H> {}
+
+#[cfg(feature = "serde-1")]
+#[derive(Deserialize)]
+#[serde(rename = "MemoryForest")]
+struct RawForest<H> {
+    trees: Vec<Option<Arc<Tree>>>,
+    hasher: H,
+}
+
+#[cfg(feature = "serde-1")]
+impl<H: Hasher> TryFrom<RawForest<H>> for MemoryForest<H> {
+    type Error = UtreexoError;
+
+    fn try_from(raw: RawForest<H>) -> Result<Self, Self::Error> {
+        if raw.trees.len() > Proof::MAX_HEIGHT + 1 {
+            return Err(UtreexoError::InvalidEncoding("Too many trees"));
+        }
+
+        // Trees are checked before building index, so that leaves of a tampered forest are never
+        // indexed
+        for (height, tree) in raw.trees.iter().enumerate() {
+            if let Some(tree) = tree {
+                tree.check_consistency(&raw.hasher, height)?;
+            }
+        }
+
+        Ok(Self::from_trees(raw.trees, raw.hasher))
+    }
+}
+
+impl<H> MemoryForest<H> {
+    /// Returns hasher used by forest
+    pub(crate) fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Creates a forest from given trees and builds index of leaves
+    pub(crate) fn from_trees(trees: Vec<Option<Arc<Tree>>>, hasher: H) -> Self {
+        // Index is sized for all the leaves up front, so that it is not rehashed while it grows
+        let num_leaves = trees.iter().flatten().map(|tree| tree.num_leaves()).sum();
+
+        let mut forest = Self {
+            trees,
+            hasher,
+            index: LeafIndex::with_capacity(num_leaves),
+        };
+        forest.index_leaves(0..forest.trees.len());
+
+        forest
+    }
+
+    /// Adds leaves of trees with given heights to index
+    fn index_leaves(&mut self, heights: Range<usize>) {
+        let index = &mut self.index;
+        for_each_leaf(&self.trees, heights, |leaf_hash, position| {
+            index.insert(leaf_hash, position)
+        });
+    }
+
+    /// Updates positions of leaves in index after blocks of leaves are moved to other positions.
+    /// Every move is given as `(old position, new position, number of leaves)` of a block, and
+    /// leaf hashes are read from current trees (i.e., at new positions).
+    fn relocate_leaves(&mut self, moves: &[(u64, u64, u64)]) {
+        // Leaves are first moved to temporary positions (which can never be positions of leaves),
+        // so that a moved leaf is never confused with another leaf whose old position is the same
+        // as new position of the moved leaf (and whose leaf hash has the same prefix)
+        const TEMPORARY: u64 = 1 << 63;
+
+        let trees = &self.trees;
+        let index = &mut self.index;
+        let moves = moves.iter().filter(|(old, new, _)| old != new);
+
+        for &(old, new, len) in moves.clone() {
+            for (i, leaf_hash) in (0..len).zip(leaves_at(trees, new, len)) {
+                index.relocate(leaf_hash, old + i, TEMPORARY | (old + i));
+            }
+        }
+
+        for &(old, new, len) in moves {
+            for (i, leaf_hash) in (0..len).zip(leaves_at(trees, new, len)) {
+                index.relocate(leaf_hash, TEMPORARY | (old + i), new + i);
+            }
+        }
+    }
+}
+
+/// Returns the number of heights of trees in a forest which holds up to given number of leaves
+pub(crate) fn num_heights(num_leaves: usize) -> usize {
+    (usize::BITS - num_leaves.leading_zeros()) as usize
+}
+
+/// Calls given function with every leaf (and its position in forest) of trees with given heights
+fn for_each_leaf(
+    trees: &[Option<Arc<Tree>>],
+    heights: Range<usize>,
+    mut f: impl FnMut(&Hash, u64),
+) {
+    // Number of leaves in taller trees
+    let mut offset = 0;
+
+    for (height, tree) in trees.iter().enumerate().rev() {
+        if let Some(tree) = tree {
+            if heights.contains(&height) {
+                for (position, leaf_hash) in tree.leaves().enumerate() {
+                    f(leaf_hash, offset + position as u64);
+                }
+            }
+
+            offset += tree.num_leaves() as u64;
+        }
+    }
+}
+
+/// Returns tree containing leaf at given position in forest and position of leaf in that tree
+fn locate(trees: &[Option<Arc<Tree>>], position: u64) -> Option<(&Tree, usize)> {
+    let mut offset = 0;
+
+    for tree in trees.iter().rev().flatten() {
+        let num_leaves = tree.num_leaves() as u64;
+
+        if position < offset + num_leaves {
+            return Some((tree, (position - offset) as usize));
+        }
+
+        offset += num_leaves;
+    }
+
+    None
+}
+
+/// Returns leaf hashes of given number of consecutive leaves starting at given position in forest
+/// (leaves must be in the same tree)
+fn leaves_at(trees: &[Option<Arc<Tree>>], start: u64, len: u64) -> impl Iterator<Item = &Hash> {
+    let (tree, start) = locate(trees, start).expect("Expected leaf at given position");
+
+    (start..(start + len as usize)).map(move |position| {
+        tree.leaf(position)
+            .expect("Expected consecutive leaves in the same tree")
+    })
+}
+
+/// Returns position of first leaf of every tree in forest (indexed by height, leaves are numbered
+/// starting with leaves of the tallest tree)
+fn offsets(trees: &[Option<Arc<Tree>>]) -> Vec<u64> {
+    let mut offsets = vec![0; trees.len()];
+    let mut offset = 0;
+
+    for (height, tree) in trees.iter().enumerate().rev() {
+        offsets[height] = offset;
+
+        if tree.is_some() {
+            offset += 1 << height;
+        }
+    }
+
+    offsets
+}
+
+/// Returns read-only views of given trees
+fn tree_refs(
+    trees: &[Option<Arc<Tree>>],
+) -> impl ExactSizeIterator<Item = Option<TreeRef<'_>>> + DoubleEndedIterator {
+    trees.iter().map(|tree| tree.as_deref().map(TreeRef::from))
+}
+
+/// Returns position of given leaf hash in forest using given index of leaves, if present
+fn find(trees: &[Option<Arc<Tree>>], index: &LeafIndex, leaf_hash: &Hash) -> Option<u64> {
+    index.find(leaf_hash, |position| {
+        locate(trees, position).and_then(|(tree, position)| tree.leaf(position)) == Some(leaf_hash)
+    })
+}
 
 impl MemoryForest {
     /// Creates a new instance of memory forest
@@ -16,228 +264,2128 @@ impl MemoryForest {
         Default::default()
     }
 
-    /// Returns all the trees in merkle forest
-    pub fn trees(&self) -> &[Option<Tree>] {
-        &self.0
+    /// Creates a new instance of memory forest which can hold given number of leaves without
+    /// reallocating list of trees or index of leaves
+    pub fn with_capacity(num_leaves: usize) -> Self {
+        Self::with_capacity_and_hasher(num_leaves, Blake3)
     }
 
-    /// Verifies inclusion proof of a value in forest
-    fn verify(&self, proof: &Proof) -> bool {
-        let height = proof.path.height();
+    /// Creates a forest containing given values (see [`MemoryForest::from_leaves_with_hasher`])
+    pub fn from_leaves<T: AsRef<[u8]> + Sync>(leaf_values: &[T]) -> Self {
+        Self::from_leaves_with_hasher(leaf_values, Blake3)
+    }
 
-        if self.0.len() < (height + 1) {
-            return false;
+    /// Bootstraps a forest from trusted root hashes and leaf hashes (see
+    /// [`MemoryForest::bootstrap_with_hasher`])
+    pub fn bootstrap(
+        roots: &[Option<Hash>],
+        leaf_hashes: impl IntoIterator<Item = Hash>,
+    ) -> Result<Self, UtreexoError> {
+        Self::bootstrap_with_hasher(roots, leaf_hashes, Blake3)
+    }
+
+    /// Parses forest from its canonical binary encoding (see
+    /// [`MemoryForest::from_bytes_with_hasher`] for layout)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        Self::from_bytes_with_hasher(bytes, Blake3)
+    }
+
+    /// Parses forest from canonical binary encoding received from an untrusted source (see
+    /// [`MemoryForest::from_untrusted_bytes_with_hasher`])
+    pub fn from_untrusted_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        Self::from_untrusted_bytes_with_hasher(bytes, Blake3)
+    }
+}
+
+impl MemoryForest<KeyedBlake3> {
+    /// Creates a new instance of memory forest which uses keyed blake3 hashing with given key
+    pub fn new_keyed(key: [u8; blake3::KEY_LEN]) -> Self {
+        Self::with_hasher(KeyedBlake3::new(key))
+    }
+}
+
+impl MemoryForest<SaltedBlake3> {
+    /// Creates a new instance of memory forest which mixes given salt into leaf hashes (see
+    /// [`SaltedBlake3`])
+    pub fn new_salted(salt: [u8; blake3::KEY_LEN]) -> Self {
+        Self::with_hasher(SaltedBlake3::new(salt))
+    }
+
+    /// Returns salt mixed into leaf hashes
+    pub fn salt(&self) -> &[u8; blake3::KEY_LEN] {
+        self.hasher.salt()
+    }
+
+    /// Returns binary encoding of forest along with its salt (`salt (32 bytes) || canonical binary
+    /// encoding of forest`)
+    pub fn to_salted_bytes(&self) -> Vec<u8> {
+        [&self.salt()[..], &self.to_bytes()].concat()
+    }
+
+    /// Parses forest from its binary encoding along with its salt (see
+    /// [`MemoryForest::to_salted_bytes`]). Just like [`MemoryForest::from_bytes_with_hasher`],
+    /// this should only be used for trusted input.
+    pub fn from_salted_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        let (salt, state) = split_salt(bytes)?;
+        Self::from_bytes_with_hasher(state, SaltedBlake3::new(salt))
+    }
+}
+
+#[cfg(feature = "std")]
+impl MemoryForest {
+    /// Reads forest from a stream containing its canonical binary encoding (see
+    /// [`MemoryForest::read_from_with_hasher`])
+    pub fn read_from(reader: impl Read) -> io::Result<Self> {
+        Self::read_from_with_hasher(reader, Blake3)
+    }
+
+    /// Reads forest from a stream containing its zstd compressed encoding (see
+    /// [`MemoryForest::write_compressed_to`])
+    #[cfg(feature = "compress")]
+    pub fn read_compressed_from(reader: impl Read) -> io::Result<Self> {
+        Self::read_compressed_from_with_hasher(reader, Blake3)
+    }
+}
+
+impl<H: Hasher> MemoryForest<H> {
+    /// Creates a new instance of memory forest which uses given hasher
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::from_trees(Default::default(), hasher)
+    }
+
+    /// Creates a new instance of memory forest which uses given hasher and can hold given number of
+    /// leaves without reallocating list of trees or index of leaves
+    pub fn with_capacity_and_hasher(num_leaves: usize, hasher: H) -> Self {
+        Self {
+            trees: Vec::with_capacity(num_heights(num_leaves)),
+            hasher,
+            index: LeafIndex::with_capacity(num_leaves),
         }
+    }
 
-        if let Some(ref tree) = self.0[height] {
-            proof.verify(*tree.root_hash())
-        } else {
-            false
+    /// Creates a forest containing given values which uses given hasher. Resulting forest is same
+    /// as inserting the values one by one, but every tree is built bottom-up in one go instead of
+    /// repeatedly merging trees (with `rayon` feature, leaves are hashed and subtrees are built in
+    /// parallel).
+    pub fn from_leaves_with_hasher<T: AsRef<[u8]> + Sync>(leaf_values: &[T], hasher: H) -> Self
+    where
+        H: Sync,
+    {
+        let leaf_hashes = bulk::hash_leaves(&hasher, leaf_values);
+        Self::from_leaf_hashes(&leaf_hashes, hasher)
+    }
+
+    /// Bootstraps a forest, which uses given hasher, from trusted root hashes (indexed by height,
+    /// e.g., obtained from a checkpoint or a header commitment) and all the leaf hashes in order of
+    /// insertion (e.g., streamed from a snapshot). Trees are built in the same way as
+    /// [`MemoryForest::from_leaves_with_hasher`] (in parallel with `rayon` feature) and the
+    /// recomputed root hashes are compared with trusted root hashes before returning.
+    ///
+    /// Returns `RootMismatch` error with the lowest height at which recomputed root hash does not
+    /// match trusted root hash (including a missing or an extra tree).
+    pub fn bootstrap_with_hasher(
+        roots: &[Option<Hash>],
+        leaf_hashes: impl IntoIterator<Item = Hash>,
+        hasher: H,
+    ) -> Result<Self, UtreexoError>
+    where
+        H: Sync,
+    {
+        let leaf_hashes: Vec<Hash> = leaf_hashes.into_iter().collect();
+        let forest = Self::from_leaf_hashes(&leaf_hashes, hasher);
+        let computed = forest.roots();
+
+        for height in 0..roots.len().max(computed.len()) {
+            let expected = roots.get(height).copied().flatten();
+
+            if computed.get(height).copied().flatten() != expected {
+                return Err(UtreexoError::RootMismatch { height });
+            }
         }
+
+        Ok(forest)
     }
 
-    /// Returns a list of sibling trees corresponding to sibling hashes in proof. This function
-    /// assumes that the proof is valid and may panic if the proof is not checked before calling
-    /// this function.
-    fn get_sibling_trees(&self, proof: &Proof) -> Vec<Tree> {
-        let height = proof.path.height();
+    /// Creates a forest containing given leaf hashes (in order of insertion) by building every tree
+    /// bottom-up
+    fn from_leaf_hashes(leaf_hashes: &[Hash], hasher: H) -> Self
+    where
+        H: Sync,
+    {
+        count(Counter::Insert, leaf_hashes.len() as u64);
 
-        // This line panics if merkle tree of given height does not exist. Therefore, proof should
-        // be verified before calling this function.
-        let mut tree = self.0[height]
-            .as_ref()
-            .expect("Expected merkle tree of given proof height. Proof is not valid.")
-            .clone();
+        let subtrees = bulk::subtrees(leaf_hashes.len());
 
-        let mut sibling_trees = Vec::with_capacity(height);
+        let mut trees = Vec::new();
 
-        for (sibling_hash, direction) in proof
-            .sibling_hashes
-            .iter()
-            .rev()
-            .zip(proof.path.directions().rev())
-        {
-            match tree.split() {
-                (left_tree, Some(right_tree)) => match direction {
-                    Direction::Right => {
-                        assert_eq!(right_tree.root_hash(), sibling_hash, "Sibling hash does not match with sub-tree's root hash. Proof is invalid.");
-                        sibling_trees.push(right_tree);
-                        tree = left_tree;
-                    }
-                    Direction::Left => {
-                        assert_eq!(left_tree.root_hash(), sibling_hash, "Sibling hash does not match with sub-tree's root hash. Proof is invalid.");
-                        sibling_trees.push(left_tree);
-                        tree = right_tree;
-                    }
-                },
-                (_, None) => unreachable!(
-                    "Number of times a tree can be split shoule be equal to its height."
-                ),
+        for (height, range) in subtrees {
+            let tree = Tree::from_leaves(&hasher, &leaf_hashes[range]);
+
+            trees.resize(trees.len().max(height + 1), None);
+            trees[height] = Some(Arc::new(tree));
+        }
+
+        Self::from_trees(trees, hasher)
+    }
+
+    /// Returns read-only views of all the trees in merkle forest (indexed by height)
+    pub fn trees(
+        &self,
+    ) -> impl ExactSizeIterator<Item = Option<TreeRef<'_>>> + DoubleEndedIterator {
+        tree_refs(&self.trees)
+    }
+
+    /// Returns inclusion proof of leaf at given index in merkle tree of given height (leaves of a
+    /// tree are indexed in order of insertion), or `None` if there is no such leaf. Returns an
+    /// error if internal state of merkle forest is found to be corrupted.
+    pub fn prove_at(&self, height: usize, index: usize) -> Result<Option<Proof>, UtreexoError> {
+        let tree = match self.trees.get(height) {
+            Some(Some(tree)) => tree,
+            _ => return Ok(None),
+        };
+
+        match tree.leaf(index) {
+            Some(leaf_hash) => tree.prove_at(leaf_hash, index).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes all the leaves for which given predicate returns `false` and returns proofs of
+    /// deleted leaves (in the order of deletion), so that the same deletions can be applied to
+    /// accumulators tracking this forest. Returns an error (after deleting some of the leaves) if
+    /// internal state of merkle forest is found to be corrupted.
+    ///
+    /// Predicate is called exactly once for every leaf (in order of insertion). Leaves are deleted
+    /// in the same order, just like calling `prove()` and `delete()` for each of them, except that
+    /// the generated proofs are not verified again before deletion.
+    pub fn retain<F: FnMut(&Hash) -> bool>(
+        &mut self,
+        mut predicate: F,
+    ) -> Result<Vec<Proof>, UtreexoError> {
+        let mut leaf_hashes = Vec::new();
+
+        for_each_leaf(&self.trees, 0..self.trees.len(), |leaf_hash, _| {
+            if !predicate(leaf_hash) {
+                leaf_hashes.push(*leaf_hash);
             }
+        });
+
+        let mut proofs = Vec::with_capacity(leaf_hashes.len());
+
+        for leaf_hash in leaf_hashes {
+            let proof = self
+                .prove_hash(&leaf_hash)?
+                .expect("Expected leaf to be present in forest");
+
+            self.delete_verified(&proof);
+            proofs.push(proof);
         }
 
-        sibling_trees.reverse();
-        sibling_trees
+        Ok(proofs)
+    }
+
+    /// Inserts a value in forest and returns position of its leaf. Position of a leaf changes when
+    /// trees are merged (or split), so, it is only valid until the next update of forest.
+    pub fn insert_with_position<T: AsRef<[u8]>>(&mut self, leaf_value: T) -> LeafPosition {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_leaf(leaf_hash)
     }
-}
 
-impl Prover for MemoryForest {
-    fn prove<T: AsRef<[u8]>>(&self, leaf_value: T) -> Option<Proof> {
-        let leaf_hash = hash_leaf(leaf_value);
+    /// Inserts a value in forest and returns its position along with its inclusion proof. Just
+    /// like the position, the proof is only valid until the next update of forest. Returns an error
+    /// if internal state of merkle forest is found to be corrupted (value is inserted anyway).
+    pub fn insert_with_proof<T: AsRef<[u8]>>(
+        &mut self,
+        leaf_value: T,
+    ) -> Result<(LeafPosition, Proof), UtreexoError> {
+        let position = self.insert_with_position(leaf_value);
+        let proof = self
+            .prove_at(position.height, position.index)?
+            .expect("Expected inserted leaf to be present in forest");
 
-        for tree in self.0.iter() {
-            if let Some(ref tree) = tree {
-                let proof = tree.prove(&leaf_hash);
+        Ok((position, proof))
+    }
 
-                if proof.is_some() {
-                    return proof;
-                }
+    /// Returns read-only view of merkle tree of given height, if present
+    pub fn tree(&self, height: usize) -> Option<TreeRef<'_>> {
+        self.trees.get(height)?.as_deref().map(TreeRef::from)
+    }
+
+    /// Returns root hashes of all the trees in merkle forest (indexed by height, same as
+    /// [`MemoryAccumulator::root_hashes`](crate::MemoryAccumulator::root_hashes))
+    pub fn roots(&self) -> Vec<Option<Hash>> {
+        self.trees
+            .iter()
+            .map(|tree| tree.as_ref().map(|tree| *tree.root_hash()))
+            .collect()
+    }
+
+    /// Starts a transaction which stages insertions and deletions without updating forest until
+    /// the transaction is committed (see [`Transaction`])
+    pub fn transaction(&mut self) -> Transaction<'_, H>
+    where
+        H: Clone,
+    {
+        Transaction::new(self)
+    }
+
+    /// Returns an immutable snapshot of forest which can be used for generating proofs from other
+    /// threads. Snapshot shares trees with forest, so, it only copies pointers to trees.
+    pub fn snapshot(&self) -> ForestSnapshot<H>
+    where
+        H: Clone,
+    {
+        ForestSnapshot {
+            trees: self.trees.clone(),
+            hasher: self.hasher.clone(),
+            index: self.index.clone(),
+        }
+    }
+
+    /// Returns a checkpoint of current state of forest, including full merkle trees and index of
+    /// leaves (see [`Checkpoint`])
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            roots: self.roots(),
+            trees: Some(self.trees.clone()),
+            index: Some(self.index.clone()),
+        }
+    }
+
+    /// Restores state of forest from given checkpoint. Returns `CheckpointWithoutTrees` error (and
+    /// leaves forest unchanged) if the checkpoint was created by an accumulator.
+    ///
+    /// Index of leaves is restored from checkpoint as well, so, this does not depend on the number
+    /// of leaves.
+    pub fn restore(&mut self, checkpoint: Checkpoint) -> Result<(), UtreexoError> {
+        let (trees, index) = checkpoint
+            .trees
+            .zip(checkpoint.index)
+            .ok_or(UtreexoError::CheckpointWithoutTrees)?;
+
+        self.trees = trees;
+        self.index = index;
+
+        Ok(())
+    }
+
+    /// Applies a block to forest, i.e., deletes leaves with given proofs (in order, every proof
+    /// should be valid after all the previous deletions) and then inserts given values. Returns new
+    /// root hashes along with update data for remote clients and undo data of the block (see
+    /// [`MemoryAccumulator::apply_block`](crate::MemoryAccumulator::apply_block)).
+    ///
+    /// Returns `InvalidDeletion` error (and leaves forest unchanged) if any of the proofs is not
+    /// valid. Forest is restored from undo data of the block (which shares trees and chunks of
+    /// index of leaves with the forest), so, a failed block does not rebuild index of leaves.
+    pub fn apply_block<T: AsRef<[u8]>>(
+        &mut self,
+        adds: &[T],
+        dels: &[Proof],
+    ) -> Result<BlockChanges, UtreexoError> {
+        let undo = self.checkpoint();
+
+        for (index, proof) in dels.iter().enumerate() {
+            if !self.delete(proof) {
+                self.restore(undo)?;
+                return Err(UtreexoError::InvalidDeletion { index });
             }
         }
 
-        None
+        let additions: Vec<Hash> = adds
+            .iter()
+            .map(|leaf_value| self.hasher.hash_leaf(leaf_value.as_ref()))
+            .collect();
+
+        for leaf_hash in additions.iter() {
+            self.insert_leaf(*leaf_hash);
+        }
+
+        Ok(BlockChanges {
+            roots: self.roots(),
+            update: UpdateData::new(undo.roots.clone(), dels.to_vec(), additions),
+            undo,
+        })
     }
-}
 
-impl Utreexo for MemoryForest {
-    fn insert<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
-        let leaf_hash = hash_leaf(leaf_value);
-        let mut new_tree = Tree::new(leaf_hash);
+    /// Returns `true` if given value is present in forest
+    pub fn contains<T: AsRef<[u8]>>(&self, leaf_value: T) -> bool {
+        self.contains_hash(&self.hasher.hash_leaf(leaf_value.as_ref()))
+    }
 
-        for tree in self.0.iter_mut() {
-            match tree {
-                Some(ref old_tree) => {
-                    new_tree = merge(old_tree, &new_tree);
-                    *tree = None;
-                }
-                None => {
-                    *tree = Some(new_tree);
-                    return;
-                }
+    /// Returns `true` if a leaf with given hash is present in forest. This only looks up index of
+    /// leaves, so it is much cheaper than generating a proof.
+    pub fn contains_hash(&self, leaf_hash: &Hash) -> bool {
+        find(&self.trees, &self.index, leaf_hash).is_some()
+    }
+
+    /// Returns `index`th chunk of given height of merkle tree of given height (see
+    /// [`MemoryForest::chunks`]), if present
+    pub fn chunk(
+        &self,
+        tree_height: usize,
+        chunk_height: usize,
+        index: usize,
+    ) -> Option<SnapshotChunk> {
+        let tree = self.trees.get(tree_height)?.as_ref()?;
+        SnapshotChunk::from_tree(tree, tree_height, chunk_height, index)
+    }
+
+    /// Returns all the chunks of forest, i.e., every merkle tree split into subtrees of given
+    /// height (trees not taller than chunk height are not split), starting with the shortest tree.
+    /// Every chunk can be verified against root hashes of forest on its own.
+    pub fn chunks(&self, chunk_height: usize) -> impl Iterator<Item = SnapshotChunk> + '_ {
+        self.trees
+            .iter()
+            .enumerate()
+            .filter_map(|(tree_height, tree)| Some((tree_height, tree.as_ref()?)))
+            .flat_map(move |(tree_height, tree)| {
+                (0..num_chunks(tree_height, chunk_height)).map(move |index| {
+                    SnapshotChunk::from_tree(tree, tree_height, chunk_height, index)
+                        .expect("Expected chunk at every index of tree")
+                })
+            })
+    }
+
+    /// Returns ASCII rendering of all the merkle trees in forest (starting with the tallest tree),
+    /// e.g., for debugging. Every node is labelled with hex encoding of first `hash_len` bytes of
+    /// its hash (or `*` if `hash_len` is zero) and every leaf is also labelled with its position in
+    /// forest.
+    ///
+    /// ```text
+    /// height 1 (2 leaves)
+    /// 5fa1
+    /// +-- 2ab4 (leaf 0)
+    /// `-- 9c07 (leaf 1)
+    /// height 0 (1 leaf)
+    /// e3d2 (leaf 2)
+    /// ```
+    pub fn render(&self, hash_len: usize) -> String {
+        let mut out = String::new();
+        let mut first_leaf = 0;
+
+        for (height, tree) in self.trees.iter().enumerate().rev() {
+            if let Some(tree) = tree {
+                let num_leaves = 1u64 << height;
+                let unit = if num_leaves == 1 { "leaf" } else { "leaves" };
+                let _ = writeln!(out, "height {} ({} {})", height, num_leaves, unit);
+
+                render_tree(&mut out, tree.nodes(), hash_len, first_leaf);
+                first_leaf += num_leaves;
             }
         }
 
-        self.0.push(Some(new_tree));
+        out
     }
 
-    fn delete(&mut self, proof: &Proof) -> bool {
-        // Proof should be valid to delete a value from accumulator
-        if !self.verify(proof) {
-            return false;
+    /// Returns DOT graph (e.g., for rendering with Graphviz) of all the merkle trees in forest,
+    /// with one cluster for every tree. Nodes are labelled with first 4 bytes of their hashes.
+    ///
+    /// If a leaf hash is given, the graph is limited to the nodes of inclusion proof of that leaf,
+    /// i.e., the path from the leaf to root (highlighted) and siblings of nodes on the path. The
+    /// graph is empty if the leaf is not present in forest.
+    pub fn to_dot(&self, leaf_hash: Option<&Hash>) -> String {
+        let focus = match leaf_hash {
+            Some(leaf_hash) => {
+                match find(&self.trees, &self.index, leaf_hash)
+                    .and_then(|position| locate(&self.trees, position))
+                {
+                    Some((tree, position)) => Some((tree.height(), position)),
+                    None => return String::from("digraph forest {\n}\n"),
+                }
+            }
+            None => None,
+        };
+
+        let mut out = String::from("digraph forest {\n    node [fontname=\"monospace\"];\n");
+
+        for (height, tree) in self.trees.iter().enumerate().rev() {
+            let tree = match (tree, focus) {
+                (Some(tree), None) => tree,
+                (Some(tree), Some((focus, _))) if focus == height => tree,
+                _ => continue,
+            };
+
+            let _ = writeln!(out, "    subgraph cluster_{} {{", height);
+            let _ = writeln!(out, "        label=\"height {}\";", height);
+            write_dot_tree(
+                &mut out,
+                "        ",
+                &format!("t{}_", height),
+                tree.nodes(),
+                focus.map(|(_, position)| position),
+            );
+            out.push_str("    }\n");
         }
 
-        let height = proof.path.height();
-        let sibling_trees = self.get_sibling_trees(&proof);
+        out.push_str("}\n");
+        out
+    }
 
-        let mut new_tree = None;
+    /// Computes difference between this forest and other forest, i.e., leaves present only on
+    /// each side and heights of merkle trees whose root hashes differ
+    pub fn diff(&self, other: &Self) -> ForestDiff {
+        let mut diff = ForestDiff::default();
 
-        for (tree, sibling_tree) in self
-            .0
-            .iter_mut()
-            .take(height)
-            .zip(sibling_trees.into_iter())
-        {
-            if let Some(ref mut new_tree) = new_tree {
-                *new_tree = merge(&sibling_tree, new_tree);
-            } else if tree.is_none() {
-                *tree = Some(sibling_tree)
-            } else {
-                // `unwrap()` is safe here because `None` condition was checked earlier
-                new_tree = Some(merge(&sibling_tree, tree.as_ref().unwrap()));
-                *tree = None;
+        for height in 0..self.trees.len().max(other.trees.len()) {
+            let tree = self.trees.get(height).and_then(Option::as_ref);
+            let other_tree = other.trees.get(height).and_then(Option::as_ref);
+
+            if tree.map(|tree| tree.root_hash()) == other_tree.map(|tree| tree.root_hash()) {
+                continue;
             }
+
+            diff.differing_roots.push(height);
+
+            diff.only_in_self.extend(
+                tree.into_iter()
+                    .flat_map(|tree| tree.leaves())
+                    .filter(|leaf_hash| !other.contains_hash(leaf_hash)),
+            );
+            diff.only_in_other.extend(
+                other_tree
+                    .into_iter()
+                    .flat_map(|tree| tree.leaves())
+                    .filter(|leaf_hash| !self.contains_hash(leaf_hash)),
+            );
         }
 
-        self.0[height] = new_tree;
-        true
+        diff
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Returns summary of shape of forest (number of leaves, heights of trees, etc.)
+    pub fn stats(&self) -> ForestStats {
+        ForestStats::from_heights(self.trees.iter().map(Option::is_some))
+    }
 
-    #[test]
-    fn check_memory_forest_ops() {
-        let mut forest = MemoryForest::new();
+    /// Returns heap memory currently used by forest. Trees (and index of leaves) shared with
+    /// clones, checkpoints or snapshots of forest are counted in full.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        // Reference counts are allocated along with the value
+        let arc_header = 2 * size_of::<usize>();
 
-        forest.insert([0; 32]);
-        forest.insert([1; 32]);
-        forest.insert([2; 32]);
-        forest.insert([3; 32]);
-        forest.insert([4; 32]);
-        forest.insert([5; 32]);
-        forest.insert([6; 32]);
-        forest.insert([7; 32]);
-        forest.insert([8; 32]);
-        forest.insert([9; 32]);
+        let trees = self.trees.iter().flatten();
 
-        // Checking distribution of trees in merkle forest
-        assert_eq!(4, forest.0.len());
-        assert!(forest.0[0].is_none());
-        assert!(forest.0[1].is_some());
-        assert!(forest.0[2].is_none());
-        assert!(forest.0[3].is_some());
+        MemoryUsage {
+            nodes: trees.clone().map(|tree| tree.heap_size()).sum(),
+            leaf_index: self.index.heap_size(),
+            overhead: self.trees.capacity() * size_of::<Option<Arc<Tree>>>()
+                + trees.count() * (arc_header + size_of::<Tree>())
+                + self.index.overhead(),
+        }
+    }
 
-        // Delete a leaf
-        let proof = forest.prove(&[0; 32]);
-        assert!(proof.is_some());
-        assert!(forest.delete(&proof.unwrap()));
+    /// Reserves capacity for at least `expected_leaves` more leaves, so that they can be inserted
+    /// without reallocating list of trees or rehashing index of leaves (e.g., after bootstrapping a
+    /// forest from a snapshot which is expected to grow by a known number of leaves)
+    pub fn reserve(&mut self, expected_leaves: usize) {
+        let num_leaves = self.num_leaves() as usize + expected_leaves;
 
-        // Checking distribution of trees in merkle forest
-        assert_eq!(4, forest.0.len());
-        assert!(forest.0[0].is_some());
-        assert!(forest.0[1].is_none());
-        assert!(forest.0[2].is_none());
-        assert!(forest.0[3].is_some());
+        self.trees
+            .reserve_exact(num_heights(num_leaves).saturating_sub(self.trees.len()));
+        self.index.reserve(expected_leaves);
+    }
 
-        // Delete a leaf
-        let proof = forest.prove(&[1; 32]);
-        assert!(proof.is_some());
-        assert!(forest.delete(&proof.unwrap()));
+    /// Releases unused capacity of internal vectors and shrinks index of leaves to the minimum size
+    /// needed for current number of leaves (index never shrinks on its own when leaves are
+    /// deleted). This is useful during quiet periods after large batches of deletions.
+    ///
+    /// Trees shared with clones, checkpoints or snapshots of forest are left untouched, and if the
+    /// index is shared, a compacted copy is created.
+    pub fn compact(&mut self) {
+        self.trees.shrink_to_fit();
 
-        // Checking distribution of trees in merkle forest
-        assert_eq!(4, forest.0.len());
-        assert!(forest.0[0].is_none());
-        assert!(forest.0[1].is_none());
-        assert!(forest.0[2].is_none());
-        assert!(forest.0[3].is_some());
+        for tree in self.trees.iter_mut().flatten() {
+            if let Some(tree) = Arc::get_mut(tree) {
+                tree.shrink_to_fit();
+            }
+        }
 
-        // Delete a leaf
-        let proof = forest.prove(&[2; 32]);
-        assert!(proof.is_some());
-        assert!(forest.delete(&proof.unwrap()));
+        self.index.shrink_to_fit();
+    }
 
-        // Checking distribution of trees in merkle forest
-        assert_eq!(4, forest.0.len());
-        assert!(forest.0[0].is_some());
-        assert!(forest.0[1].is_some());
-        assert!(forest.0[2].is_some());
-        assert!(forest.0[3].is_none());
+    /// Removes all the trees from forest (hasher is kept)
+    pub fn clear(&mut self) {
+        self.trees.clear();
+        self.index = Default::default();
+    }
 
-        // Delete a leaf
-        let proof = forest.prove(&[3; 32]);
-        assert!(proof.is_some());
-        assert!(forest.delete(&proof.unwrap()));
+    /// Removes all the trees from forest and returns an iterator over their leaf hashes (in order
+    /// of insertion). Forest is emptied even if the iterator is not consumed.
+    pub fn drain(&mut self) -> impl Iterator<Item = Hash> {
+        let trees = mem::take(&mut self.trees);
+        self.index = Default::default();
 
-        // Checking distribution of trees in merkle forest
-        assert_eq!(4, forest.0.len());
-        assert!(forest.0[0].is_none());
-        assert!(forest.0[1].is_some());
-        assert!(forest.0[2].is_some());
-        assert!(forest.0[3].is_none());
+        trees.into_iter().rev().flatten().flat_map(|tree| {
+            (0..tree.num_leaves()).map(move |position| {
+                *tree
+                    .leaf(position)
+                    .expect("Expected leaf at every position of tree")
+            })
+        })
+    }
 
-        // Add a leaf
-        forest.insert([0; 32]);
+    /// Returns compact root witness of forest which can be used for verifying inclusion proofs
+    pub fn root_witness(&self) -> RootWitness {
+        RootWitness::from_roots(
+            self.trees
+                .iter()
+                .map(|tree| tree.as_ref().map(|tree| *tree.root_hash())),
+        )
+    }
 
-        // Checking distribution of trees in merkle forest
-        assert_eq!(4, forest.0.len());
-        assert!(forest.0[0].is_some());
-        assert!(forest.0[1].is_some());
-        assert!(forest.0[2].is_some());
-        assert!(forest.0[3].is_none());
+    /// Returns a single hash committing to all the roots of forest (see
+    /// [`RootWitness::commitment`]). Accumulators and forests with the same leaves and hasher have
+    /// the same root commitment.
+    pub fn root_commitment(&self) -> Hash {
+        self.root_witness().commitment(&self.hasher)
+    }
 
-        // Check proof of a value not present in the set
-        assert!(forest.prove(&[1; 32]).is_none());
+    /// Returns a single hash committing to all the roots of forest, with roots bagged in given
+    /// order (see [`RootWitness::commitment_with_order`])
+    pub fn root_commitment_with_order(&self, order: BaggingOrder) -> Hash {
+        self.root_witness()
+            .commitment_with_order(&self.hasher, order)
+    }
+
+    /// Returns proof of inclusion of root hash of merkle tree of given height in root commitment of
+    /// forest (see [`RootWitness::prove_root`]), if there is a tree of given height
+    pub fn prove_root(&self, height: usize) -> Option<RootProof> {
+        self.root_witness().prove_root(&self.hasher, height)
+    }
+
+    /// Returns proof of inclusion of root hash of merkle tree of given height in root commitment of
+    /// forest with roots bagged in given order (see [`RootWitness::prove_root_with_order`]), if
+    /// there is a tree of given height
+    pub fn prove_root_with_order(&self, height: usize, order: BaggingOrder) -> Option<RootProof> {
+        self.root_witness()
+            .prove_root_with_order(&self.hasher, height, order)
+    }
+
+    /// Returns canonical binary encoding of forest (hasher is not encoded). Forests with the same
+    /// trees always have the same encoding, independent of how they were built.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let trees = &self.trees[..num_present(&self.trees)];
+        let num_nodes: usize = trees.iter().flatten().map(|tree| tree.nodes().len()).sum();
+        let mut bytes = Vec::with_capacity(5 + 1 + trees.len() + num_nodes * Hash::LEN);
+
+        write_header(&mut bytes, FOREST_MAGIC);
+
+        // Number of trees always fits in a byte because height of a tree cannot exceed
+        // `Proof::MAX_HEIGHT`
+        bytes.push(trees.len() as u8);
+
+        for tree in trees.iter() {
+            match tree {
+                None => bytes.push(ABSENT),
+                Some(tree) => {
+                    bytes.push(PRESENT);
+
+                    for node in tree.nodes() {
+                        bytes.extend_from_slice(node.as_bytes());
+                    }
+                }
+            }
+        }
+
+        bytes
+    }
+
+    /// Parses forest, which uses given hasher, from its canonical binary encoding
+    ///
+    /// Layout: `b"UTRF" || version (1 byte) || number of trees (1 byte) || trees`, where each tree
+    /// is either `0x00` (no tree of that height) or `0x01 || nodes`. Tree at position `i` contains
+    /// exactly `2^(i + 1) - 1` node hashes in post-order and last tree should be present. Leaves
+    /// are not encoded separately because they are derived from nodes. Returns `UnsupportedVersion`
+    /// error if state was encoded with a different format version.
+    ///
+    /// Note that intermediate hashes are not recalculated while parsing, so, this should only be
+    /// used for trusted input (e.g. snapshots written by the same node). Use
+    /// [`MemoryForest::from_untrusted_bytes_with_hasher`] for untrusted input.
+    pub fn from_bytes_with_hasher(bytes: &[u8], hasher: H) -> Result<Self, UtreexoError> {
+        let mut reader = Reader::new(bytes);
+        reader.read_header(FOREST_MAGIC)?;
+
+        let num_trees = usize::from(reader.read_u8()?);
+
+        if num_trees > Proof::MAX_HEIGHT + 1 {
+            return Err(UtreexoError::InvalidEncoding("Too many trees"));
+        }
+
+        let trees = (0..num_trees)
+            .map(|height| {
+                if !reader.read_presence()? {
+                    return Ok(None);
+                }
+
+                let num_nodes = 1usize
+                    .checked_shl((height + 1) as u32)
+                    .map_or(usize::MAX, |num| num - 1);
+                let nodes = reader.read_hashes(num_nodes)?;
+
+                let tree = Tree::from_nodes(height, nodes)
+                    .expect("Expected exactly 2^(h + 1) - 1 nodes in tree of height h");
+
+                Ok(Some(Arc::new(tree)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        check_last_present(&trees)?;
+        reader.finish()?;
+
+        Ok(Self::from_trees(trees, hasher))
+    }
+
+    /// Writes canonical binary encoding of forest (see [`MemoryForest::to_bytes`]) to given stream.
+    /// Nodes are written in small chunks, so, the full encoding is never materialized in memory.
+    ///
+    /// Note that this function issues many small writes. So, it is recommended to use a buffered
+    /// writer (e.g. `std::io::BufWriter`).
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let trees = &self.trees[..num_present(&self.trees)];
+
+        let mut header = Vec::with_capacity(5 + 1);
+        write_header(&mut header, FOREST_MAGIC);
+        header.push(trees.len() as u8);
+
+        writer.write_all(&header)?;
+
+        let mut chunk_bytes = Vec::with_capacity(STREAM_CHUNK_LEN * Hash::LEN);
+
+        for tree in trees.iter() {
+            match tree {
+                None => writer.write_all(&[ABSENT])?,
+                Some(tree) => {
+                    writer.write_all(&[PRESENT])?;
+
+                    for chunk in tree.nodes().chunks(STREAM_CHUNK_LEN) {
+                        chunk_bytes.clear();
+
+                        for node in chunk {
+                            chunk_bytes.extend_from_slice(node.as_bytes());
+                        }
+
+                        writer.write_all(&chunk_bytes)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads forest, which uses given hasher, from a stream containing its canonical binary
+    /// encoding (see [`MemoryForest::from_bytes_with_hasher`] for layout). Nodes are read in small
+    /// chunks, so, the full encoding is never materialized in memory.
+    ///
+    /// Exactly one encoded forest is read from the stream. Decoding errors are returned as I/O
+    /// errors of `InvalidData` kind wrapping a [`UtreexoError`].
+    #[cfg(feature = "std")]
+    pub fn read_from_with_hasher(mut reader: impl Read, hasher: H) -> io::Result<Self> {
+        let mut header = [0; 5 + 1];
+        reader.read_exact(&mut header)?;
+
+        let mut header_reader = Reader::new(&header);
+        header_reader
+            .read_header(FOREST_MAGIC)
+            .map_err(invalid_data)?;
+
+        let num_trees = usize::from(header_reader.read_u8().map_err(invalid_data)?);
+
+        if num_trees > Proof::MAX_HEIGHT + 1 {
+            return Err(invalid_data(UtreexoError::InvalidEncoding(
+                "Too many trees",
+            )));
+        }
+
+        let mut trees = Vec::with_capacity(num_trees);
+        let mut chunk_bytes = vec![0; STREAM_CHUNK_LEN * Hash::LEN];
+
+        for height in 0..num_trees {
+            let mut marker = [0];
+            reader.read_exact(&mut marker)?;
+
+            if !Reader::new(&marker).read_presence().map_err(invalid_data)? {
+                trees.push(None);
+                continue;
+            }
+
+            let num_nodes = 1usize
+                .checked_shl((height + 1) as u32)
+                .map_or(usize::MAX, |num| num - 1);
+
+            // Capacity is bounded so that malformed input cannot cause huge allocations
+            let mut nodes = Vec::with_capacity(num_nodes.min(STREAM_CHUNK_LEN));
+
+            while nodes.len() < num_nodes {
+                let chunk_len = (num_nodes - nodes.len()).min(STREAM_CHUNK_LEN);
+                let chunk_bytes = &mut chunk_bytes[..chunk_len * Hash::LEN];
+
+                reader.read_exact(chunk_bytes)?;
+
+                nodes.extend(
+                    chunk_bytes
+                        .chunks(Hash::LEN)
+                        .map(|hash| Hash::try_from(hash).expect("Expected exactly 32 bytes")),
+                );
+            }
+
+            let tree = Tree::from_nodes(height, nodes)
+                .expect("Expected exactly 2^(h + 1) - 1 nodes in tree of height h");
+
+            trees.push(Some(Arc::new(tree)));
+        }
+
+        check_last_present(&trees).map_err(invalid_data)?;
+
+        Ok(Self::from_trees(trees, hasher))
+    }
+
+    /// Writes zstd compressed canonical binary encoding of forest to given stream with given
+    /// compression level (`0` selects zstd's default level)
+    #[cfg(feature = "compress")]
+    pub fn write_compressed_to(&self, writer: impl Write, level: i32) -> io::Result<()> {
+        let mut encoder = zstd::stream::Encoder::new(writer, level)?;
+        self.write_to(&mut encoder)?;
+        encoder.finish()?;
+
+        Ok(())
+    }
+
+    /// Reads forest, which uses given hasher, from a stream containing its zstd compressed
+    /// encoding (see [`MemoryForest::write_compressed_to`])
+    #[cfg(feature = "compress")]
+    pub fn read_compressed_from_with_hasher(reader: impl Read, hasher: H) -> io::Result<Self> {
+        Self::read_from_with_hasher(zstd::stream::Decoder::new(reader)?, hasher)
+    }
+
+    /// Parses forest, which uses given hasher, from canonical binary encoding received from an
+    /// untrusted source. In addition to [`MemoryForest::from_bytes_with_hasher`], all the
+    /// intermediate nodes are recalculated with given hasher and compared with encoded ones.
+    /// Returns `CorruptState` error with height of tree and index of the first inconsistent node
+    /// otherwise.
+    pub fn from_untrusted_bytes_with_hasher(bytes: &[u8], hasher: H) -> Result<Self, UtreexoError> {
+        let forest = Self::from_bytes_with_hasher(bytes, hasher)?;
+        forest.verify_consistency()?;
+
+        Ok(forest)
+    }
+
+    /// Walks every tree in forest and checks that it is a consistent merkle tree, i.e., tree at
+    /// position `h` contains exactly `2^(h + 1) - 1` nodes and all the intermediate hashes are
+    /// recalculated from leaves with forest's hasher.
+    ///
+    /// Returns `CorruptState` error with height of tree and index of the first inconsistent node.
+    /// This is useful for sanity checking long running in-memory state (deserialized forests are
+    /// already checked while decoding).
+    pub fn verify_consistency(&self) -> Result<(), UtreexoError> {
+        for (height, tree) in self.trees.iter().enumerate() {
+            if let Some(tree) = tree {
+                tree.check_consistency(&self.hasher, height)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recalculates all the intermediate hashes of every tree in forest from its leaves with
+    /// forest's hasher, e.g., for repairing state after [`MemoryForest::verify_consistency`]
+    /// detects corruption. Returns `CorruptState` error if the number of leaves in a tree does not
+    /// match its height, in which case, the tree can not be repaired.
+    pub fn rebuild(&mut self) -> Result<(), UtreexoError> {
+        for (height, tree) in self.trees.iter_mut().enumerate() {
+            if let Some(tree) = tree {
+                Arc::make_mut(tree).rebuild(&self.hasher, height)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verifies inclusion proof of a value in forest
+    fn verify(&self, proof: &Proof) -> bool {
+        let height = proof.path.height();
+
+        if self.trees.len() < (height + 1) {
+            return false;
+        }
+
+        if let Some(ref tree) = self.trees[height] {
+            proof.verify(&self.hasher, *tree.root_hash())
+        } else {
+            false
+        }
+    }
+
+    /// Inserts an already hashed leaf in forest and returns its position
+    pub(crate) fn insert_leaf(&mut self, leaf_hash: Hash) -> LeafPosition {
+        count(Counter::Insert, 1);
+
+        // Merging trees does not change order of existing leaves, so, only the new leaf is indexed
+        let position = self.num_leaves();
+        self.index.insert(&leaf_hash, position);
+
+        let mut new_tree = Tree::new(leaf_hash);
+
+        for (height, tree) in self.trees.iter_mut().enumerate() {
+            match tree {
+                Some(ref old_tree) => {
+                    new_tree = merge(&self.hasher, old_tree, &new_tree);
+                    *tree = None;
+                }
+                None => {
+                    *tree = Some(Arc::new(new_tree));
+                    return LeafPosition::last(height);
+                }
+            }
+        }
+
+        self.trees.push(Some(Arc::new(new_tree)));
+        LeafPosition::last(self.trees.len() - 1)
+    }
+
+    /// Deletes leaf corresponding to given proof from forest. This function assumes that the proof
+    /// is valid and may panic if the proof is not checked before calling this function.
+    fn delete_verified(&mut self, proof: &Proof) {
+        let height = proof.path.height();
+        let sibling_trees = self.get_sibling_trees(proof);
+
+        count(Counter::Delete, 1);
+        count(Counter::Split, (height > 0).into());
+
+        let leaf_index = proof.path.leaf_index();
+
+        let old_offsets = offsets(&self.trees);
+        self.index
+            .remove(&proof.leaf_hash, old_offsets[height] + leaf_index);
+
+        let mut new_tree = None;
+        let mut merged_height = None;
+
+        for (sibling_height, (tree, sibling_tree)) in self
+            .trees
+            .iter_mut()
+            .take(height)
+            .zip(sibling_trees)
+            .enumerate()
+        {
+            if let Some(ref mut new_tree) = new_tree {
+                *new_tree = merge(&self.hasher, &sibling_tree, new_tree);
+            } else if tree.is_none() {
+                *tree = Some(Arc::new(sibling_tree))
+            } else {
+                // `unwrap()` is safe here because `None` condition was checked earlier
+                new_tree = Some(merge(&self.hasher, &sibling_tree, tree.as_ref().unwrap()));
+                merged_height = Some(sibling_height);
+                *tree = None;
+            }
+        }
+
+        self.trees[height] = new_tree.map(Arc::new);
+
+        // Only sibling subtrees and the tree merged with them are moved (trees taller than proof's
+        // height and trees between merged tree and proof's height keep their positions). Siblings
+        // below merged tree become separate trees, and the new tree contains remaining siblings
+        // (from the tallest one) followed by the merged tree.
+        let new_offsets = offsets(&self.trees);
+        let mut moves = Vec::with_capacity(height + 1);
+
+        for sibling_height in 0..height {
+            let old =
+                old_offsets[height] + (((leaf_index >> sibling_height) ^ 1) << sibling_height);
+            let new = match merged_height {
+                Some(merged_height) if sibling_height >= merged_height => {
+                    new_offsets[height] + (1 << height) - (1 << (sibling_height + 1))
+                }
+                _ => new_offsets[sibling_height],
+            };
+
+            moves.push((old, new, 1 << sibling_height));
+        }
+
+        if let Some(merged_height) = merged_height {
+            let new = new_offsets[height] + (1 << height) - (1 << merged_height);
+            moves.push((old_offsets[merged_height], new, 1 << merged_height));
+        }
+
+        self.relocate_leaves(&moves);
+    }
+
+    /// Returns a list of sibling trees corresponding to sibling hashes in proof. This function
+    /// assumes that the proof is valid and may panic if the proof is not checked before calling
+    /// this function.
+    fn get_sibling_trees(&self, proof: &Proof) -> Vec<Tree> {
+        let height = proof.path.height();
+
+        // This line panics if merkle tree of given height does not exist. Therefore, proof should
+        // be verified before calling this function.
+        let tree = self.trees[height]
+            .as_ref()
+            .expect("Expected merkle tree of given proof height. Proof is not valid.");
+
+        let sibling_trees = tree.sibling_subtrees(&proof.path);
+
+        for (sibling_tree, sibling_hash) in sibling_trees.iter().zip(proof.sibling_hashes.iter()) {
+            assert_eq!(
+                sibling_tree.root_hash(),
+                sibling_hash,
+                "Sibling hash does not match with sub-tree's root hash. Proof is invalid."
+            );
+        }
+
+        sibling_trees
+    }
+}
+
+impl<H: Hasher> Prover for MemoryForest<H> {
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.prove_hash(&leaf_hash)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        prove(&self.trees, &self.index, leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        prove_batch(&self.trees, &self.index, leaf_hashes)
+    }
+}
+
+/// Returns inclusion proof of a leaf hash in given trees using given index of leaves, if present
+fn prove(
+    trees: &[Option<Arc<Tree>>],
+    index: &LeafIndex,
+    leaf_hash: &Hash,
+) -> Result<Option<Proof>, UtreexoError> {
+    let (tree, position) = match find(trees, index, leaf_hash).and_then(|p| locate(trees, p)) {
+        Some(location) => location,
+        None => return Ok(None),
+    };
+
+    count(Counter::Prove, 1);
+    tree.prove_at(leaf_hash, position).map(Some)
+}
+
+/// Returns inclusion proofs of given leaf hashes (in the same order) in given trees using given
+/// index of leaves. All the leaves are looked up first and proofs are generated in the order of
+/// their positions, so, trees are visited one by one instead of being located for every leaf.
+fn prove_batch(
+    trees: &[Option<Arc<Tree>>],
+    index: &LeafIndex,
+    leaf_hashes: &[Hash],
+) -> Result<Vec<Option<Proof>>, UtreexoError> {
+    let mut positions: Vec<(u64, usize)> = leaf_hashes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, leaf_hash)| Some((find(trees, index, leaf_hash)?, i)))
+        .collect();
+    positions.sort_unstable();
+
+    let mut proofs = vec![None; leaf_hashes.len()];
+    let mut positions = positions.into_iter().peekable();
+    let mut offset = 0;
+
+    // Trees are numbered in the order of insertion of leaves (tallest tree first)
+    for tree in trees.iter().rev().flatten() {
+        let num_leaves = tree.num_leaves() as u64;
+
+        while let Some((position, i)) =
+            positions.next_if(|(position, _)| *position < offset + num_leaves)
+        {
+            let proof = tree.prove_at(&leaf_hashes[i], (position - offset) as usize)?;
+            count(Counter::Prove, 1);
+            proofs[i] = Some(proof);
+        }
+
+        offset += num_leaves;
+    }
+
+    Ok(proofs)
+}
+
+/// Position of a leaf in forest: height of merkle tree containing the leaf and index of the leaf in
+/// that tree (leaves of a tree are indexed in order of insertion, see [`MemoryForest::prove_at`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeafPosition {
+    /// Height of merkle tree
+    pub height: usize,
+    /// Index of leaf in merkle tree
+    pub index: usize,
+}
+
+impl LeafPosition {
+    /// Returns position of the last leaf of merkle tree of given height
+    fn last(height: usize) -> Self {
+        Self {
+            height,
+            index: (1 << height) - 1,
+        }
+    }
+}
+
+/// Immutable read-only view of a forest at a point in time (see [`MemoryForest::snapshot`])
+///
+/// Snapshot shares trees with the forest, so, creating it is cheap and it is `Send + Sync` (if
+/// hasher is), i.e., it can be used by proof serving threads while the forest is being updated.
+///
+/// Snapshot also shares index of leaves with the forest. Index is stored in chunks of 4096 slots,
+/// and while the snapshot is alive, an update of forest copies only the chunks it modifies.
+#[derive(Debug, Clone)]
+pub struct ForestSnapshot<H = Blake3> {
+    /// Merkle trees in forest
+    trees: Vec<Option<Arc<Tree>>>,
+    /// Hasher used for calculating leaf and intermediate hashes
+    hasher: H,
+    /// Index of leaves in forest
+    index: LeafIndex,
+}
+
+impl<H: Hasher> ForestSnapshot<H> {
+    /// Returns root hashes of all the trees in merkle forest at the time of snapshot (indexed by
+    /// height)
+    pub fn roots(&self) -> Vec<Option<Hash>> {
+        self.trees
+            .iter()
+            .map(|tree| tree.as_ref().map(|tree| *tree.root_hash()))
+            .collect()
+    }
+
+    /// Returns read-only views of all the trees in merkle forest at the time of snapshot (indexed
+    /// by height)
+    pub fn trees(
+        &self,
+    ) -> impl ExactSizeIterator<Item = Option<TreeRef<'_>>> + DoubleEndedIterator {
+        tree_refs(&self.trees)
+    }
+}
+
+impl<H: Hasher> Prover for ForestSnapshot<H> {
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.prove_hash(&leaf_hash)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        prove(&self.trees, &self.index, leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        prove_batch(&self.trees, &self.index, leaf_hashes)
+    }
+}
+
+impl<H: Hasher> Utreexo for MemoryForest<H> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_leaf(leaf_hash);
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.insert_leaf(leaf_hash);
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        // Proof should be valid to delete a value from accumulator
+        if !self.verify(proof) {
+            return false;
+        }
+
+        self.delete_verified(proof);
+        true
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.trees
+            .iter()
+            .flatten()
+            .map(|tree| tree.num_leaves() as u64)
+            .sum()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H> Zeroize for MemoryForest<H> {
+    fn zeroize(&mut self) {
+        // Trees shared with clones are only dropped (they are wiped when their last owner drops
+        // them)
+        for tree in self.trees.iter_mut().flatten() {
+            if let Some(tree) = Arc::get_mut(tree) {
+                tree.zeroize();
+            }
+        }
+
+        self.trees = Vec::new();
+        self.index.zeroize();
+    }
+}
+
+// Trees and index of leaves wipe themselves when they are dropped
+#[cfg(feature = "zeroize")]
+impl<H> ZeroizeOnDrop for MemoryForest<H> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_memory_forest_ops() {
+        let mut forest = MemoryForest::new();
+        assert!(forest.is_empty());
+
+        forest.insert([0; 32]);
+        forest.insert([1; 32]);
+        forest.insert([2; 32]);
+        forest.insert([3; 32]);
+        forest.insert([4; 32]);
+        forest.insert([5; 32]);
+        forest.insert([6; 32]);
+        forest.insert([7; 32]);
+        forest.insert([8; 32]);
+        forest.insert([9; 32]);
+
+        // Checking distribution of trees in merkle forest
+        assert_eq!(4, forest.trees.len());
+        assert!(forest.trees[0].is_none());
+        assert!(forest.trees[1].is_some());
+        assert!(forest.trees[2].is_none());
+        assert!(forest.trees[3].is_some());
+        assert_eq!(10, forest.len());
+
+        // Delete a leaf
+        let proof = forest.prove([0; 32]).unwrap();
+        assert!(proof.is_some());
+        assert!(forest.delete(&proof.unwrap()));
+        assert_eq!(9, forest.num_leaves());
+
+        // Checking distribution of trees in merkle forest
+        assert_eq!(4, forest.trees.len());
+        assert!(forest.trees[0].is_some());
+        assert!(forest.trees[1].is_none());
+        assert!(forest.trees[2].is_none());
+        assert!(forest.trees[3].is_some());
+
+        // Delete a leaf
+        let proof = forest.prove([1; 32]).unwrap();
+        assert!(proof.is_some());
+        assert!(forest.delete(&proof.unwrap()));
+
+        // Checking distribution of trees in merkle forest
+        assert_eq!(4, forest.trees.len());
+        assert!(forest.trees[0].is_none());
+        assert!(forest.trees[1].is_none());
+        assert!(forest.trees[2].is_none());
+        assert!(forest.trees[3].is_some());
+
+        // Delete a leaf
+        let proof = forest.prove([2; 32]).unwrap();
+        assert!(proof.is_some());
+        assert!(forest.delete(&proof.unwrap()));
+
+        // Checking distribution of trees in merkle forest
+        assert_eq!(4, forest.trees.len());
+        assert!(forest.trees[0].is_some());
+        assert!(forest.trees[1].is_some());
+        assert!(forest.trees[2].is_some());
+        assert!(forest.trees[3].is_none());
+
+        // Delete a leaf
+        let proof = forest.prove([3; 32]).unwrap();
+        assert!(proof.is_some());
+        assert!(forest.delete(&proof.unwrap()));
+
+        // Checking distribution of trees in merkle forest
+        assert_eq!(4, forest.trees.len());
+        assert!(forest.trees[0].is_none());
+        assert!(forest.trees[1].is_some());
+        assert!(forest.trees[2].is_some());
+        assert!(forest.trees[3].is_none());
+
+        // Add a leaf
+        forest.insert([0; 32]);
+
+        // Checking distribution of trees in merkle forest
+        assert_eq!(4, forest.trees.len());
+        assert!(forest.trees[0].is_some());
+        assert!(forest.trees[1].is_some());
+        assert!(forest.trees[2].is_some());
+        assert!(forest.trees[3].is_none());
+
+        // Check proof of a value not present in the set
+        assert!(forest.prove([1; 32]).unwrap().is_none());
+    }
+
+    #[test]
+    fn check_memory_forest_structural_sharing() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut cloned_forest = forest.clone();
+        assert!(Arc::ptr_eq(
+            forest.trees[2].as_ref().unwrap(),
+            cloned_forest.trees[2].as_ref().unwrap()
+        ));
+
+        // Only the touched trees are replaced in the clone
+        cloned_forest.insert([6; 32]);
+        assert!(Arc::ptr_eq(
+            forest.trees[2].as_ref().unwrap(),
+            cloned_forest.trees[2].as_ref().unwrap()
+        ));
+        assert!(forest.trees[0].is_none());
+        assert!(cloned_forest.trees[0].is_some());
+    }
+
+    #[test]
+    fn check_memory_forest_from_leaves() {
+        // Large enough for subtrees to be built in parallel with `rayon` feature
+        let values: Vec<[u8; 2]> = (0..3000u16).map(u16::to_be_bytes).collect();
+
+        let mut expected = MemoryForest::new();
+
+        for value in values.iter() {
+            expected.insert(value);
+        }
+
+        let forest = MemoryForest::from_leaves(&values);
+        assert_eq!(expected, forest);
+        assert_eq!(Ok(()), forest.verify_consistency());
+
+        assert_eq!(
+            MemoryForest::new(),
+            MemoryForest::from_leaves::<[u8; 2]>(&[])
+        );
+    }
+
+    #[test]
+    fn check_memory_forest_index() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..23u8 {
+            forest.insert([value; 32]);
+        }
+
+        for value in [3u8, 22, 0, 9, 16].iter() {
+            let proof = forest.prove([*value; 32]).unwrap().unwrap();
+            assert!(forest.delete(&proof));
+            assert!(!forest.contains([*value; 32]));
+        }
+
+        // Proofs generated using index should match the ones found by searching every tree
+        for value in 0..23u8 {
+            let leaf_hash = Blake3.hash_leaf(&[value; 32]);
+            let proof = forest.prove([value; 32]).unwrap();
+
+            let expected = forest
+                .trees
+                .iter()
+                .flatten()
+                .find_map(|tree| tree.prove(&leaf_hash).unwrap());
+
+            assert_eq!(expected, proof);
+            assert_eq!(proof.is_some(), forest.contains([value; 32]));
+            assert_eq!(proof.is_some(), forest.contains_hash(&leaf_hash));
+            assert_eq!(proof, forest.prove_hash(&leaf_hash).unwrap());
+            assert_eq!(proof, forest.prove_value([value; 32]).unwrap());
+        }
+
+        let restored = MemoryForest::from_bytes(&forest.to_bytes()).unwrap();
+        assert_eq!(forest, restored);
+    }
+
+    #[test]
+    fn check_memory_forest_index_positions() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..37u8 {
+            forest.insert([value; 32]);
+        }
+
+        // Duplicate leaf
+        forest.insert([5; 32]);
+
+        // Deletions from trees of all the heights, where moved leaves are relocated in index
+        for value in (0..37u16).map(|value| ((value * 17) % 37) as u8) {
+            let proof = forest.prove([value; 32]).unwrap().unwrap();
+            assert!(forest.delete(&proof));
+            assert_eq!(forest.num_leaves(), forest.index.len() as u64);
+
+            for_each_leaf(
+                &forest.trees,
+                0..forest.trees.len(),
+                |leaf_hash, position| {
+                    let found = forest
+                        .index
+                        .find(leaf_hash, |candidate| candidate == position);
+                    assert_eq!(Some(position), found);
+                },
+            );
+        }
+
+        assert_eq!(1, forest.num_leaves());
+        assert!(forest.contains([5; 32]));
+    }
+
+    #[cfg(all(feature = "serde-1", feature = "json"))]
+    #[test]
+    fn check_memory_forest_serde_index() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let json = serde_json::to_string(&forest).unwrap();
+        let deserialized: MemoryForest = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(forest, deserialized);
+        assert!(deserialized.contains([4; 32]));
+    }
+
+    #[cfg(all(feature = "serde-1", feature = "json"))]
+    #[test]
+    fn check_memory_forest_serde_tampered() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        let json = serde_json::to_value(&forest).unwrap();
+        let other_hash = serde_json::to_value(Hash::from([9; 32])).unwrap();
+
+        // Leaf of tree of height 2 does not match its intermediate nodes
+        let mut tampered = json.clone();
+        tampered["trees"][2]["nodes"][0] = other_hash.clone();
+        assert!(serde_json::from_value::<MemoryForest>(tampered).is_err());
+
+        // Root of tree of height 1 does not match its leaves
+        let mut tampered = json.clone();
+        tampered["trees"][1]["nodes"][2] = other_hash;
+        assert!(serde_json::from_value::<MemoryForest>(tampered).is_err());
+
+        // Tree of height 1 is moved to height 0 (i.e., it has too many nodes for its height)
+        let mut tampered = json.clone();
+        tampered["trees"][0] = tampered["trees"][1].clone();
+        tampered["trees"][1] = serde_json::Value::Null;
+        assert!(serde_json::from_value::<MemoryForest>(tampered).is_err());
+
+        assert_eq!(forest, serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn check_memory_forest_compact() {
+        let mut forest =
+            MemoryForest::from_leaves(&(0..100u8).map(|v| [v; 32]).collect::<Vec<_>>());
+
+        for value in 0..90u8 {
+            let proof = forest.prove([value; 32]).unwrap().unwrap();
+            assert!(forest.delete(&proof));
+        }
+
+        let expected = forest.clone();
+        let leaf_index = forest.memory_usage().leaf_index;
+
+        forest.compact();
+
+        assert!(forest.memory_usage().leaf_index < leaf_index);
+        assert_eq!(expected, forest);
+        assert!(forest.contains([95; 32]));
+        assert!(forest.prove([99; 32]).unwrap().is_some());
+    }
+
+    #[test]
+    fn check_memory_forest_prove_batch() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..45u8 {
+            forest.insert([value; 32]);
+        }
+
+        let proof = forest.prove([6; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+
+        // Duplicate, missing and unordered leaves across all the trees
+        let values = [44u8, 3, 6, 40, 3, 100, 0, 32, 43];
+        let leaf_hashes: Vec<Hash> = values
+            .iter()
+            .map(|value| Blake3.hash_leaf(&[*value; 32]))
+            .collect();
+
+        let expected: Vec<Option<Proof>> = values
+            .iter()
+            .map(|value| forest.prove([*value; 32]).unwrap())
+            .collect();
+
+        assert_eq!(expected, forest.prove_batch(&leaf_hashes).unwrap());
+        assert_eq!(
+            expected,
+            forest.snapshot().prove_batch(&leaf_hashes).unwrap()
+        );
+        assert_eq!(7, expected.iter().flatten().count());
+        assert!(forest.prove_batch(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_memory_forest_prove_at() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..7u8 {
+            forest.insert([value; 32]);
+        }
+
+        // Trees of heights 2, 1 and 0 contain leaves 0-3, 4-5 and 6 respectively
+        assert_eq!(
+            forest.prove([5; 32]).unwrap(),
+            forest.prove_at(1, 1).unwrap()
+        );
+        assert_eq!(
+            forest.prove([6; 32]).unwrap(),
+            forest.prove_at(0, 0).unwrap()
+        );
+        assert_eq!(
+            forest.prove([2; 32]).unwrap(),
+            forest.prove_at(2, 2).unwrap()
+        );
+
+        assert_eq!(None, forest.prove_at(1, 2).unwrap());
+        assert_eq!(None, forest.prove_at(3, 0).unwrap());
+        assert_eq!(None, forest.prove_at(100, 0).unwrap());
+
+        let proof = forest.prove_at(2, 3).unwrap().unwrap();
+        assert_eq!(&Blake3.hash_leaf(&[3; 32]), proof.leaf_hash());
+        assert!(forest.delete(&proof));
+    }
+
+    #[test]
+    fn check_memory_forest_insert_with_proof() {
+        let mut forest = MemoryForest::new();
+
+        assert_eq!(
+            LeafPosition {
+                height: 0,
+                index: 0
+            },
+            forest.insert_with_position([0; 32])
+        );
+
+        for value in 1..5u8 {
+            let (position, proof) = forest.insert_with_proof([value; 32]).unwrap();
+
+            assert_eq!(Some(proof.clone()), forest.prove([value; 32]).unwrap());
+            assert_eq!(position.height, proof.path.height());
+            assert_eq!(
+                Some(proof),
+                forest.prove_at(position.height, position.index).unwrap()
+            );
+        }
+
+        // Sixth leaf is merged with fifth leaf into a tree of height 1
+        assert_eq!(
+            LeafPosition {
+                height: 1,
+                index: 1
+            },
+            forest.insert_with_position([5; 32])
+        );
+    }
+
+    #[test]
+    fn check_memory_forest_retain() {
+        let values: Vec<[u8; 1]> = (0..30u8).map(|value| [value]).collect();
+
+        let mut forest = MemoryForest::from_leaves(&values);
+        let mut accumulator = crate::MemoryAccumulator::from_leaves(&values);
+        let mut expected = forest.clone();
+
+        let odd: Vec<Hash> = values
+            .iter()
+            .filter(|value| value[0] % 2 == 1)
+            .map(|value| Blake3.hash_leaf(value))
+            .collect();
+
+        let proofs = forest.retain(|leaf_hash| !odd.contains(leaf_hash)).unwrap();
+        assert_eq!(15, proofs.len());
+
+        for (proof, leaf_hash) in proofs.iter().zip(odd.iter()) {
+            assert_eq!(leaf_hash, proof.leaf_hash());
+            assert!(accumulator.delete(proof));
+
+            let proof = expected.prove_hash(leaf_hash).unwrap().unwrap();
+            assert!(expected.delete(&proof));
+        }
+
+        assert_eq!(expected, forest);
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+        assert_eq!(15, forest.num_leaves());
+        assert!(forest.contains([4]) && !forest.contains([5]));
+        assert!(forest.verify_consistency().is_ok());
+
+        assert!(forest.retain(|_| true).unwrap().is_empty());
+        assert_eq!(15, forest.retain(|_| false).unwrap().len());
+        assert!(forest.is_empty());
+    }
+
+    #[test]
+    fn check_memory_forest_drain() {
+        let values: Vec<[u8; 1]> = (0..13u8).map(|value| [value]).collect();
+
+        let mut forest = MemoryForest::from_leaves(&values);
+        let snapshot = forest.snapshot();
+
+        let leaf_hashes: Vec<Hash> = forest.drain().collect();
+        let expected: Vec<Hash> = values.iter().map(|value| Blake3.hash_leaf(value)).collect();
+
+        assert_eq!(expected, leaf_hashes);
+        assert_eq!(MemoryForest::new(), forest);
+        assert!(!forest.contains([3]));
+
+        // Snapshot is not affected
+        assert!(snapshot.prove([3]).unwrap().is_some());
+
+        let mut forest = MemoryForest::from_leaves(&values);
+        forest.clear();
+
+        assert!(forest.is_empty());
+        assert!(forest.prove([3]).unwrap().is_none());
+
+        forest.insert([3]);
+        assert!(forest.contains([3]));
+    }
+
+    #[test]
+    fn check_memory_forest_bootstrap() {
+        let values: Vec<[u8; 1]> = (0..21u8).map(|value| [value]).collect();
+
+        let mut expected = MemoryForest::from_leaves(&values);
+        let proof = expected.prove([4]).unwrap().unwrap();
+        assert!(expected.delete(&proof));
+
+        let roots = expected.roots();
+        let leaf_hashes: Vec<Hash> = expected.clone().drain().collect();
+
+        assert_eq!(
+            Ok(expected.clone()),
+            MemoryForest::bootstrap(&roots, leaf_hashes.iter().copied())
+        );
+
+        // Leaves in a different order
+        let mut swapped = leaf_hashes.clone();
+        swapped.swap(17, 18);
+
+        assert_eq!(
+            Err(UtreexoError::RootMismatch { height: 2 }),
+            MemoryForest::bootstrap(&roots, swapped)
+        );
+
+        // Missing leaf
+        assert_eq!(
+            Err(UtreexoError::RootMismatch { height: 0 }),
+            MemoryForest::bootstrap(&roots, leaf_hashes[1..].iter().copied())
+        );
+
+        // Extra (empty) heights in trusted roots are allowed
+        let mut padded = roots;
+        padded.push(None);
+        assert!(MemoryForest::bootstrap(&padded, leaf_hashes).is_ok());
+    }
+
+    #[test]
+    fn check_memory_forest_apply_block() {
+        let values: Vec<[u8; 1]> = (0..11u8).map(|value| [value]).collect();
+
+        let mut forest = MemoryForest::from_leaves(&values);
+        let mut accumulator = crate::MemoryAccumulator::from_leaves(&values);
+        let before = forest.clone();
+
+        let mut held = forest.prove([9]).unwrap().unwrap();
+        let mut dels = vec![forest.prove([2]).unwrap().unwrap()];
+
+        // Every proof is generated after the previous deletions
+        let mut scratch = forest.clone();
+        assert!(scratch.delete(&dels[0]));
+        dels.push(scratch.prove([10]).unwrap().unwrap());
+
+        let adds = [[11], [12], [13]];
+
+        let changes = forest.apply_block(&adds, &dels).unwrap();
+
+        assert_eq!(forest.roots(), changes.roots);
+        assert_eq!(before.roots(), changes.update.prev_roots());
+
+        // Accumulator produces the same changes (but its undo data does not contain trees)
+        let accumulator_changes = accumulator.apply_block(&adds, &dels).unwrap();
+        assert_eq!(changes.roots, accumulator_changes.roots);
+        assert_eq!(changes.update, accumulator_changes.update);
+        assert!(!accumulator_changes.undo.has_trees());
+
+        // Remote clients can update their proofs with update data
+        assert!(held.extend(&Blake3, &changes.update));
+        assert_eq!(forest.prove([9]).unwrap().unwrap(), held);
+
+        // Block can be undone with undo data
+        forest.restore(changes.undo).unwrap();
+        assert_eq!(before, forest);
+
+        // Second proof is not valid after the first deletion
+        let dels = vec![dels[0].clone(), before.prove([4]).unwrap().unwrap()];
+
+        assert_eq!(
+            Err(UtreexoError::InvalidDeletion { index: 1 }),
+            forest.apply_block(&adds, &dels)
+        );
+        assert_eq!(before, forest);
+
+        // Restored index of leaves still points to positions of leaves before the block
+        for value in values.iter() {
+            assert_eq!(before.prove(value).unwrap(), forest.prove(value).unwrap());
+        }
+        assert_eq!(None, forest.prove([11]).unwrap());
+    }
+
+    #[test]
+    fn check_memory_forest_render() {
+        let mut forest = MemoryForest::new();
+        assert_eq!("", forest.render(2));
+
+        for value in 0..7u8 {
+            forest.insert([value; 32]);
+        }
+
+        let proof = forest.prove([1; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+
+        let expected = "\
+height 2 (4 leaves)
+*
++-- *
+|   +-- * (leaf 0)
+|   `-- * (leaf 1)
+`-- *
+    +-- * (leaf 2)
+    `-- * (leaf 3)
+height 1 (2 leaves)
+*
++-- * (leaf 4)
+`-- * (leaf 5)
+";
+        assert_eq!(expected, forest.render(0));
+
+        let root_hash = forest.roots()[2].unwrap();
+        let rendered = forest.render(2);
+        assert!(rendered.starts_with(&format!(
+            "height 2 (4 leaves)\n{}\n",
+            &root_hash.to_string()[..4]
+        )));
+    }
+
+    #[test]
+    fn check_memory_forest_to_dot() {
+        let forest = MemoryForest::from_leaves(&[[0; 32], [1; 32], [2; 32], [3; 32], [4; 32]]);
+        let label = |hash: &Hash| String::from(&hash.to_string()[..8]);
+        let leaf = |value: u8| label(&Blake3.hash_leaf(&[value; 32]));
+
+        let dot = forest.to_dot(None);
+        assert!(dot.starts_with("digraph forest {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("subgraph cluster_2 {"));
+        assert!(dot.contains("subgraph cluster_0 {"));
+        assert!(dot.contains(&format!(
+            "t2_6 [label=\"{}\"];",
+            label(&forest.roots()[2].unwrap())
+        )));
+        assert!(dot.contains(&format!("t2_0 [label=\"{}\", shape=box];", leaf(0))));
+        assert!(dot.contains(&format!("t0_0 [label=\"{}\", shape=box];", leaf(4))));
+        assert_eq!(6, dot.matches(" -> ").count());
+
+        let tree_dot = forest.trees().nth(2).flatten().unwrap().to_dot(Some(1));
+        assert!(tree_dot.starts_with("digraph tree {\n"));
+        assert!(tree_dot.contains("n6 -> n2;"));
+        assert!(tree_dot.contains(&format!(
+            "n1 [label=\"{}\", shape=box, style=filled];",
+            leaf(1)
+        )));
+        assert_eq!(4, tree_dot.matches(" -> ").count());
+
+        // Only nodes of proof of leaf 2 are present
+        let dot = forest.to_dot(Some(&Blake3.hash_leaf(&[2; 32])));
+        assert!(!dot.contains("cluster_0"));
+        assert_eq!(4, dot.matches(" -> ").count());
+        assert!(dot.contains("t2_6 -> t2_2;"));
+        assert!(dot.contains(&format!(
+            "t2_3 [label=\"{}\", shape=box, style=filled];",
+            leaf(2)
+        )));
+        assert!(dot.contains(&format!("t2_4 [label=\"{}\", shape=box];", leaf(3))));
+        assert!(!dot.contains(&leaf(0)));
+
+        assert_eq!(
+            "digraph forest {\n}\n",
+            forest.to_dot(Some(&Blake3.hash_leaf(&[9; 32])))
+        );
+    }
+
+    #[test]
+    fn check_memory_forest_tree_refs() {
+        let forest = MemoryForest::from_leaves(&[[0; 32], [1; 32], [2; 32]]);
+        let trees: Vec<_> = forest.trees().collect();
+
+        assert_eq!(2, trees.len());
+        assert_eq!(trees[1], forest.tree(1));
+        assert_eq!(None, forest.tree(2));
+
+        let tree = trees[1].unwrap();
+
+        assert_eq!(1, tree.height());
+        assert_eq!(2, tree.num_leaves());
+        assert_eq!(3, tree.num_nodes());
+        assert_eq!(
+            vec![&Blake3.hash_leaf(&[0; 32]), &Blake3.hash_leaf(&[1; 32])],
+            tree.leaves().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&Blake3.hash_leaf(&[1; 32])), tree.leaf(1));
+        assert_eq!(None, tree.leaf(2));
+        assert_eq!(forest.roots()[1].as_ref(), Some(tree.root_hash()));
+        assert!(forest.trees().eq(forest.snapshot().trees()));
+    }
+
+    #[test]
+    fn check_memory_forest_with_capacity() {
+        let mut forest = MemoryForest::with_capacity(100);
+        let usage = forest.memory_usage();
+
+        for value in 0..100u8 {
+            forest.insert([value; 32]);
+        }
+
+        assert_eq!(usage.leaf_index, forest.memory_usage().leaf_index);
+        assert_eq!(7, forest.trees().len());
+        assert_eq!(7, forest.trees.capacity());
+        assert!(forest.contains([99; 32]));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn check_memory_forest_zeroize() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut tree = forest.trees[0].as_deref().unwrap().clone();
+        tree.zeroize();
+        assert!(tree.nodes().is_empty());
+
+        // Trees and index shared with a clone are left untouched
+        let clone = forest.clone();
+        forest.zeroize();
+
+        assert_eq!(MemoryForest::new(), forest);
+        assert_eq!(0, forest.memory_usage().leaf_index);
+        assert!(!forest.contains([4; 32]));
+        assert!(clone.contains([4; 32]));
+        assert!(clone.prove([2; 32]).unwrap().is_some());
+    }
+
+    #[test]
+    fn check_memory_forest_reserve() {
+        let leaves: Vec<[u8; 32]> = (0..100u8).map(|value| [value; 32]).collect();
+
+        // Index of a bulk built forest is sized for its leaves
+        let mut forest = MemoryForest::from_leaves(&leaves[..60]);
+        let usage = forest.memory_usage();
+        assert_eq!(LeafIndex::capacity_for(60) * 16, usage.leaf_index);
+
+        forest.reserve(40);
+        let usage = forest.memory_usage();
+
+        for value in leaves[60..].iter() {
+            forest.insert(value);
+        }
+
+        assert_eq!(usage.leaf_index, forest.memory_usage().leaf_index);
+        assert_eq!(7, forest.trees.capacity());
+        assert_eq!(forest, MemoryForest::from_leaves(&leaves));
+        assert!(forest.contains([42; 32]));
+    }
+
+    #[test]
+    fn check_forest_snapshot() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        let snapshot = forest.snapshot();
+
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+
+        let handle = std::thread::spawn(move || snapshot.prove([0; 32]).unwrap());
+        assert_eq!(Some(proof), handle.join().unwrap());
+        assert!(forest.prove([0; 32]).unwrap().is_none());
+    }
+
+    #[test]
+    fn check_memory_forest_checkpoint() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let checkpoint = forest.checkpoint();
+        assert_eq!(5, checkpoint.num_leaves());
+        assert!(checkpoint.has_trees());
+
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(forest.prove([0; 32]).unwrap().is_none());
+
+        assert_eq!(Ok(()), forest.restore(checkpoint));
+        assert_eq!(Some(proof), forest.prove([0; 32]).unwrap());
+
+        let trees = forest.trees.clone();
+        assert_eq!(
+            Err(UtreexoError::CheckpointWithoutTrees),
+            forest.restore(crate::MemoryAccumulator::new().checkpoint())
+        );
+        assert_eq!(trees, forest.trees);
+    }
+
+    #[test]
+    fn check_memory_forest_bytes() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let bytes = forest.to_bytes();
+
+        assert_eq!(5 + 1 + 3 + (1 + 7) * 32, bytes.len());
+        assert_eq!(Ok(forest.clone()), MemoryForest::from_bytes(&bytes));
+        assert!(MemoryForest::from_bytes(&bytes)
+            .unwrap()
+            .prove([3; 32])
+            .unwrap()
+            .is_some());
+
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unexpected end of input")),
+            MemoryForest::from_bytes(&bytes[..bytes.len() - 1])
+        );
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Too many trees")),
+            MemoryForest::from_bytes(b"UTRF\x01\x41")
+        );
+        assert_eq!(
+            Err(UtreexoError::UnsupportedVersion {
+                version: 2,
+                supported: 1
+            }),
+            MemoryForest::from_bytes(b"UTRF\x02\x00")
+        );
+
+        // State reached through deletions is encoded in the same way as the state built directly
+        let mut deleted_forest = MemoryForest::new();
+        deleted_forest.insert([1; 32]);
+        deleted_forest.insert([2; 32]);
+        assert!(deleted_forest.delete(&deleted_forest.prove([2; 32]).unwrap().unwrap()));
+        assert_eq!(2, deleted_forest.trees.len());
+
+        let mut expected = MemoryForest::new();
+        expected.insert([1; 32]);
+
+        assert_eq!(expected.to_bytes(), deleted_forest.to_bytes());
+        assert_eq!(expected, deleted_forest);
+
+        let mut invalid = expected.to_bytes();
+        invalid[5] = 2;
+        invalid.push(0);
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Last root should be present")),
+            MemoryForest::from_bytes(&invalid)
+        );
+
+        let keyed_forest = MemoryForest::new_keyed([7; 32]);
+        assert_eq!(b"UTRF\x01\x00", &keyed_forest.to_bytes()[..]);
+        assert_eq!(
+            Ok(keyed_forest),
+            MemoryForest::from_bytes_with_hasher(b"UTRF\x01\x00", KeyedBlake3::new([7; 32]))
+        );
+    }
+
+    #[test]
+    fn check_memory_forest_duplicate_leaves() {
+        let mut forest = MemoryForest::new();
+
+        for value in [1u8, 2, 1].iter() {
+            forest.insert([*value; 32]);
+        }
+
+        // Forest with duplicate leaves survives its own snapshot and chunk sync
+        let restored = MemoryForest::from_bytes(&forest.to_bytes()).unwrap();
+        assert_eq!(forest, restored);
+
+        let mut assembler = crate::SnapshotAssembler::new(forest.roots(), 0).unwrap();
+
+        for chunk in forest.chunks(0) {
+            assert_eq!(Ok(()), assembler.add_chunk(chunk));
+        }
+
+        assert_eq!(Ok(forest.clone()), assembler.finish());
+
+        // Every copy of duplicate leaf is deleted separately
+        let mut forest = restored;
+
+        for _ in 0..2 {
+            let proof = forest.prove([1; 32]).unwrap().unwrap();
+            assert!(forest.delete(&proof));
+            assert_eq!(Ok(()), forest.verify_consistency());
+        }
+
+        assert!(!forest.contains([1; 32]));
+        assert!(forest.contains([2; 32]));
+        assert_eq!(1, forest.num_leaves());
+    }
+
+    #[test]
+    fn check_memory_forest_verify_consistency() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        assert_eq!(Ok(()), forest.verify_consistency());
+
+        // Tree is placed at wrong height
+        forest.trees.swap(1, 2);
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 3,
+                height: 1
+            }),
+            forest.verify_consistency()
+        );
+
+        // Tree is hashed with a different hasher
+        let mut keyed_forest = MemoryForest::new_keyed([7; 32]);
+        keyed_forest.trees = forest.trees;
+        keyed_forest.trees.swap(1, 2);
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 2,
+                height: 1
+            }),
+            keyed_forest.verify_consistency()
+        );
+    }
+
+    #[test]
+    fn check_memory_forest_rebuild() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut corrupt_forest = forest.clone();
+        Arc::make_mut(corrupt_forest.trees[2].as_mut().unwrap()).nodes_mut()[2] = [9; 32].into();
+        assert!(corrupt_forest.verify_consistency().is_err());
+
+        assert_eq!(Ok(()), corrupt_forest.rebuild());
+        assert_eq!(Ok(()), corrupt_forest.verify_consistency());
+        assert_eq!(forest, corrupt_forest);
+
+        // Trees can not be rebuilt if they are placed at wrong height
+        corrupt_forest.trees.swap(1, 2);
+        assert!(corrupt_forest.rebuild().is_err());
+    }
+
+    #[test]
+    fn check_memory_forest_untrusted_bytes() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut bytes = forest.to_bytes();
+        assert_eq!(Ok(forest), MemoryForest::from_untrusted_bytes(&bytes));
+
+        // Corrupt the root hash of tree of height 2
+        let len = bytes.len();
+        bytes[len - 1] ^= 1;
+
+        assert!(MemoryForest::from_bytes(&bytes).is_ok());
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 6,
+                height: 2
+            }),
+            MemoryForest::from_untrusted_bytes(&bytes)
+        );
+        assert!(MemoryForest::from_untrusted_bytes_with_hasher(
+            &MemoryForest::new_keyed([7; 32]).to_bytes(),
+            KeyedBlake3::new([7; 32])
+        )
+        .is_ok());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn check_memory_forest_stream() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut bytes = Vec::new();
+        forest.write_to(&mut bytes).unwrap();
+
+        assert_eq!(forest.to_bytes(), bytes);
+        assert_eq!(forest, MemoryForest::read_from(&bytes[..]).unwrap());
+
+        let error = MemoryForest::read_from(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+
+        bytes[4] = 2;
+        let error = MemoryForest::read_from(&bytes[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(
+            Some(&UtreexoError::UnsupportedVersion {
+                version: 2,
+                supported: 1
+            }),
+            error
+                .get_ref()
+                .and_then(|error| error.downcast_ref::<UtreexoError>())
+        );
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn check_memory_forest_compressed() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut bytes = Vec::new();
+        forest.write_compressed_to(&mut bytes, 0).unwrap();
+
+        assert_eq!(
+            forest,
+            MemoryForest::read_compressed_from(&bytes[..]).unwrap()
+        );
     }
 }
@@ -1,75 +1,349 @@
+#[cfg(feature = "schema")]
+use alloc::{borrow::ToOwned, boxed::Box, string::String};
+use core::{cmp::Ordering, convert::TryFrom, fmt, hash, str::FromStr};
+
+#[cfg(feature = "schema")]
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation},
+    JsonSchema,
+};
 #[cfg(feature = "serde-1")]
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+use crate::UtreexoError;
 
-/// Blake3 hash
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+/// Hash of a leaf or an intermediate node in merkle forest
+///
+/// With `serde-1` feature, hashes are serialized as hex strings for human readable formats (e.g.
+/// JSON) and as raw bytes otherwise. JSON schema of a hash (with `schema` feature) describes the
+/// hex string.
+///
+/// With `zeroize` feature, hashes can be wiped using `Zeroize`. Hashes are `Copy`, so, they are not
+/// wiped on drop and every copy has to be wiped separately.
+#[derive(Copy, Clone, Eq)]
 pub struct Hash {
-    #[cfg_attr(feature = "serde-1", serde(serialize_with = "serialize_hash"))]
-    #[cfg_attr(feature = "serde-1", serde(deserialize_with = "deserialize_hash"))]
-    inner: blake3::Hash,
+    bytes: [u8; blake3::OUT_LEN],
 }
 
 impl Hash {
+    /// Length of hash in bytes
+    pub const LEN: usize = blake3::OUT_LEN;
+
     /// Returns bytes of the hash
     pub fn as_bytes(&self) -> &[u8; blake3::OUT_LEN] {
-        self.inner.as_bytes()
+        &self.bytes
+    }
+
+    /// Returns bytes of the hash by value
+    pub fn to_bytes(self) -> [u8; blake3::OUT_LEN] {
+        self.into()
+    }
+}
+
+/// Returns value of a hex digit
+fn hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
     }
 }
 
 impl From<[u8; blake3::OUT_LEN]> for Hash {
     fn from(bytes: [u8; blake3::OUT_LEN]) -> Self {
-        Self {
-            inner: bytes.into(),
-        }
+        Self { bytes }
     }
 }
 
 impl From<Hash> for [u8; blake3::OUT_LEN] {
     fn from(hash: Hash) -> Self {
-        hash.inner.into()
+        hash.bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Hash {
+    type Error = UtreexoError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() != blake3::OUT_LEN {
+            return Err(UtreexoError::InvalidHash);
+        }
+
+        let mut hash_bytes = [0; blake3::OUT_LEN];
+        hash_bytes.copy_from_slice(bytes);
+
+        Ok(hash_bytes.into())
+    }
+}
+
+impl fmt::LowerHex for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("0x")?;
+        }
+
+        for byte in self.as_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Hashes are displayed as lowercase hex
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+// Parses hash from its hex encoding (both lowercase and uppercase hex digits are accepted)
+impl FromStr for Hash {
+    type Err = UtreexoError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        let hex = hex.as_bytes();
+
+        if hex.len() != 2 * blake3::OUT_LEN {
+            return Err(UtreexoError::InvalidHash);
+        }
+
+        let mut bytes = [0; blake3::OUT_LEN];
+
+        for (byte, digits) in bytes.iter_mut().zip(hex.chunks(2)) {
+            match (hex_digit(digits[0]), hex_digit(digits[1])) {
+                (Some(high), Some(low)) => *byte = (high << 4) | low,
+                _ => return Err(UtreexoError::InvalidHash),
+            }
+        }
+
+        Ok(bytes.into())
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+// Hashes are ordered lexicographically by their bytes
+impl Ord for Hash {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl PartialOrd for Hash {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Hashes are compared in constant time (using `blake3::Hash`)
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        blake3::Hash::from(self.bytes) == other.bytes
     }
 }
 
 impl PartialEq<[u8; blake3::OUT_LEN]> for Hash {
     fn eq(&self, other: &[u8; blake3::OUT_LEN]) -> bool {
-        self.inner.eq(other)
+        blake3::Hash::from(self.bytes) == *other
     }
 }
 
-impl From<blake3::Hash> for Hash {
-    fn from(hash: blake3::Hash) -> Self {
-        Self { inner: hash }
+impl hash::Hash for Hash {
+    fn hash<S: hash::Hasher>(&self, state: &mut S) {
+        self.bytes.hash(state)
     }
 }
 
-impl From<Hash> for blake3::Hash {
-    fn from(hash: Hash) -> Self {
-        hash.inner
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Hash")
+            .field(&format_args!("{}", self))
+            .finish()
+    }
+}
+
+/// Converts a blake3 hash (blake3 types are not part of public API, so, hashes are only converted
+/// from and to byte arrays outside the crate)
+pub(crate) fn from_blake3(hash: blake3::Hash) -> Hash {
+    Hash { bytes: hash.into() }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for Hash {
+    fn zeroize(&mut self) {
+        self.bytes.zeroize();
     }
 }
 
 #[cfg(feature = "serde-1")]
-fn serialize_hash<S>(hash: &blake3::Hash, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    serializer.serialize_bytes(hash.as_bytes())
+impl Serialize for Hash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl JsonSchema for Hash {
+    fn schema_name() -> String {
+        "Hash".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some("Hex encoded 32 byte hash".to_owned()),
+                ..Default::default()
+            })),
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                max_length: Some(64),
+                min_length: Some(64),
+                pattern: Some("^[0-9a-fA-F]{64}$".to_owned()),
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
 }
 
 #[cfg(feature = "serde-1")]
-fn deserialize_hash<'de, D>(deserializer: D) -> Result<blake3::Hash, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let hash_raw: &[u8] = Deserialize::deserialize(deserializer)?;
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HashVisitor)
+        } else {
+            deserializer.deserialize_bytes(HashVisitor)
+        }
+    }
+}
+
+/// Visitor for deserializing hashes from hex strings or raw bytes
+#[cfg(feature = "serde-1")]
+struct HashVisitor;
+
+#[cfg(feature = "serde-1")]
+impl<'de> Visitor<'de> for HashVisitor {
+    type Value = Hash;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("32 bytes or a hex string of 64 digits")
+    }
 
-    if hash_raw.len() != blake3::OUT_LEN {
-        return Err(serde::de::Error::custom("Invalid hash length"));
+    fn visit_str<E: de::Error>(self, hex: &str) -> Result<Self::Value, E> {
+        hex.parse().map_err(E::custom)
     }
 
-    let mut hash_bytes: [u8; blake3::OUT_LEN] = [0; blake3::OUT_LEN];
-    hash_bytes.copy_from_slice(hash_raw);
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Hash::try_from(bytes).map_err(|_| E::invalid_length(bytes.len(), &self))
+    }
+
+    // Some binary formats serialize bytes as a sequence
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0; blake3::OUT_LEN];
 
-    Ok(hash_bytes.into())
+        for (len, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(len, &self))?;
+        }
+
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(de::Error::invalid_length(blake3::OUT_LEN + 1, &self));
+        }
+
+        Ok(bytes.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_hash_conversions() {
+        let hash = Hash::from([1; 32]);
+
+        assert_eq!(hash, [1; 32]);
+        assert_eq!([1; 32], hash.to_bytes());
+        assert_eq!(&[1; 32][..], hash.as_ref());
+
+        assert_eq!(Ok(hash), Hash::try_from(&[1; 32][..]));
+        assert_eq!(Err(UtreexoError::InvalidHash), Hash::try_from(&[1; 31][..]));
+    }
+
+    #[test]
+    fn check_hash_hex() {
+        let mut bytes = [0xab; 32];
+        bytes[0] = 0x01;
+        let hash = Hash::from(bytes);
+
+        let hex = format!("01{}", "ab".repeat(31));
+
+        assert_eq!(hex, hash.to_string());
+        assert_eq!(hex, format!("{:x}", hash));
+        assert_eq!(format!("0x{}", hex), format!("{:#x}", hash));
+
+        assert_eq!(Ok(hash), hex.parse());
+        assert_eq!(Ok(hash), hex.to_uppercase().parse());
+        assert_eq!(Err(UtreexoError::InvalidHash), hex[1..].parse::<Hash>());
+        assert_eq!(
+            Err(UtreexoError::InvalidHash),
+            hex.replace("ab", "xy").parse::<Hash>()
+        );
+    }
+
+    #[test]
+    fn check_hash_ordering() {
+        let mut first = [0; 32];
+        first[31] = 1;
+
+        let mut second = [0; 32];
+        second[0] = 1;
+
+        assert!(Hash::from(first) < Hash::from(second));
+        assert!(Hash::from([2; 32]) > Hash::from(second));
+        assert_eq!(
+            Ordering::Equal,
+            Hash::from([2; 32]).cmp(&Hash::from([2; 32]))
+        );
+    }
+
+    #[cfg(feature = "serde-1")]
+    #[test]
+    fn check_hash_serde() {
+        use serde_test::{assert_de_tokens, assert_tokens, Configure, Token};
+
+        let hash = Hash::from([1; 32]);
+        let hex = "0101010101010101010101010101010101010101010101010101010101010101";
+
+        assert_tokens(&hash.readable(), &[Token::Str(hex)]);
+        assert_tokens(&hash.compact(), &[Token::Bytes(&[1; 32])]);
+
+        let mut tokens = vec![Token::Seq { len: Some(32) }];
+        tokens.extend(vec![Token::U8(1); 32]);
+        tokens.push(Token::SeqEnd);
+        assert_de_tokens(&hash.compact(), &tokens);
+    }
 }
@@ -0,0 +1,587 @@
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "digest")]
+use core::marker::PhantomData;
+
+#[cfg(feature = "poseidon")]
+use ark_bn254::Fr;
+#[cfg(feature = "poseidon")]
+use ark_ff::{BigInt, BigInteger, PrimeField};
+#[cfg(feature = "poseidon")]
+use core::convert::TryInto;
+#[cfg(feature = "digest")]
+use digest::{consts::U32, Digest, OutputSizeUser};
+#[cfg(feature = "poseidon")]
+use light_poseidon::{Poseidon, PoseidonHasher as _};
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hash::from_blake3,
+    metrics::{count, Counter},
+    Hash,
+};
+
+/// Trait for hash functions used to calculate leaf and intermediate hashes in merkle forest
+pub trait Hasher {
+    /// Calculates hash of a leaf
+    fn hash_leaf(&self, value: &[u8]) -> Hash;
+
+    /// Calculates intermediate hash of two values
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash;
+
+    /// Calculates leaf hashes of given values (same as calling `hash_leaf()` for every value, but
+    /// hashers may override it to reuse their state between values)
+    fn hash_leaves<T: AsRef<[u8]>>(&self, leaf_values: &[T]) -> Vec<Hash> {
+        leaf_values
+            .iter()
+            .map(|leaf_value| self.hash_leaf(leaf_value.as_ref()))
+            .collect()
+    }
+}
+
+/// Blake3 hasher (this is the default hasher used by accumulator and forest)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct Blake3;
+
+impl Hasher for Blake3 {
+    fn hash_leaf(&self, value: &[u8]) -> Hash {
+        count(Counter::Hash, 1);
+
+        crate::hash_leaf(value)
+    }
+
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+        count(Counter::Hash, 1);
+
+        crate::hash_intermediate(left, right)
+    }
+
+    fn hash_leaves<T: AsRef<[u8]>>(&self, leaf_values: &[T]) -> Vec<Hash> {
+        hash_leaves_reusing(blake3::Hasher::new(), leaf_values)
+    }
+}
+
+/// Keyed Blake3 hasher (uses `blake3::Hasher::new_keyed()` for calculating leaf and intermediate
+/// hashes so that accumulator is bound to an application key)
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct KeyedBlake3 {
+    key: [u8; blake3::KEY_LEN],
+}
+
+impl KeyedBlake3 {
+    /// Creates a new instance of keyed blake3 hasher with given key
+    pub fn new(key: [u8; blake3::KEY_LEN]) -> Self {
+        Self { key }
+    }
+}
+
+impl Hasher for KeyedBlake3 {
+    fn hash_leaf(&self, value: &[u8]) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+
+        // Add `0` byte to leaf nodes to prevent second preimage attack
+        // https://en.wikipedia.org/wiki/Merkle_tree#Second_preimage_attack
+        hasher.update(&[0]);
+        crate::update_leaf(&mut hasher, value);
+
+        // Calling inherent `finalize()` explicitly because `Digest::finalize()` may be in scope
+        from_blake3(blake3::Hasher::finalize(&hasher))
+    }
+
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+
+        // Add `1` byte to intermediate nodes to prevent second preimage attack
+        // https://en.wikipedia.org/wiki/Merkle_tree#Second_preimage_attack
+        hasher.update(&[1]);
+        hasher.update(left.as_bytes());
+        hasher.update(right.as_bytes());
+
+        from_blake3(blake3::Hasher::finalize(&hasher))
+    }
+
+    fn hash_leaves<T: AsRef<[u8]>>(&self, leaf_values: &[T]) -> Vec<Hash> {
+        hash_leaves_reusing(blake3::Hasher::new_keyed(&self.key), leaf_values)
+    }
+}
+
+/// Blake3 hasher with a per-accumulator salt mixed into leaf hashes (leaf hashes are calculated
+/// using `blake3::Hasher::new_keyed()` with salt as key and intermediate hashes are same as
+/// [`Blake3`])
+///
+/// Unlike key of [`KeyedBlake3`], salt is not secret and is included in serialized state of
+/// accumulators and forests (see [`MemoryForest::to_salted_bytes`]). It prevents adversaries, who
+/// can influence inserted values, from precomputing values against a known hashing scheme, so, it
+/// should be chosen randomly for every accumulator.
+///
+/// [`MemoryForest::to_salted_bytes`]: crate::MemoryForest::to_salted_bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct SaltedBlake3 {
+    salt: [u8; blake3::KEY_LEN],
+}
+
+impl SaltedBlake3 {
+    /// Creates a new instance of salted blake3 hasher with given salt
+    pub fn new(salt: [u8; blake3::KEY_LEN]) -> Self {
+        Self { salt }
+    }
+
+    /// Returns salt mixed into leaf hashes
+    pub fn salt(&self) -> &[u8; blake3::KEY_LEN] {
+        &self.salt
+    }
+}
+
+impl Hasher for SaltedBlake3 {
+    fn hash_leaf(&self, value: &[u8]) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hasher = blake3::Hasher::new_keyed(&self.salt);
+        hasher.update(&[0]);
+        crate::update_leaf(&mut hasher, value);
+
+        from_blake3(blake3::Hasher::finalize(&hasher))
+    }
+
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+        count(Counter::Hash, 1);
+
+        crate::hash_intermediate(left, right)
+    }
+
+    fn hash_leaves<T: AsRef<[u8]>>(&self, leaf_values: &[T]) -> Vec<Hash> {
+        hash_leaves_reusing(blake3::Hasher::new_keyed(&self.salt), leaf_values)
+    }
+}
+
+/// Calculates leaf hashes of given values using the same blake3 hasher (reset to its initial state,
+/// including key, before every value) instead of creating a new hasher for every value
+fn hash_leaves_reusing<T: AsRef<[u8]>>(mut hasher: blake3::Hasher, leaf_values: &[T]) -> Vec<Hash> {
+    count(Counter::Hash, leaf_values.len() as u64);
+
+    leaf_values
+        .iter()
+        .map(|leaf_value| {
+            hasher.reset();
+            hasher.update(&[0]);
+            crate::update_leaf(&mut hasher, leaf_value.as_ref());
+
+            from_blake3(blake3::Hasher::finalize(&hasher))
+        })
+        .collect()
+}
+
+// Key is not printed to prevent it from leaking into logs
+impl fmt::Debug for KeyedBlake3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyedBlake3")
+    }
+}
+
+/// Adapter for using any `digest::Digest` (`digest` 0.10) implementation with 32 byte output
+/// (e.g. `Sha256`, `Keccak256`) as hasher
+#[cfg(feature = "digest")]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-1", serde(bound = ""))]
+pub struct DigestHasher<D>(#[cfg_attr(feature = "serde-1", serde(skip))] PhantomData<D>);
+
+#[cfg(feature = "digest")]
+impl<D> DigestHasher<D> {
+    /// Creates a new instance of digest hasher
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D> Hasher for DigestHasher<D>
+where
+    D: Digest + OutputSizeUser<OutputSize = U32>,
+{
+    fn hash_leaf(&self, value: &[u8]) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hasher = D::new();
+
+        // Add `0` byte to leaf nodes to prevent second preimage attack
+        // https://en.wikipedia.org/wiki/Merkle_tree#Second_preimage_attack
+        hasher.update([0]);
+        hasher.update(value);
+
+        digest_output(hasher)
+    }
+
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hasher = D::new();
+
+        // Add `1` byte to intermediate nodes to prevent second preimage attack
+        // https://en.wikipedia.org/wiki/Merkle_tree#Second_preimage_attack
+        hasher.update([1]);
+        hasher.update(left.as_bytes());
+        hasher.update(right.as_bytes());
+
+        digest_output(hasher)
+    }
+}
+
+#[cfg(feature = "digest")]
+fn digest_output<D: Digest + OutputSizeUser<OutputSize = U32>>(hasher: D) -> Hash {
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&hasher.finalize());
+    bytes.into()
+}
+
+/// Poseidon hasher over BN254 scalar field (circom compatible parameters)
+///
+/// Leaf values are split into 31 byte chunks (so that each chunk fits in a field element) and
+/// absorbed one by one, starting from length of value. Intermediate hashes use a different domain
+/// tag than leaf hashes to prevent second preimage attack. All the hashes are big-endian encodings
+/// of field elements, so, they can be verified cheaply inside SNARK/STARK circuits.
+///
+/// # Non-canonical hashes
+///
+/// A 32 byte hash can be greater than field modulus `p`, i.e., it may not be a canonical encoding
+/// of a field element (hashes calculated by this hasher are always canonical, but leaf hashes
+/// inserted directly or hashes in proofs received from peers may not be). Such hashes are reduced
+/// modulo `p` before hashing, but the number of times `p` is subtracted from each child is added
+/// to domain tag of intermediate hash. So, `x` and `x + p` never produce the same intermediate
+/// hash (which would let a proof of `x` verify for `x + p` as well), while intermediate hashes of
+/// canonical children are same as circom's Poseidon with intermediate domain tag.
+#[cfg(feature = "poseidon")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct PoseidonHasher;
+
+#[cfg(feature = "poseidon")]
+impl PoseidonHasher {
+    /// Number of bytes of leaf value absorbed in one field element
+    const CHUNK_SIZE: usize = 31;
+
+    /// Domain tag for leaf hashes
+    const LEAF_TAG: u64 = 0;
+
+    /// Domain tag for intermediate hashes (of canonical children)
+    const INTERMEDIATE_TAG: u64 = 1;
+
+    /// Number of multiples of field modulus below `2^256` (`2^256 / p` is less than 6)
+    const NUM_MULTIPLES: u64 = 6;
+
+    /// Hashes two field elements with given domain tag
+    fn hash_elements(tag: u64, left: Fr, right: Fr) -> Fr {
+        Poseidon::<Fr>::with_domain_tag_circom(2, Fr::from(tag))
+            .expect("Poseidon parameters for two inputs should always be available")
+            .hash(&[left, right])
+            .expect("Number of inputs should match Poseidon parameters")
+    }
+}
+
+#[cfg(feature = "poseidon")]
+impl Hasher for PoseidonHasher {
+    fn hash_leaf(&self, value: &[u8]) -> Hash {
+        count(Counter::Hash, 1);
+
+        let mut hash = Fr::from(value.len() as u64);
+
+        if value.is_empty() {
+            hash = Self::hash_elements(Self::LEAF_TAG, hash, Fr::from(0u64));
+        }
+
+        for chunk in value.chunks(Self::CHUNK_SIZE) {
+            let element = Fr::from_be_bytes_mod_order(chunk);
+            hash = Self::hash_elements(Self::LEAF_TAG, hash, element);
+        }
+
+        field_element_to_hash(hash)
+    }
+
+    fn hash_intermediate(&self, left: &Hash, right: &Hash) -> Hash {
+        count(Counter::Hash, 1);
+
+        let (left, left_multiple) = hash_to_field_element(left);
+        let (right, right_multiple) = hash_to_field_element(right);
+
+        // Tags of non-canonical children are odd (just like intermediate tag), so, they are never
+        // equal to leaf tag
+        let tag =
+            Self::INTERMEDIATE_TAG + 2 * (left_multiple * Self::NUM_MULTIPLES + right_multiple);
+
+        field_element_to_hash(Self::hash_elements(tag, left, right))
+    }
+}
+
+/// Reduces a hash modulo field modulus and returns the field element along with the number of
+/// times modulus was subtracted from the hash (zero for canonical encodings of field elements)
+#[cfg(feature = "poseidon")]
+fn hash_to_field_element(hash: &Hash) -> (Fr, u64) {
+    let bytes = hash.as_bytes();
+
+    // Limbs of big integer are little-endian
+    let mut value = BigInt::<4>([0; 4]);
+    for (limb, chunk) in value.0.iter_mut().zip(bytes.rchunks(8)) {
+        *limb = u64::from_be_bytes(chunk.try_into().expect("Expected exactly 8 bytes"));
+    }
+
+    let mut multiple = 0;
+    while value >= Fr::MODULUS {
+        value.sub_with_borrow(&Fr::MODULUS);
+        multiple += 1;
+    }
+
+    let element = Fr::from_bigint(value).expect("Expected a value less than field modulus");
+
+    (element, multiple)
+}
+
+#[cfg(feature = "poseidon")]
+fn field_element_to_hash(element: Fr) -> Hash {
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&element.into_bigint().to_bytes_be());
+    bytes.into()
+}
+
+// Manual implementations to avoid unnecessary trait bounds on `D`
+
+#[cfg(feature = "digest")]
+impl<D> fmt::Debug for DigestHasher<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DigestHasher")
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D> Default for DigestHasher<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D> Clone for DigestHasher<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D> Copy for DigestHasher<D> {}
+
+#[cfg(feature = "digest")]
+impl<D> PartialEq for DigestHasher<D> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D> Eq for DigestHasher<D> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_blake3_hasher() {
+        let left = [0; 32].into();
+        let right = [1; 32].into();
+
+        assert_eq!(crate::hash_leaf([2; 32]), Blake3.hash_leaf(&[2; 32]));
+        assert_eq!(
+            crate::hash_intermediate(&left, &right),
+            Blake3.hash_intermediate(&left, &right)
+        );
+    }
+
+    #[test]
+    fn check_keyed_blake3_hasher() {
+        let hasher = KeyedBlake3::new([7; 32]);
+
+        let mut expected = blake3::Hasher::new_keyed(&[7; 32]);
+        expected.update(&[0]);
+        expected.update(&[2; 32]);
+
+        assert_eq!(
+            hasher.hash_leaf(&[2; 32]),
+            from_blake3(blake3::Hasher::finalize(&expected))
+        );
+        assert_ne!(hasher.hash_leaf(&[2; 32]), Blake3.hash_leaf(&[2; 32]));
+        assert_ne!(
+            hasher.hash_leaf(&[2; 32]),
+            KeyedBlake3::new([8; 32]).hash_leaf(&[2; 32])
+        );
+
+        let left = [0; 32].into();
+        let right = [1; 32].into();
+
+        assert_ne!(
+            hasher.hash_intermediate(&left, &right),
+            Blake3.hash_intermediate(&left, &right)
+        );
+    }
+
+    #[test]
+    fn check_salted_blake3_hasher() {
+        let hasher = SaltedBlake3::new([7; 32]);
+        let left = [0; 32].into();
+        let right = [1; 32].into();
+
+        // Leaf hashes are salted but intermediate hashes are not
+        assert_eq!(&[7; 32], hasher.salt());
+        assert_ne!(Blake3.hash_leaf(&[2; 32]), hasher.hash_leaf(&[2; 32]));
+        assert_ne!(
+            SaltedBlake3::new([8; 32]).hash_leaf(&[2; 32]),
+            hasher.hash_leaf(&[2; 32])
+        );
+        assert_eq!(
+            Blake3.hash_intermediate(&left, &right),
+            hasher.hash_intermediate(&left, &right)
+        );
+        assert_eq!(
+            vec![hasher.hash_leaf(&[2; 32]), hasher.hash_leaf(&[3; 32])],
+            hasher.hash_leaves(&[[2; 32], [3; 32]])
+        );
+    }
+
+    #[test]
+    fn check_large_leaf_hash() {
+        // Large enough to be hashed using multiple threads with `rayon` feature
+        let value = (0..(1 << 20)).map(|i| i as u8).collect::<Vec<u8>>();
+        let keyed = KeyedBlake3::new([7; 32]);
+
+        let mut expected = blake3::Hasher::new();
+        expected.update(&[0]);
+        expected.update(&value);
+        let expected = from_blake3(blake3::Hasher::finalize(&expected));
+
+        assert_eq!(expected, Blake3.hash_leaf(&value));
+        assert_eq!(
+            vec![expected, expected],
+            Blake3.hash_leaves(&[&value, &value])
+        );
+
+        let mut expected = blake3::Hasher::new_keyed(&[7; 32]);
+        expected.update(&[0]);
+        expected.update(&value);
+
+        assert_eq!(
+            from_blake3(blake3::Hasher::finalize(&expected)),
+            keyed.hash_leaf(&value)
+        );
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn check_digest_hasher() {
+        use sha2::Sha256;
+
+        let hasher = DigestHasher::<Sha256>::new();
+
+        let leaf_hash = hasher.hash_leaf(&[2; 32]);
+        let expected: [u8; 32] = Sha256::new()
+            .chain_update([0])
+            .chain_update([2; 32])
+            .finalize()
+            .into();
+        assert_eq!(leaf_hash, expected);
+
+        let left = [0; 32].into();
+        let right = [1; 32].into();
+
+        let intermediate_hash = hasher.hash_intermediate(&left, &right);
+        let expected: [u8; 32] = Sha256::new()
+            .chain_update([1])
+            .chain_update([0; 32])
+            .chain_update([1; 32])
+            .finalize()
+            .into();
+        assert_eq!(intermediate_hash, expected);
+    }
+
+    #[cfg(feature = "poseidon")]
+    #[test]
+    fn check_poseidon_hasher() {
+        let hasher = PoseidonHasher;
+
+        let leaf_hash = hasher.hash_leaf(&[2; 32]);
+        assert_eq!(leaf_hash, hasher.hash_leaf(&[2; 32]));
+        assert_ne!(leaf_hash, hasher.hash_leaf(&[2; 31]));
+        assert_ne!(hasher.hash_leaf(&[]), hasher.hash_leaf(&[0]));
+
+        // All the hashes should be canonical encodings of field elements
+        let intermediate_hash = hasher.hash_intermediate(&leaf_hash, &leaf_hash);
+        assert_eq!(
+            intermediate_hash,
+            field_element_to_hash(Fr::from_be_bytes_mod_order(intermediate_hash.as_bytes()))
+        );
+
+        // Intermediate hashes should be domain separated from leaf hashes
+        let element = Fr::from_be_bytes_mod_order(leaf_hash.as_bytes());
+        assert_ne!(
+            intermediate_hash,
+            field_element_to_hash(PoseidonHasher::hash_elements(
+                PoseidonHasher::LEAF_TAG,
+                element,
+                element
+            ))
+        );
+
+        // Intermediate hashes of canonical children use intermediate domain tag
+        assert_eq!(
+            intermediate_hash,
+            field_element_to_hash(PoseidonHasher::hash_elements(
+                PoseidonHasher::INTERMEDIATE_TAG,
+                element,
+                element
+            ))
+        );
+    }
+
+    #[cfg(feature = "poseidon")]
+    #[test]
+    fn check_poseidon_hasher_non_canonical() {
+        let hasher = PoseidonHasher;
+
+        let leaf_hash = hasher.hash_leaf(&[2; 32]);
+        let other_hash = hasher.hash_leaf(&[3; 32]);
+
+        // `leaf_hash + p` is less than `2^256` because `p` is greater than `2^253`
+        let mut value = hash_to_field_element(&leaf_hash).0.into_bigint();
+        assert!(!value.add_with_carry(&Fr::MODULUS));
+
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&value.to_bytes_be());
+        let non_canonical = Hash::from(bytes);
+
+        let (element, multiple) = hash_to_field_element(&non_canonical);
+        assert_eq!(
+            (element, 1),
+            (hash_to_field_element(&leaf_hash).0, multiple)
+        );
+
+        // `x` and `x + p` encode the same field element but their intermediate hashes differ
+        assert_ne!(
+            hasher.hash_intermediate(&leaf_hash, &other_hash),
+            hasher.hash_intermediate(&non_canonical, &other_hash)
+        );
+        assert_ne!(
+            hasher.hash_intermediate(&other_hash, &leaf_hash),
+            hasher.hash_intermediate(&other_hash, &non_canonical)
+        );
+        assert_ne!(
+            hasher.hash_intermediate(&non_canonical, &other_hash),
+            hasher.hash_intermediate(&other_hash, &non_canonical)
+        );
+
+        // Largest hash is reduced as well
+        assert_eq!(5, hash_to_field_element(&Hash::from([255; 32])).1);
+    }
+}
@@ -0,0 +1,127 @@
+use alloc::{string::String, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Direction, Proof, UtreexoError};
+
+/// JSON representation of a proof
+///
+/// ```json
+/// {
+///   "height": 2,
+///   "path": "10",
+///   "leaf_hash": "<64 hex digits>",
+///   "sibling_hashes": ["<64 hex digits>", "<64 hex digits>"]
+/// }
+/// ```
+///
+/// - `path` contains one bit per level, from leaf to root, where `1` means that the sibling is on
+///   the left and `0` means that the sibling is on the right
+/// - `sibling_hashes` are ordered from leaf to root
+#[derive(Serialize, Deserialize)]
+struct JsonProof {
+    height: usize,
+    path: String,
+    leaf_hash: String,
+    sibling_hashes: Vec<String>,
+}
+
+impl Proof {
+    /// Returns JSON representation of proof with hex encoded hashes
+    pub fn to_json(&self) -> String {
+        let json_proof = JsonProof {
+            height: self.path.height(),
+            path: self
+                .path
+                .0
+                .iter()
+                .map(|bit| if bit { '1' } else { '0' })
+                .collect(),
+            leaf_hash: self.leaf_hash.to_string(),
+            sibling_hashes: self
+                .sibling_hashes
+                .iter()
+                .map(|hash| hash.to_string())
+                .collect(),
+        };
+
+        serde_json::to_string(&json_proof).expect("Proof should always be serializable to JSON")
+    }
+
+    /// Parses proof from its JSON representation (see [`Proof::to_json`])
+    pub fn from_json(json: &str) -> Result<Self, UtreexoError> {
+        let json_proof: JsonProof =
+            serde_json::from_str(json).map_err(|err| UtreexoError::InvalidJson(err.to_string()))?;
+
+        if json_proof.path.len() != json_proof.height {
+            return Err(UtreexoError::InvalidJson(format!(
+                "Length of path ({}) does not match height ({})",
+                json_proof.path.len(),
+                json_proof.height
+            )));
+        }
+
+        let path = json_proof
+            .path
+            .chars()
+            .map(|bit| match bit {
+                '0' => Ok(Direction::Right),
+                '1' => Ok(Direction::Left),
+                _ => Err(UtreexoError::InvalidJson(String::from(
+                    "Path should only contain `0` and `1`",
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sibling_hashes = json_proof
+            .sibling_hashes
+            .iter()
+            .map(|hash| hash.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Proof::from_parts(json_proof.leaf_hash.parse()?, path, sibling_hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_proof_json() {
+        let mut forest = MemoryForest::new();
+
+        forest.insert([0; 32]);
+        forest.insert([1; 32]);
+        forest.insert([2; 32]);
+        forest.insert([3; 32]);
+
+        let proof = forest.prove([2; 32]).unwrap().unwrap();
+        let json = proof.to_json();
+
+        assert!(json.starts_with(r#"{"height":2,"path":"01","leaf_hash":""#));
+        assert_eq!(proof, Proof::from_json(&json).unwrap());
+
+        let json = json.replace(r#""path":"01""#, r#""path":"0x""#);
+        assert!(matches!(
+            Proof::from_json(&json),
+            Err(UtreexoError::InvalidJson(_))
+        ));
+
+        let json = format!(
+            r#"{{"height":1,"path":"1","leaf_hash":"{}","sibling_hashes":[]}}"#,
+            proof.leaf_hash
+        );
+        assert_eq!(
+            Err(UtreexoError::ProofLengthMismatch {
+                height: 1,
+                num_sibling_hashes: 0
+            }),
+            Proof::from_json(&json)
+        );
+
+        let json = r#"{"height":0,"path":"","leaf_hash":"zz","sibling_hashes":[]}"#;
+        assert_eq!(Err(UtreexoError::InvalidHash), Proof::from_json(json));
+    }
+}
@@ -0,0 +1,50 @@
+use alloc::{string::String, vec::Vec};
+
+/// Trait for values which have a canonical byte encoding, i.e., the bytes hashed to calculate their
+/// leaf hashes
+///
+/// Every value must always be encoded to same bytes (independent of platform, version of crate,
+/// etc.) so that its leaf hash does not change between insertion and deletion.
+pub trait LeafEncode {
+    /// Appends canonical encoding of value to given bytes
+    fn encode_leaf(&self, bytes: &mut Vec<u8>);
+
+    /// Returns canonical encoding of value
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode_leaf(&mut bytes);
+        bytes
+    }
+}
+
+impl LeafEncode for [u8] {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self);
+    }
+}
+
+impl<const N: usize> LeafEncode for [u8; N] {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self);
+    }
+}
+
+impl LeafEncode for Vec<u8> {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self);
+    }
+}
+
+/// Strings are encoded as their UTF-8 bytes
+impl LeafEncode for str {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.as_bytes());
+    }
+}
+
+/// Strings are encoded as their UTF-8 bytes
+impl LeafEncode for String {
+    fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.as_bytes());
+    }
+}
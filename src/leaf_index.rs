@@ -0,0 +1,558 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::{convert::TryInto, fmt, mem::size_of};
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+use crate::Hash;
+
+/// Position stored in empty slots (there can never be a leaf at this position)
+const EMPTY: u64 = u64::MAX;
+
+/// Minimum number of slots in a non-empty table
+const MIN_CAPACITY: usize = 8;
+
+/// Number of slots in a chunk of table is `2^CHUNK_BITS` (i.e., chunks of 64 KiB)
+const CHUNK_BITS: usize = 12;
+
+/// Number of slots in a chunk of table
+const CHUNK_LEN: usize = 1 << CHUNK_BITS;
+
+/// Compact open addressing (linear probing) hash table which maps leaf hashes to positions of
+/// leaves in forest
+///
+/// Leaf hashes are not stored in table. Every slot only contains first 8 bytes of leaf hash and
+/// position of leaf. So, lookups take a function to check whether a leaf hash is present at a
+/// position.
+///
+/// Home slot of a leaf is calculated by mixing its prefix with a per-index random seed, so that
+/// leaves ground to share the low bits of their hashes (e.g., by spending outputs with chosen
+/// values) do not end up in the same cluster. With `std` feature, the seed is taken from
+/// [`RandomState`](std::collections::hash_map::RandomState). Without it, there is no source of
+/// randomness and a fixed seed is used; use a hasher with a secret key or salt (e.g.,
+/// [`SaltedBlake3`](crate::SaltedBlake3)) if leaves can be chosen by an adversary.
+///
+/// Slots are stored in reference counted chunks of `CHUNK_LEN` slots. So, cloning the index (e.g.,
+/// for a snapshot of forest) only copies pointers to chunks, and an update after cloning copies
+/// only the chunks it writes to.
+///
+/// With `zeroize` feature, slots are wiped when the index is dropped or resized. A chunk shared
+/// with a clone is wiped by the last index dropping it.
+#[derive(Clone)]
+pub(crate) struct LeafIndex {
+    /// Chunks of slots (a table with fewer than `CHUNK_LEN` slots has one smaller chunk)
+    chunks: Vec<Arc<Vec<Slot>>>,
+    /// Number of slots in table (always zero or a power of two)
+    capacity: usize,
+    /// Number of occupied slots
+    len: usize,
+    /// Seed mixed into prefixes to calculate home slots
+    seed: Seed,
+}
+
+/// Seed of home slot function (see [`home`])
+#[derive(Clone, Copy)]
+struct Seed {
+    /// Value xor-ed with prefix
+    k0: u64,
+    /// Multiplier (always odd)
+    k1: u64,
+}
+
+impl Seed {
+    /// Returns a new random seed
+    #[cfg(feature = "std")]
+    fn new() -> Self {
+        use std::{
+            collections::hash_map::RandomState,
+            hash::{BuildHasher, Hasher},
+        };
+
+        let state = RandomState::new();
+        let key = |value: u64| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(value);
+            hasher.finish()
+        };
+
+        Self {
+            k0: key(0),
+            k1: key(1) | 1,
+        }
+    }
+
+    /// Returns the fixed seed (there is no source of randomness without `std`)
+    #[cfg(not(feature = "std"))]
+    fn new() -> Self {
+        Self {
+            k0: 0x243f_6a88_85a3_08d3,
+            k1: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+}
+
+/// Slot of leaf index
+#[derive(Clone, Copy)]
+struct Slot {
+    /// First 8 bytes of leaf hash
+    prefix: u64,
+    /// Position of leaf in forest (or `EMPTY`)
+    position: u64,
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        prefix: 0,
+        position: EMPTY,
+    };
+
+    fn is_empty(&self) -> bool {
+        self.position == EMPTY
+    }
+}
+
+impl LeafIndex {
+    /// Returns the number of slots needed for given number of leaves (tables are kept at most 3/4
+    /// full)
+    pub(crate) fn capacity_for(len: usize) -> usize {
+        if len == 0 {
+            0
+        } else {
+            (len + len / 3 + 1).next_power_of_two().max(MIN_CAPACITY)
+        }
+    }
+
+    /// Creates an empty index which can hold given number of leaves without resizing
+    pub(crate) fn with_capacity(len: usize) -> Self {
+        let capacity = Self::capacity_for(len);
+
+        Self {
+            chunks: empty_chunks(capacity),
+            capacity,
+            len: 0,
+            seed: Seed::new(),
+        }
+    }
+
+    /// Returns the number of leaves in index
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of slots in index
+    #[cfg(test)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns heap memory (in bytes) used by slots of index (chunks shared with clones are
+    /// counted in full)
+    pub(crate) fn heap_size(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.capacity() * size_of::<Slot>())
+            .sum()
+    }
+
+    /// Returns heap memory (in bytes) used for bookkeeping of chunks of index (list of chunks,
+    /// reference counts and headers of slot vectors)
+    pub(crate) fn overhead(&self) -> usize {
+        // Reference counts are allocated along with the chunk
+        let chunk_header = 2 * size_of::<usize>() + size_of::<Vec<Slot>>();
+
+        self.chunks.capacity() * size_of::<Arc<Vec<Slot>>>() + self.chunks.len() * chunk_header
+    }
+
+    /// Adds a leaf at given position to index
+    pub(crate) fn insert(&mut self, leaf_hash: &Hash, position: u64) {
+        if Self::capacity_for(self.len + 1) > self.capacity {
+            self.grow();
+        }
+
+        let prefix = prefix(leaf_hash);
+        let mask = self.capacity - 1;
+        let mut index = home(self.seed, prefix, mask);
+
+        while !self.slot(index).is_empty() {
+            index = (index + 1) & mask;
+        }
+
+        *self.slot_mut(index) = Slot { prefix, position };
+        self.len += 1;
+    }
+
+    /// Returns position of given leaf hash in forest, if present. `is_leaf` is called with
+    /// candidate positions (with matching prefix) and should return `true` if the leaf hash is
+    /// present at that position.
+    pub(crate) fn find(&self, leaf_hash: &Hash, is_leaf: impl Fn(u64) -> bool) -> Option<u64> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let prefix = prefix(leaf_hash);
+        let mask = self.capacity - 1;
+        let mut index = home(self.seed, prefix, mask);
+
+        loop {
+            let slot = self.slot(index);
+
+            if slot.is_empty() {
+                return None;
+            }
+
+            if slot.prefix == prefix && is_leaf(slot.position) {
+                return Some(slot.position);
+            }
+
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// Changes position of leaf at given position (e.g., when the leaf is moved to another tree).
+    /// Returns `false` if the leaf is not present.
+    pub(crate) fn relocate(&mut self, leaf_hash: &Hash, from: u64, to: u64) -> bool {
+        match self.locate(leaf_hash, from) {
+            Some(index) => {
+                self.slot_mut(index).position = to;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes leaf at given position from index. Returns `false` if the leaf is not present.
+    pub(crate) fn remove(&mut self, leaf_hash: &Hash, position: u64) -> bool {
+        let mut hole = match self.locate(leaf_hash, position) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        // Shift following slots of the same cluster backwards, so that there are no empty slots
+        // between home slot of a leaf and the slot it is stored in
+        let mask = self.capacity - 1;
+        let mut index = hole;
+
+        loop {
+            index = (index + 1) & mask;
+            let slot = self.slot(index);
+
+            if slot.is_empty() {
+                break;
+            }
+
+            let distance_from_home = index.wrapping_sub(home(self.seed, slot.prefix, mask)) & mask;
+            let distance_from_hole = index.wrapping_sub(hole) & mask;
+
+            if distance_from_home >= distance_from_hole {
+                *self.slot_mut(hole) = slot;
+                hole = index;
+            }
+        }
+
+        *self.slot_mut(hole) = Slot::EMPTY;
+        self.len -= 1;
+
+        true
+    }
+
+    /// Grows table (if needed), so that given number of additional leaves can be inserted without
+    /// resizing
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let capacity = Self::capacity_for(self.len + additional);
+
+        if capacity > self.capacity {
+            self.resize(capacity);
+        }
+    }
+
+    /// Shrinks table to the minimum number of slots needed for current number of leaves
+    pub(crate) fn shrink_to_fit(&mut self) {
+        let capacity = Self::capacity_for(self.len);
+
+        if capacity < self.capacity {
+            self.resize(capacity);
+        }
+    }
+
+    /// Returns index of slot containing leaf at given position, if present
+    fn locate(&self, leaf_hash: &Hash, position: u64) -> Option<usize> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let prefix = prefix(leaf_hash);
+        let mask = self.capacity - 1;
+        let mut index = home(self.seed, prefix, mask);
+
+        loop {
+            let slot = self.slot(index);
+
+            if slot.is_empty() {
+                return None;
+            }
+
+            if slot.prefix == prefix && slot.position == position {
+                return Some(index);
+            }
+
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// Returns slot at given index
+    fn slot(&self, index: usize) -> Slot {
+        self.chunks[index >> CHUNK_BITS][index & (CHUNK_LEN - 1)]
+    }
+
+    /// Returns mutable reference to slot at given index (chunk containing the slot is copied if it
+    /// is shared with a clone of index)
+    fn slot_mut(&mut self, index: usize) -> &mut Slot {
+        &mut Arc::make_mut(&mut self.chunks[index >> CHUNK_BITS])[index & (CHUNK_LEN - 1)]
+    }
+
+    /// Doubles the number of slots in table
+    fn grow(&mut self) {
+        self.resize((self.capacity * 2).max(MIN_CAPACITY));
+    }
+
+    /// Changes the number of slots in table (which must be zero or a power of two large enough for
+    /// all the leaves) and reinserts all the leaves
+    fn resize(&mut self, capacity: usize) {
+        let chunks = core::mem::replace(&mut self.chunks, empty_chunks(capacity));
+        let mask = capacity.wrapping_sub(1);
+        self.capacity = capacity;
+
+        for slot in chunks.iter().flat_map(|chunk| chunk.iter()) {
+            if slot.is_empty() {
+                continue;
+            }
+
+            let mut index = home(self.seed, slot.prefix, mask);
+
+            while !self.slot(index).is_empty() {
+                index = (index + 1) & mask;
+            }
+
+            *self.slot_mut(index) = *slot;
+        }
+
+        #[cfg(feature = "zeroize")]
+        {
+            let mut chunks = chunks;
+            zeroize_chunks(&mut chunks);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for LeafIndex {
+    fn zeroize(&mut self) {
+        zeroize_chunks(&mut self.chunks);
+        self.chunks = Vec::new();
+        self.capacity = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for LeafIndex {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for Slot {
+    fn zeroize(&mut self) {
+        self.prefix.zeroize();
+        self.position.zeroize();
+    }
+}
+
+impl Default for LeafIndex {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl fmt::Debug for LeafIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeafIndex")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Returns chunks of an empty table with given number of slots
+fn empty_chunks(capacity: usize) -> Vec<Arc<Vec<Slot>>> {
+    (0..capacity.div_ceil(CHUNK_LEN))
+        .map(|_| Arc::new(vec![Slot::EMPTY; capacity.min(CHUNK_LEN)]))
+        .collect()
+}
+
+/// Wipes slots of all the chunks which are not shared with a clone of index (shared chunks are
+/// wiped by the last index dropping them)
+#[cfg(feature = "zeroize")]
+fn zeroize_chunks(chunks: &mut [Arc<Vec<Slot>>]) {
+    for chunk in chunks.iter_mut() {
+        if let Some(slots) = Arc::get_mut(chunk) {
+            slots.zeroize();
+        }
+    }
+}
+
+/// Returns first 8 bytes of leaf hash
+fn prefix(leaf_hash: &Hash) -> u64 {
+    u64::from_le_bytes(
+        leaf_hash.as_bytes()[..8]
+            .try_into()
+            .expect("Expected at least 8 bytes"),
+    )
+}
+
+/// Multiplier used for taking slot index from high bits of mixed prefix (`2^64 / golden ratio`)
+const FIBONACCI: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Returns home slot of given prefix (folded multiply of seeded prefix followed by Fibonacci
+/// hashing). Slot index is taken from high bits of the last product because low bits of a folded
+/// multiply cluster for some seeds when prefixes differ only in their high bits.
+fn home(seed: Seed, prefix: u64, mask: usize) -> usize {
+    let product = u128::from(prefix ^ seed.k0) * u128::from(seed.k1);
+    let folded = product as u64 ^ (product >> 64) as u64;
+
+    // Number of slots is a power of two (and at least `MIN_CAPACITY`), so, `mask` has no gaps
+    (folded.wrapping_mul(FIBONACCI) >> (64 - mask.count_ones())) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake3, Hasher};
+
+    #[test]
+    fn check_leaf_index() {
+        let mut index = LeafIndex::default();
+        let leaves: Vec<Hash> = (0..20u8).map(|value| Hash::from([value; 32])).collect();
+
+        for (position, leaf_hash) in leaves.iter().enumerate() {
+            index.insert(leaf_hash, position as u64);
+        }
+
+        assert_eq!(20, index.len());
+        assert_eq!(32, index.capacity());
+
+        let find = |index: &LeafIndex, leaf_hash: &Hash| {
+            index.find(leaf_hash, |position| {
+                &leaves[position as usize] == leaf_hash
+            })
+        };
+
+        assert_eq!(Some(7), find(&index, &leaves[7]));
+
+        // Leaves with same prefix (all the leaves have same home slot)
+        let mut collisions = LeafIndex::default();
+        let colliding: Vec<Hash> = (0..5u8)
+            .map(|value| {
+                let mut bytes = [0; 32];
+                bytes[31] = value;
+                Hash::from(bytes)
+            })
+            .collect();
+
+        for (position, leaf_hash) in colliding.iter().enumerate() {
+            collisions.insert(leaf_hash, position as u64);
+        }
+
+        assert!(collisions.remove(&colliding[1], 1));
+        assert!(!collisions.remove(&colliding[1], 1));
+
+        for (position, leaf_hash) in colliding.iter().enumerate().skip(2) {
+            let found = collisions.find(leaf_hash, |candidate| {
+                &colliding[candidate as usize] == leaf_hash
+            });
+            assert_eq!(Some(position as u64), found);
+        }
+
+        assert!(index.remove(&leaves[7], 7));
+        assert_eq!(None, find(&index, &leaves[7]));
+        assert_eq!(Some(8), find(&index, &leaves[8]));
+
+        for (position, leaf_hash) in leaves.iter().enumerate().skip(10) {
+            assert!(index.remove(leaf_hash, position as u64));
+        }
+
+        index.shrink_to_fit();
+        assert_eq!(16, index.capacity());
+        assert_eq!(Some(8), find(&index, &leaves[8]));
+
+        assert!(index.relocate(&leaves[8], 8, 3));
+        assert!(!index.relocate(&leaves[8], 8, 3));
+        assert_eq!(None, find(&index, &leaves[8]));
+        assert_eq!(Some(3), index.find(&leaves[8], |position| position == 3));
+    }
+
+    #[test]
+    fn check_leaf_index_chunks() {
+        let leaves: Vec<Hash> = (0..20_000u32)
+            .map(|value| Blake3.hash_leaf(&value.to_le_bytes()))
+            .collect();
+
+        let mut index = LeafIndex::default();
+
+        for (position, leaf_hash) in leaves.iter().enumerate() {
+            index.insert(leaf_hash, position as u64);
+        }
+
+        assert_eq!(32_768, index.capacity());
+        assert_eq!(8, index.chunks.len());
+
+        // Updating a clone copies only the chunks it writes to
+        let snapshot = index.clone();
+        assert!(index.remove(&leaves[0], 0));
+
+        let shared = index
+            .chunks
+            .iter()
+            .zip(snapshot.chunks.iter())
+            .filter(|(chunk, other)| Arc::ptr_eq(chunk, other))
+            .count();
+        assert!(shared >= 6);
+
+        assert_eq!(Some(0), snapshot.find(&leaves[0], |position| position == 0));
+        assert_eq!(None, index.find(&leaves[0], |position| position == 0));
+
+        for (position, leaf_hash) in leaves.iter().enumerate().skip(1) {
+            let found = index.find(leaf_hash, |candidate| candidate == position as u64);
+            assert_eq!(Some(position as u64), found);
+        }
+    }
+
+    #[test]
+    fn check_leaf_index_seeded_home() {
+        // Leaves whose prefixes share low 32 bits (these would all have same home slot if low bits
+        // of prefix were used as slot index)
+        let leaves: Vec<Hash> = (0..4096u64)
+            .map(|value| {
+                let mut bytes = [0; 32];
+                bytes[..8].copy_from_slice(&(value << 32).to_le_bytes());
+                Hash::from(bytes)
+            })
+            .collect();
+
+        let mut index = LeafIndex::default();
+
+        for (position, leaf_hash) in leaves.iter().enumerate() {
+            index.insert(leaf_hash, position as u64);
+        }
+
+        let mask = index.capacity() - 1;
+        let longest_probe = (0..index.capacity())
+            .filter(|&slot| !index.slot(slot).is_empty())
+            .map(|slot| slot.wrapping_sub(home(index.seed, index.slot(slot).prefix, mask)) & mask)
+            .max();
+
+        assert!(longest_probe < Some(64));
+    }
+}
@@ -5,43 +5,138 @@
 //! # Utreexo
 extern crate alloc;
 
+use alloc::vec::Vec;
+
 mod accumulator;
+mod apply_block;
+mod archive;
+#[cfg(feature = "bitcoin-leaf")]
+pub mod bitcoin_leaf;
+mod bulk;
+mod cache;
+mod checkpoint;
+mod chunk;
+mod diff;
+mod encoding;
+mod error;
+pub mod estimate;
 mod forest;
 mod hash;
+mod hasher;
+#[cfg(feature = "json")]
+mod json;
+mod leaf_encode;
+mod leaf_index;
+mod metrics;
+mod observer;
+mod operation_log;
+mod partial;
 mod path;
 mod proof;
 mod prover;
+mod render;
+mod reorg;
+mod roots;
+#[cfg(feature = "service")]
+pub mod service;
+mod sharded;
+mod shared;
+mod stats;
+mod transaction;
 mod tree;
+mod typed;
+mod update;
 mod utreexo;
+pub mod wire;
+mod witness;
 
-pub(crate) use self::{
-    hash::Hash,
-    path::{Direction, Path},
-    tree::{merge, Tree},
-};
+pub(crate) use self::tree::{merge, Tree};
 
+use self::hash::from_blake3;
 pub use self::{
-    accumulator::MemoryAccumulator, forest::MemoryForest, proof::Proof, prover::Prover,
+    accumulator::MemoryAccumulator,
+    apply_block::ApplyBlock,
+    archive::ArchiveForest,
+    cache::{CachePolicy, LruPolicy, TtlPolicy},
+    checkpoint::{Checkpoint, CheckpointStore},
+    chunk::{SnapshotAssembler, SnapshotChunk},
+    diff::ForestDiff,
+    error::UtreexoError,
+    forest::{ForestSnapshot, LeafPosition, MemoryForest},
+    hash::Hash,
+    hasher::{Blake3, Hasher, KeyedBlake3, SaltedBlake3},
+    leaf_encode::LeafEncode,
+    observer::ObservedUtreexo,
+    operation_log::{LoggedUtreexo, Operation, OperationLog},
+    partial::PartialForest,
+    path::{Direction, Directions, Path},
+    proof::Proof,
+    prover::Prover,
+    reorg::ReorgManager,
+    roots::Roots,
+    sharded::ShardedForest,
+    shared::SharedUtreexo,
+    stats::ForestStats,
+    transaction::Transaction,
+    tree::TreeRef,
+    typed::TypedForest,
+    update::{BlockChanges, UpdateData},
     utreexo::Utreexo,
+    witness::{BaggingOrder, RootProof, RootWitness},
 };
 
-use blake3::Hasher;
+#[cfg(feature = "digest")]
+pub use self::hasher::DigestHasher;
+#[cfg(feature = "poseidon")]
+pub use self::hasher::PoseidonHasher;
+#[cfg(feature = "metrics")]
+pub use self::metrics::{metrics, reset_metrics, Metrics};
+#[cfg(feature = "std")]
+pub use self::shared::{SharedAccumulator, SharedForest};
+
+/// Leaf values of at least this size are hashed using multiple threads (with `rayon` feature)
+#[cfg(feature = "rayon")]
+const PARALLEL_LEAF_THRESHOLD: usize = 128 * 1024;
 
-/// Calculates hash of a leaf
+/// Calculates hash of a leaf (with `rayon` feature, large values are hashed using multiple threads)
 pub fn hash_leaf(value: impl AsRef<[u8]>) -> Hash {
-    let mut hasher = Hasher::new();
+    let mut hasher = blake3::Hasher::new();
 
     // Add `0` byte to leaf nodes to prevent second preimage attack
     // https://en.wikipedia.org/wiki/Merkle_tree#Second_preimage_attack
     hasher.update(&[0]);
-    hasher.update(value.as_ref());
+    update_leaf(&mut hasher, value.as_ref());
+
+    from_blake3(hasher.finalize())
+}
+
+/// Adds leaf value to blake3 hasher. With `rayon` feature, values larger than
+/// `PARALLEL_LEAF_THRESHOLD` are split and hashed using multiple threads (the resulting hash is
+/// the same).
+pub(crate) fn update_leaf(hasher: &mut blake3::Hasher, value: &[u8]) {
+    #[cfg(feature = "rayon")]
+    {
+        if value.len() >= PARALLEL_LEAF_THRESHOLD {
+            hasher.update_with_join::<blake3::join::RayonJoin>(value);
+            return;
+        }
+    }
+
+    hasher.update(value);
+}
 
-    hasher.finalize().into()
+/// Calculates hashes of many leaves (same as calling [`hash_leaf`] for every value, but a single
+/// hasher is reused for many values and, with `rayon` feature, values are hashed in parallel)
+///
+/// Leaf hashes can be inserted directly using bulk insertion (e.g.,
+/// [`MemoryAccumulator::insert_hash_batch`]).
+pub fn hash_leaves<T: AsRef<[u8]> + Sync>(leaf_values: &[T]) -> Vec<Hash> {
+    bulk::hash_leaves(&Blake3, leaf_values)
 }
 
 /// Calculates intermediate hash of two values
 pub(crate) fn hash_intermediate(left: &Hash, right: &Hash) -> Hash {
-    let mut hasher = Hasher::new();
+    let mut hasher = blake3::Hasher::new();
 
     // Add `1` byte to intermediate nodes to prevent second preimage attack
     // https://en.wikipedia.org/wiki/Merkle_tree#Second_preimage_attack
@@ -49,5 +144,5 @@ pub(crate) fn hash_intermediate(left: &Hash, right: &Hash) -> Hash {
     hasher.update(left.as_bytes());
     hasher.update(right.as_bytes());
 
-    hasher.finalize().into()
+    from_blake3(hasher.finalize())
 }
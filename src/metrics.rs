@@ -0,0 +1,116 @@
+#[cfg(feature = "metrics")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters of operations performed by all the accumulators and forests in process since start (or
+/// since the last [`reset_metrics`])
+///
+/// Hash invocations are only counted for hashers provided by this crate.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of inserted leaves
+    pub inserts: u64,
+    /// Number of deleted leaves
+    pub deletes: u64,
+    /// Number of generated inclusion proofs
+    pub proofs: u64,
+    /// Number of verified inclusion proofs (both valid and invalid)
+    pub verifications: u64,
+    /// Number of leaf and intermediate hashes calculated
+    pub hashes: u64,
+    /// Number of times two merkle trees (or subtrees) are merged into one (including merges
+    /// replayed while updating proofs)
+    pub merges: u64,
+    /// Number of times a merkle tree is split into subtrees by a deletion
+    pub splits: u64,
+}
+
+/// Operation counted in metrics
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Counter {
+    Insert,
+    Delete,
+    Prove,
+    Verify,
+    Hash,
+    Merge,
+    Split,
+}
+
+/// Global counters (indexed by `Counter`)
+#[cfg(feature = "metrics")]
+static COUNTERS: [AtomicU64; 7] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// Adds given number of operations to counter (this is a no-op without `metrics` feature)
+#[inline]
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn count(counter: Counter, num: u64) {
+    #[cfg(feature = "metrics")]
+    COUNTERS[counter as usize].fetch_add(num, Ordering::Relaxed);
+}
+
+/// Returns current values of all the counters (see [`Metrics`])
+#[cfg(feature = "metrics")]
+pub fn metrics() -> Metrics {
+    let get = |counter: Counter| COUNTERS[counter as usize].load(Ordering::Relaxed);
+
+    Metrics {
+        inserts: get(Counter::Insert),
+        deletes: get(Counter::Delete),
+        proofs: get(Counter::Prove),
+        verifications: get(Counter::Verify),
+        hashes: get(Counter::Hash),
+        merges: get(Counter::Merge),
+        splits: get(Counter::Split),
+    }
+}
+
+/// Resets all the counters to zero
+#[cfg(feature = "metrics")]
+pub fn reset_metrics() {
+    for counter in COUNTERS.iter() {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+    use crate::{MemoryAccumulator, MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_metrics() {
+        // Counters are global (and tests run in parallel), so, only lower bounds are checked
+        let before = metrics();
+
+        let mut forest = MemoryForest::new();
+        let mut accumulator = MemoryAccumulator::new();
+
+        for value in 0..4u8 {
+            forest.insert([value; 32]);
+            accumulator.insert([value; 32]);
+        }
+
+        let proof = forest.prove([1; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(accumulator.delete(&proof));
+
+        let after = metrics();
+
+        assert!(after.inserts >= before.inserts + 8);
+        assert!(after.deletes >= before.deletes + 2);
+        assert!(after.proofs > before.proofs);
+        assert!(after.verifications >= before.verifications + 2);
+        assert!(after.hashes >= before.hashes + 14);
+        assert!(after.merges >= before.merges + 6);
+        assert!(after.splits >= before.splits + 2);
+    }
+}
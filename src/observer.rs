@@ -0,0 +1,140 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+use crate::{Hash, Proof, Prover, Roots, Utreexo, UtreexoError};
+
+/// Callback invoked with new root hashes after a mutation
+type RootsCallback = Box<dyn FnMut(&[Option<Hash>])>;
+
+/// Wrapper over an accumulator (or forest) which notifies registered callbacks with new root hashes
+/// after every mutation (i.e., every insertion and every successful deletion), so that applications
+/// can push roots (or root commitments) to metrics, gossip or storage without polling
+pub struct ObservedUtreexo<U> {
+    /// Underlying accumulator (or forest)
+    utreexo: U,
+    /// Callbacks invoked after every mutation (in order of registration)
+    callbacks: Vec<RootsCallback>,
+}
+
+impl<U: Utreexo + Roots> ObservedUtreexo<U> {
+    /// Creates a new observed accumulator wrapping given accumulator (or forest) without any
+    /// callbacks
+    pub fn new(utreexo: U) -> Self {
+        Self {
+            utreexo,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Registers a callback which is invoked with new root hashes (indexed by height) after every
+    /// mutation
+    pub fn on_roots_changed(&mut self, callback: impl FnMut(&[Option<Hash>]) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Returns the underlying accumulator (or forest)
+    pub fn utreexo(&self) -> &U {
+        &self.utreexo
+    }
+
+    /// Consumes the wrapper and returns the underlying accumulator (or forest), dropping all the
+    /// callbacks
+    pub fn into_inner(self) -> U {
+        self.utreexo
+    }
+
+    /// Invokes all the callbacks with current root hashes
+    fn notify(&mut self) {
+        if self.callbacks.is_empty() {
+            return;
+        }
+
+        let roots = self.utreexo.roots();
+
+        for callback in self.callbacks.iter_mut() {
+            callback(&roots);
+        }
+    }
+}
+
+impl<U: fmt::Debug> fmt::Debug for ObservedUtreexo<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservedUtreexo")
+            .field("utreexo", &self.utreexo)
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
+
+impl<U: Utreexo + Roots> Utreexo for ObservedUtreexo<U> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        self.utreexo.insert_value(leaf_value);
+        self.notify();
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.utreexo.insert_hash(leaf_hash);
+        self.notify();
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        if !self.utreexo.delete(proof) {
+            return false;
+        }
+
+        self.notify();
+        true
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.utreexo.num_leaves()
+    }
+}
+
+impl<U: Prover> Prover for ObservedUtreexo<U> {
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        self.utreexo.prove_value(leaf_value)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.utreexo.prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        self.utreexo.prove_batch(leaf_hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::MemoryForest;
+
+    #[test]
+    fn check_observed_utreexo() {
+        let mut forest = ObservedUtreexo::new(MemoryForest::new());
+        let notified = Rc::new(RefCell::new(Vec::new()));
+        let num_calls = Rc::new(RefCell::new(0));
+
+        let cloned = notified.clone();
+        forest.on_roots_changed(move |roots| cloned.borrow_mut().push(roots.to_vec()));
+
+        let cloned = num_calls.clone();
+        forest.on_roots_changed(move |_| *cloned.borrow_mut() += 1);
+
+        forest.insert([0; 32]);
+        forest.insert([1; 32]);
+        assert_eq!(forest.utreexo().roots(), notified.borrow()[1]);
+
+        // Failed deletions are not notified
+        let proof = forest.prove([0; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(!forest.delete(&proof));
+
+        assert_eq!(3, notified.borrow().len());
+        assert_eq!(3, *num_calls.borrow());
+        assert_eq!(forest.utreexo().roots(), notified.borrow()[2]);
+    }
+}
@@ -0,0 +1,226 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Blake3, Hash, Hasher, Proof, Prover, Utreexo, UtreexoError};
+
+/// Operation applied to an accumulator (or forest)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub enum Operation {
+    /// Insertion of a leaf with given hash
+    Insert(Hash),
+    /// Deletion of a leaf with given proof
+    Delete(Proof),
+}
+
+/// Append-only log of operations applied to an accumulator (or forest), which can be replayed to
+/// deterministically reconstruct its state (e.g., for recovery after a crash or for finding the
+/// first operation at which states of two nodes diverge)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+pub struct OperationLog {
+    /// Operations in order of application
+    operations: Vec<Operation>,
+}
+
+impl OperationLog {
+    /// Creates a new empty operation log
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends an operation to log
+    pub fn push(&mut self, operation: Operation) {
+        self.operations.push(operation);
+    }
+
+    /// Returns all the operations in log (in order of application)
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Returns the number of operations in log
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns `true` if there are no operations in log
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Returns index of the first operation which differs between two logs (or the length of the
+    /// shorter log if one log is a prefix of the other), or `None` if both logs are same
+    pub fn divergence(&self, other: &OperationLog) -> Option<usize> {
+        if self == other {
+            return None;
+        }
+
+        let common = self
+            .operations
+            .iter()
+            .zip(other.operations.iter())
+            .take_while(|(first, second)| first == second)
+            .count();
+
+        Some(common)
+    }
+
+    /// Applies all the operations in log (in order) to given accumulator (or forest). Returns
+    /// `ReplayFailed` error if a deletion cannot be applied (operations before it remain applied).
+    pub fn replay<U: Utreexo>(&self, utreexo: &mut U) -> Result<(), UtreexoError> {
+        for (index, operation) in self.operations.iter().enumerate() {
+            match operation {
+                Operation::Insert(leaf_hash) => utreexo.insert_hash(*leaf_hash),
+                Operation::Delete(proof) => {
+                    if !utreexo.delete(proof) {
+                        return Err(UtreexoError::ReplayFailed { index });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Wrapper over an accumulator (or forest) which records all the successful operations in an
+/// [`OperationLog`]
+///
+/// Values are hashed by the wrapper before insertion, so, it should use the same hasher as the
+/// underlying accumulator.
+#[derive(Debug, Default, Clone)]
+pub struct LoggedUtreexo<U, H = Blake3> {
+    /// Underlying accumulator (or forest)
+    utreexo: U,
+    /// Log of operations applied to accumulator
+    log: OperationLog,
+    /// Hasher used for calculating leaf hashes
+    hasher: H,
+}
+
+impl<U: Utreexo> LoggedUtreexo<U> {
+    /// Creates a new logged accumulator wrapping given accumulator (or forest) with an empty log
+    pub fn new(utreexo: U) -> Self {
+        Self::with_hasher(utreexo, Blake3)
+    }
+}
+
+impl<U: Utreexo, H: Hasher> LoggedUtreexo<U, H> {
+    /// Creates a new logged accumulator wrapping given accumulator (or forest), which uses given
+    /// hasher, with an empty log
+    pub fn with_hasher(utreexo: U, hasher: H) -> Self {
+        Self {
+            utreexo,
+            log: Default::default(),
+            hasher,
+        }
+    }
+
+    /// Returns the underlying accumulator (or forest)
+    pub fn utreexo(&self) -> &U {
+        &self.utreexo
+    }
+
+    /// Returns log of all the operations applied so far
+    pub fn log(&self) -> &OperationLog {
+        &self.log
+    }
+
+    /// Consumes the wrapper and returns the underlying accumulator (or forest) along with its log
+    pub fn into_parts(self) -> (U, OperationLog) {
+        (self.utreexo, self.log)
+    }
+}
+
+impl<U: Utreexo, H: Hasher> Utreexo for LoggedUtreexo<U, H> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_hash(leaf_hash);
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.utreexo.insert_hash(leaf_hash);
+        self.log.push(Operation::Insert(leaf_hash));
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        if !self.utreexo.delete(proof) {
+            return false;
+        }
+
+        self.log.push(Operation::Delete(proof.clone()));
+        true
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.utreexo.num_leaves()
+    }
+}
+
+impl<U: Prover, H> Prover for LoggedUtreexo<U, H> {
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        self.utreexo.prove_value(leaf_value)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.utreexo.prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        self.utreexo.prove_batch(leaf_hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryAccumulator, MemoryForest};
+
+    #[test]
+    fn check_operation_log() {
+        let mut forest = LoggedUtreexo::new(MemoryForest::new());
+
+        for value in 0..10u8 {
+            forest.insert([value; 32]);
+        }
+
+        for value in [3u8, 7].iter() {
+            let proof = forest.prove([*value; 32]).unwrap().unwrap();
+            assert!(forest.delete(&proof));
+            assert!(!forest.delete(&proof));
+        }
+
+        let (forest, log) = forest.into_parts();
+        assert_eq!(12, log.len());
+
+        let mut accumulator = MemoryAccumulator::new();
+        log.replay(&mut accumulator).unwrap();
+        assert_eq!(forest.roots(), accumulator.root_hashes());
+
+        let mut replayed = MemoryForest::new();
+        log.replay(&mut replayed).unwrap();
+        assert_eq!(forest, replayed);
+
+        // Log of a node which inserted a different value
+        let mut other = OperationLog::new();
+
+        for (index, operation) in log.operations().iter().enumerate() {
+            if index == 5 {
+                other.push(Operation::Insert([0; 32].into()));
+            } else {
+                other.push(operation.clone());
+            }
+        }
+
+        assert_eq!(Some(5), log.divergence(&other));
+        assert_eq!(None, log.divergence(&log.clone()));
+
+        assert_eq!(
+            Err(UtreexoError::ReplayFailed { index: 10 }),
+            other.replay(&mut MemoryAccumulator::new())
+        );
+    }
+}
@@ -0,0 +1,645 @@
+use alloc::vec::Vec;
+use core::mem::{self, size_of};
+
+use crate::{
+    metrics::{count, Counter},
+    Blake3, CachePolicy, Direction, Hash, Hasher, Path, Proof, Prover, TtlPolicy, Utreexo,
+    UtreexoError,
+};
+
+/// Accumulator which also keeps inclusion proofs of leaves marked as remembered
+///
+/// Just like [`MemoryAccumulator`](crate::MemoryAccumulator), it only stores root hashes of merkle
+/// trees, and any leaf can be deleted using a proof generated elsewhere (e.g., by a bridge node
+/// running a [`MemoryForest`](crate::MemoryForest)). Additionally, it stores a proof for every
+/// remembered leaf and updates those proofs while applying insertions and deletions, so, it can
+/// prove remembered leaves without storing full merkle trees.
+///
+/// Every update touches all the remembered proofs, so, this is meant for a relatively small number
+/// of remembered leaves (e.g., UTXOs of a wallet).
+///
+/// Which inserted leaves are remembered (and which remembered leaves are forgotten first when
+/// shrinking) is decided by a [`CachePolicy`]. With the default [`TtlPolicy`] and a TTL horizon
+/// (see [`PartialForest::set_ttl_horizon`]), leaves inserted with a time-to-live hint are
+/// remembered only if they are expected to be deleted within the horizon.
+///
+/// On constrained hosts, memory used by remembered proofs can be capped (see
+/// [`PartialForest::set_memory_limit`]). When the cap is exceeded, leaves chosen by cache policy
+/// are forgotten (e.g., least recently proved leaves with [`LruPolicy`](crate::LruPolicy)), so,
+/// their proofs have to be obtained elsewhere instead of growing memory without bound.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PartialForest<H = Blake3, P = TtlPolicy> {
+    /// Root hashes of merkle trees in forest
+    roots: Vec<Option<Hash>>,
+    /// Proofs of remembered leaves
+    remembered: Vec<Proof>,
+    /// Hasher used for calculating leaf and intermediate hashes
+    hasher: H,
+    /// Policy deciding which leaves are remembered
+    policy: P,
+    /// Maximum memory (in bytes) used by remembered proofs
+    memory_limit: Option<usize>,
+}
+
+/// Subtree containing a remembered leaf while applying an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    /// Merkle tree of given height in forest
+    Root(usize),
+    /// Sibling subtree of given height from proof of deleted leaf
+    Sibling(usize),
+    /// Merkle tree being built by the update
+    New,
+}
+
+impl PartialForest {
+    /// Creates a new instance of partial forest
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<P: CachePolicy> PartialForest<Blake3, P> {
+    /// Creates a new instance of partial forest which uses given cache policy
+    pub fn with_policy(policy: P) -> Self {
+        Self::with_hasher_and_policy(Blake3, policy)
+    }
+}
+
+impl<H: Hasher> PartialForest<H> {
+    /// Creates a new instance of partial forest which uses given hasher
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::with_hasher_and_policy(hasher, Default::default())
+    }
+
+    /// Returns the TTL horizon used for deciding which leaves inserted with a TTL hint are
+    /// remembered (`None` if such leaves are never remembered)
+    pub fn ttl_horizon(&self) -> Option<u64> {
+        self.policy.horizon()
+    }
+
+    /// Sets the TTL horizon, i.e., leaves inserted with a TTL hint (using
+    /// [`PartialForest::insert_with_ttl`]) are remembered only if their TTL is less than or equal
+    /// to the horizon. With `None`, leaves inserted with a TTL hint are never remembered.
+    pub fn set_ttl_horizon(&mut self, ttl_horizon: Option<u64>) {
+        self.policy.set_horizon(ttl_horizon);
+    }
+}
+
+impl<H: Hasher, P: CachePolicy> PartialForest<H, P> {
+    /// Creates a new instance of partial forest which uses given hasher and cache policy
+    pub fn with_hasher_and_policy(hasher: H, policy: P) -> Self {
+        Self {
+            roots: Default::default(),
+            remembered: Default::default(),
+            hasher,
+            policy,
+            memory_limit: None,
+        }
+    }
+
+    /// Returns the cache policy of forest
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Returns the root hashes of all the merkle trees in forest
+    pub fn root_hashes(&self) -> &[Option<Hash>] {
+        &self.roots
+    }
+
+    /// Returns leaf hashes of all the remembered leaves
+    pub fn remembered(&self) -> impl Iterator<Item = &Hash> {
+        self.remembered.iter().map(Proof::leaf_hash)
+    }
+
+    /// Returns memory (in bytes) used by proofs of remembered leaves
+    pub fn cache_size(&self) -> usize {
+        self.remembered.iter().map(proof_size).sum()
+    }
+
+    /// Returns the maximum memory (in bytes) used by proofs of remembered leaves (`None` if memory
+    /// is not limited)
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Sets the maximum memory (in bytes) used by proofs of remembered leaves. Whenever
+    /// [`PartialForest::cache_size`] exceeds the limit (proofs grow as the forest grows),
+    /// remembered leaves chosen by cache policy are forgotten until it fits (or policy does not
+    /// choose any more leaves). Returns leaf hashes of leaves forgotten to fit the new limit.
+    pub fn set_memory_limit(&mut self, memory_limit: Option<usize>) -> Vec<Hash> {
+        self.memory_limit = memory_limit;
+        self.enforce_memory_limit()
+    }
+
+    /// Returns `true` if a leaf with given hash is remembered
+    pub fn is_remembered(&self, leaf_hash: &Hash) -> bool {
+        self.position(leaf_hash).is_some()
+    }
+
+    /// Inserts a new value in forest and remembers it (regardless of cache policy)
+    pub fn insert_and_remember<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        self.insert_with_memory(leaf_value, true);
+    }
+
+    /// Inserts a new value in forest and remembers it only if `remember` is `true` (regardless of
+    /// cache policy)
+    pub fn insert_with_memory<T: AsRef<[u8]>>(&mut self, leaf_value: T, remember: bool) {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_hash_with_memory(leaf_hash, remember);
+    }
+
+    /// Inserts an already hashed leaf in forest and remembers it only if `remember` is `true`
+    /// (regardless of cache policy)
+    pub fn insert_hash_with_memory(&mut self, leaf_hash: Hash, remember: bool) {
+        self.policy.on_insert(&leaf_hash, None);
+        self.insert_leaf(leaf_hash, remember, None);
+    }
+
+    /// Inserts a new value in forest with a time-to-live hint, i.e., the expected number of blocks
+    /// (or any other unit used for TTL horizon) until the value is deleted. Cache policy decides
+    /// whether the value is remembered (e.g., [`TtlPolicy`] remembers it if its TTL is within TTL
+    /// horizon). Returns `true` if the value is remembered.
+    pub fn insert_with_ttl<T: AsRef<[u8]>>(&mut self, leaf_value: T, ttl: u64) -> bool {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_hash_with_ttl(leaf_hash, ttl)
+    }
+
+    /// Inserts an already hashed leaf in forest with a time-to-live hint (see
+    /// [`PartialForest::insert_with_ttl`]). Returns `true` if the leaf is remembered.
+    pub fn insert_hash_with_ttl(&mut self, leaf_hash: Hash, ttl: u64) -> bool {
+        let remember = self.policy.on_insert(&leaf_hash, Some(ttl));
+        self.insert_leaf(leaf_hash, remember, Some(ttl));
+        remember
+    }
+
+    /// Notifies cache policy that the remembered leaf with given hash is accessed (e.g., its proof
+    /// is used). Returns `false` if the leaf is not remembered.
+    ///
+    /// Proofs are generated using shared references (see [`Prover`]), so, accesses are not tracked
+    /// automatically.
+    pub fn touch(&mut self, leaf_hash: &Hash) -> bool {
+        if !self.is_remembered(leaf_hash) {
+            return false;
+        }
+
+        self.policy.on_access(leaf_hash);
+        true
+    }
+
+    /// Forgets remembered leaves chosen by cache policy until at most `max_remembered` leaves are
+    /// remembered (or policy does not choose any more leaves), e.g., on memory pressure. Returns
+    /// leaf hashes of forgotten leaves.
+    pub fn shrink_to(&mut self, max_remembered: usize) -> Vec<Hash> {
+        let mut forgotten = Vec::new();
+
+        while self.remembered.len() > max_remembered {
+            match self.evict() {
+                Some(proof) => forgotten.push(proof.leaf_hash),
+                None => break,
+            }
+        }
+
+        forgotten
+    }
+
+    /// Starts remembering the leaf of given proof. Returns `false` if the proof is not valid.
+    pub fn remember(&mut self, proof: &Proof) -> bool {
+        if !self.verify(proof) {
+            return false;
+        }
+
+        if !self.is_remembered(&proof.leaf_hash) {
+            self.remembered.push(proof.clone());
+            self.policy.on_remember(&proof.leaf_hash, None);
+            self.enforce_memory_limit();
+        }
+
+        true
+    }
+
+    /// Stops remembering the leaf with given hash (the leaf stays in forest). Returns `false` if
+    /// the leaf was not remembered.
+    pub fn forget(&mut self, leaf_hash: &Hash) -> bool {
+        match self.position(leaf_hash) {
+            Some(index) => {
+                self.remembered.swap_remove(index);
+                self.policy.on_forget(leaf_hash);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets a remembered leaf chosen by cache policy and returns its proof, or returns `None` if
+    /// policy does not choose any leaf
+    fn evict(&mut self) -> Option<Proof> {
+        loop {
+            let leaf_hash = self.policy.evict()?;
+
+            if let Some(index) = self.position(&leaf_hash) {
+                return Some(self.remembered.swap_remove(index));
+            }
+        }
+    }
+
+    /// Forgets remembered leaves chosen by cache policy until proofs of remembered leaves fit in
+    /// memory limit. Returns leaf hashes of forgotten leaves.
+    fn enforce_memory_limit(&mut self) -> Vec<Hash> {
+        let mut forgotten = Vec::new();
+
+        let memory_limit = match self.memory_limit {
+            Some(memory_limit) => memory_limit,
+            None => return forgotten,
+        };
+
+        let mut cache_size = self.cache_size();
+
+        while cache_size > memory_limit {
+            match self.evict() {
+                Some(proof) => {
+                    cache_size -= proof_size(&proof);
+                    forgotten.push(proof.leaf_hash);
+                }
+                None => break,
+            }
+        }
+
+        forgotten
+    }
+
+    /// Returns index of proof of given leaf hash in remembered proofs
+    fn position(&self, leaf_hash: &Hash) -> Option<usize> {
+        self.remembered
+            .iter()
+            .position(|proof| &proof.leaf_hash == leaf_hash)
+    }
+
+    /// Verifies inclusion proof of a value in forest
+    fn verify(&self, proof: &Proof) -> bool {
+        match self.roots.get(proof.path.height()) {
+            Some(Some(root_hash)) => proof.verify(&self.hasher, *root_hash),
+            _ => false,
+        }
+    }
+
+    /// Inserts an already hashed leaf (with given TTL hint) in forest and remembers it if
+    /// `remember` is `true`
+    fn insert_leaf(&mut self, leaf_hash: Hash, remember: bool, ttl: Option<u64>) {
+        count(Counter::Insert, 1);
+
+        let remember = remember && !self.is_remembered(&leaf_hash);
+
+        if remember {
+            self.policy.on_remember(&leaf_hash, ttl);
+        }
+
+        insert_leaf(
+            &self.hasher,
+            &mut self.roots,
+            &mut self.remembered,
+            leaf_hash,
+            remember,
+        );
+
+        // Insertion adds a sibling hash to proofs of remembered leaves in merged trees
+        self.enforce_memory_limit();
+    }
+}
+
+impl<H: Hasher, P: CachePolicy> Utreexo for PartialForest<H, P> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_hash(leaf_hash);
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        let remember = self.policy.on_insert(&leaf_hash, None);
+        self.insert_leaf(leaf_hash, remember, None);
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        // Proof should be valid to delete a value from accumulator
+        if !self.verify(proof) {
+            return false;
+        }
+
+        count(Counter::Delete, 1);
+        count(Counter::Split, (proof.path.height() > 0).into());
+
+        if self.is_remembered(&proof.leaf_hash) {
+            self.policy.on_forget(&proof.leaf_hash);
+        }
+
+        delete_leaf(&self.hasher, &mut self.roots, &mut self.remembered, proof);
+        true
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.roots
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| root.is_some())
+            .map(|(height, _)| 1 << height)
+            .sum()
+    }
+}
+
+impl<H: Hasher, P: CachePolicy> Prover for PartialForest<H, P> {
+    /// Returns proof of a leaf value if it is remembered (`None` otherwise)
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.prove_hash(&leaf_hash)
+    }
+
+    /// Returns proof of a leaf hash if it is remembered (`None` otherwise)
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        Ok(self.position(leaf_hash).map(|index| {
+            count(Counter::Prove, 1);
+            self.remembered[index].clone()
+        }))
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        leaf_hashes
+            .iter()
+            .map(|leaf_hash| self.prove_hash(leaf_hash))
+            .collect()
+    }
+}
+
+/// Returns memory (in bytes) used by a remembered proof
+fn proof_size(proof: &Proof) -> usize {
+    size_of::<Proof>() + proof.heap_size()
+}
+
+/// Inserts an already hashed leaf in forest with given root hashes and updates given proofs. If
+/// `remember` is `true`, proof of the new leaf is added to proofs.
+pub(crate) fn insert_leaf<H: Hasher>(
+    hasher: &H,
+    roots: &mut Vec<Option<Hash>>,
+    proofs: &mut Vec<Proof>,
+    leaf_hash: Hash,
+    remember: bool,
+) {
+    let mut anchors: Vec<Anchor> = proofs
+        .iter()
+        .map(|proof| Anchor::Root(proof.path.height()))
+        .collect();
+
+    if remember {
+        proofs.push(Proof::for_root(leaf_hash));
+        anchors.push(Anchor::New);
+    }
+
+    let mut new_hash = leaf_hash;
+
+    for (height, root) in roots.iter_mut().enumerate() {
+        match root.take() {
+            Some(root_hash) => {
+                let left = (Anchor::Root(height), root_hash);
+                let right = (Anchor::New, new_hash);
+                merge_anchors(proofs, &mut anchors, left, right);
+                count(Counter::Merge, 1);
+
+                new_hash = hasher.hash_intermediate(&root_hash, &new_hash);
+            }
+            None => {
+                *root = Some(new_hash);
+                return;
+            }
+        }
+    }
+
+    roots.push(Some(new_hash));
+}
+
+/// Deletes leaf corresponding to given proof from forest with given root hashes and updates given
+/// proofs (proof of deleted leaf is removed). This function assumes that the proof is valid.
+pub(crate) fn delete_leaf<H: Hasher>(
+    hasher: &H,
+    roots: &mut [Option<Hash>],
+    proofs: &mut Vec<Proof>,
+    proof: &Proof,
+) {
+    let height = proof.path.height();
+
+    // Remembered leaves in the same tree as deleted leaf end up in one of the sibling subtrees
+    // (all the other merkle trees stay as they are until they are merged)
+    let mut remembered = Vec::with_capacity(proofs.len());
+    let mut anchors = Vec::with_capacity(proofs.len());
+
+    for mut remembered_proof in mem::take(proofs) {
+        let remembered_height = remembered_proof.path.height();
+
+        if remembered_height != height {
+            anchors.push(Anchor::Root(remembered_height));
+        } else if let Some(level) = divergence(&remembered_proof.path, &proof.path) {
+            remembered_proof.truncate(level);
+            anchors.push(Anchor::Sibling(level));
+        } else {
+            // Deleted leaf itself
+            continue;
+        }
+
+        remembered.push(remembered_proof);
+    }
+
+    *proofs = remembered;
+
+    let mut new_hash = None;
+
+    for (level, (root, sibling_hash)) in roots
+        .iter_mut()
+        .take(height)
+        .zip(proof.sibling_hashes.iter())
+        .enumerate()
+    {
+        match (new_hash, *root) {
+            (Some(current_hash), _) => {
+                let left = (Anchor::Sibling(level), *sibling_hash);
+                let right = (Anchor::New, current_hash);
+                merge_anchors(proofs, &mut anchors, left, right);
+                count(Counter::Merge, 1);
+
+                new_hash = Some(hasher.hash_intermediate(sibling_hash, &current_hash));
+            }
+            (None, None) => {
+                // Sibling subtree becomes a separate merkle tree
+                *root = Some(*sibling_hash);
+            }
+            (None, Some(root_hash)) => {
+                let left = (Anchor::Sibling(level), *sibling_hash);
+                let right = (Anchor::Root(level), root_hash);
+                merge_anchors(proofs, &mut anchors, left, right);
+                count(Counter::Merge, 1);
+
+                new_hash = Some(hasher.hash_intermediate(sibling_hash, &root_hash));
+                *root = None;
+            }
+        }
+    }
+
+    roots[height] = new_hash;
+}
+
+/// Updates remembered proofs when two subtrees (given as their anchor and root hash) are merged
+/// into a new subtree
+fn merge_anchors(
+    proofs: &mut [Proof],
+    anchors: &mut [Anchor],
+    left: (Anchor, Hash),
+    right: (Anchor, Hash),
+) {
+    for (proof, anchor) in proofs.iter_mut().zip(anchors.iter_mut()) {
+        if *anchor == left.0 {
+            proof.push(Direction::Right, right.1);
+            *anchor = Anchor::New;
+        } else if *anchor == right.0 {
+            proof.push(Direction::Left, left.1);
+            *anchor = Anchor::New;
+        }
+    }
+}
+
+/// Returns the highest level at which two paths of the same height diverge (i.e., height of
+/// sibling subtree of second path which contains leaf of first path), or `None` if both paths are
+/// same
+fn divergence(first: &Path, second: &Path) -> Option<usize> {
+    let first = first.directions();
+    let second = second.directions();
+
+    first
+        .zip(second)
+        .enumerate()
+        .filter(|(_, (first, second))| first != second)
+        .map(|(level, _)| level)
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LruPolicy, MemoryForest};
+
+    /// Checks that partial forest has same roots as forest and that proofs of all the remembered
+    /// leaves are same as the ones generated by forest
+    fn check_proofs<P: CachePolicy>(forest: &MemoryForest, partial: &PartialForest<Blake3, P>) {
+        assert_eq!(forest.roots(), partial.root_hashes());
+
+        for leaf_hash in partial.remembered() {
+            let expected = forest.prove_hash(leaf_hash).unwrap();
+            assert!(expected.is_some());
+            assert_eq!(expected, partial.prove_hash(leaf_hash).unwrap());
+        }
+    }
+
+    #[test]
+    fn check_partial_forest() {
+        let mut forest = MemoryForest::new();
+        let mut partial = PartialForest::new();
+
+        for value in 0..40u8 {
+            forest.insert([value; 32]);
+
+            if value % 3 == 0 {
+                partial.insert_and_remember([value; 32]);
+            } else {
+                partial.insert_with_memory([value; 32], false);
+            }
+
+            check_proofs(&forest, &partial);
+        }
+
+        assert_eq!(14, partial.remembered().count());
+
+        // Delete both remembered and other leaves from trees of all the heights
+        for value in [7u8, 0, 39, 20, 33, 1, 25, 12, 38, 5, 18, 30].iter() {
+            let proof = forest.prove([*value; 32]).unwrap().unwrap();
+
+            assert!(forest.delete(&proof));
+            assert!(partial.delete(&proof));
+            assert!(!partial.delete(&proof));
+
+            check_proofs(&forest, &partial);
+
+            forest.insert([*value, 1]);
+            partial.insert([*value, 1]);
+
+            check_proofs(&forest, &partial);
+        }
+
+        assert_eq!(8, partial.remembered().count());
+        assert!(partial.prove([0; 32]).unwrap().is_none());
+        assert!(partial.prove([2; 32]).unwrap().is_none());
+
+        let proof = forest.prove([2; 32]).unwrap().unwrap();
+        assert!(partial.remember(&proof));
+        assert!(partial.forget(&Blake3.hash_leaf(&[3; 32])));
+        assert!(!partial.forget(&Blake3.hash_leaf(&[3; 32])));
+
+        // Clairvoyant caching of leaves expected to be deleted soon
+        assert!(!partial.insert_with_ttl([50; 32], 1));
+        forest.insert([50; 32]);
+
+        partial.set_ttl_horizon(Some(6));
+
+        for (value, ttl) in [(51u8, 3u64), (52, 100), (53, 6)].iter() {
+            assert_eq!(*ttl <= 6, partial.insert_with_ttl([*value; 32], *ttl));
+            forest.insert([*value; 32]);
+        }
+
+        check_proofs(&forest, &partial);
+        assert!(partial.prove([51; 32]).unwrap().is_some());
+        assert!(partial.prove([52; 32]).unwrap().is_none());
+        assert!(partial.prove([53; 32]).unwrap().is_some());
+
+        let proof = forest.prove([4; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(partial.delete(&proof));
+
+        check_proofs(&forest, &partial);
+        assert_eq!(forest.num_leaves(), partial.num_leaves());
+    }
+
+    #[test]
+    fn check_partial_forest_memory_limit() {
+        let mut forest = MemoryForest::new();
+        let mut partial = PartialForest::with_policy(LruPolicy::new());
+
+        for value in 0..8u8 {
+            forest.insert([value; 32]);
+            partial.insert([value; 32]);
+        }
+
+        // Tree of height 3 with all the leaves remembered
+        assert_eq!(8, partial.remembered().count());
+        let proof_size = partial.cache_size() / 8;
+
+        assert!(partial.touch(&Blake3.hash_leaf(&[0; 32])));
+        let forgotten = partial.set_memory_limit(Some(5 * proof_size));
+
+        assert_eq!(3, forgotten.len());
+        assert!(partial.cache_size() <= 5 * proof_size);
+        assert!(partial.prove([0; 32]).unwrap().is_some());
+        assert!(partial.prove([1; 32]).unwrap().is_none());
+        check_proofs(&forest, &partial);
+
+        // Newly inserted leaves are remembered by forgetting least recently used ones
+        for value in 8..16u8 {
+            forest.insert([value; 32]);
+            partial.insert([value; 32]);
+
+            assert!(partial.cache_size() <= 5 * proof_size);
+            check_proofs(&forest, &partial);
+        }
+
+        assert_eq!(5, partial.remembered().count());
+        assert_eq!(Some(5 * proof_size), partial.memory_limit());
+
+        assert!(partial.set_memory_limit(None).is_empty());
+        partial.insert([16; 32]);
+        assert_eq!(6, partial.remembered().count());
+    }
+}
@@ -1,3 +1,9 @@
+#[cfg(any(feature = "serde-1", feature = "schema"))]
+use alloc::vec::Vec;
+#[cfg(feature = "serde-1")]
+use core::convert::TryFrom;
+#[cfg(feature = "zeroize")]
+use core::sync::atomic::{compiler_fence, Ordering};
 use core::{
     convert::TryInto,
     iter::{DoubleEndedIterator, Iterator},
@@ -5,13 +11,47 @@ use core::{
 };
 
 use bit_vec::{BitVec, Iter};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "serde-1")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+#[cfg(feature = "serde-1")]
+use crate::{Proof, UtreexoError};
 
 /// Represents path in a merkle proof (direction of path is from leaf to root)
+///
+/// With `serde-1` feature, deserialized paths are validated before use, i.e., a path whose number
+/// of bits exceeds its storage or [`Proof::MAX_HEIGHT`](crate::Proof::MAX_HEIGHT) is rejected
+/// while decoding.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
-pub struct Path(pub(crate) BitVec);
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(feature = "serde-1", serde(try_from = "RawPath"))]
+pub struct Path(#[cfg_attr(feature = "schema", schemars(with = "RawBitVec"))] pub(crate) BitVec);
+
+/// Unvalidated path used for deserialization
+#[cfg(feature = "serde-1")]
+#[derive(Deserialize)]
+#[serde(rename = "Path")]
+struct RawPath(RawBitVec);
+
+/// Serialized representation of `BitVec` (used for validating deserialized paths and generating
+/// JSON schema of [`Path`])
+#[cfg(any(feature = "serde-1", feature = "schema"))]
+#[cfg_attr(feature = "serde-1", derive(Deserialize))]
+#[cfg_attr(feature = "serde-1", serde(rename = "BitVec"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(feature = "schema", schemars(rename = "BitVec"))]
+#[allow(dead_code)]
+struct RawBitVec {
+    /// Bits packed in blocks of 32 bits (least significant bit first)
+    storage: Vec<u32>,
+    /// Number of bits
+    nbits: usize,
+}
 
 impl Path {
     /// Returns height of path
@@ -53,6 +93,46 @@ impl Path {
 
         Path(path)
     }
+
+    /// Returns index of the leaf at the start of path in its merkle tree (i.e., the number used
+    /// for creating path with [`Path::for_height_and_num`])
+    pub(crate) fn leaf_index(&self) -> u64 {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, bit)| *bit)
+            .fold(0, |index, (level, _)| index | (1 << level))
+    }
+}
+
+#[cfg(feature = "serde-1")]
+impl TryFrom<RawPath> for Path {
+    type Error = UtreexoError;
+
+    fn try_from(RawPath(raw): RawPath) -> Result<Self, Self::Error> {
+        if raw.nbits > raw.storage.len().saturating_mul(32) {
+            return Err(UtreexoError::InvalidEncoding(
+                "Path is longer than its storage",
+            ));
+        }
+
+        Proof::check_height(raw.nbits, Proof::MAX_HEIGHT)?;
+
+        Ok(Path(BitVec::from_fn(raw.nbits, |i| {
+            raw.storage[i / 32] & (1 << (i % 32)) != 0
+        })))
+    }
+}
+
+/// Bits of path are cleared using regular writes (followed by a compiler fence) because storage of
+/// bit vector cannot be accessed mutably without `unsafe` code
+#[cfg(feature = "zeroize")]
+impl Zeroize for Path {
+    fn zeroize(&mut self) {
+        self.0.clear();
+        self.0.truncate(0);
+        compiler_fence(Ordering::SeqCst);
+    }
 }
 
 /// Represents direction to take in a merkle path
@@ -1,13 +1,38 @@
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "serde-1")]
+use core::convert::TryFrom;
+use core::{
+    mem::{size_of, size_of_val},
+    slice,
+};
 
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "serde-1")]
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use bit_vec::BitVec;
 
-use crate::{hash_intermediate, Direction, Hash, Path};
+use crate::{
+    encoding::Reader,
+    metrics::{count, Counter},
+    partial, Direction, Hash, Hasher, Path, UpdateData, UtreexoError,
+};
 
 /// Inclusion proof of a value in a merkle forest
+///
+/// With `serde-1` feature, deserialized proofs are validated in the same way as
+/// [`Proof::from_parts`], so, a proof with mismatched path and sibling hashes is rejected while
+/// decoding (use `Proof::deserialize_with_max_height` to enforce a lower maximum height).
+///
+/// With `zeroize` feature, leaf hash, sibling hashes and path of a proof are wiped when it is
+/// dropped (or explicitly using `Zeroize`).
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(feature = "serde-1", serde(try_from = "RawProof"))]
 pub struct Proof {
     /// Path is from leaf to root node
     pub(crate) path: Path,
@@ -17,9 +42,265 @@ pub struct Proof {
     pub(crate) sibling_hashes: Vec<Hash>,
 }
 
+/// Unvalidated proof used for deserialization
+#[cfg(feature = "serde-1")]
+#[derive(Deserialize)]
+#[serde(rename = "Proof")]
+struct RawProof {
+    path: Path,
+    leaf_hash: Hash,
+    sibling_hashes: Vec<Hash>,
+}
+
+#[cfg(feature = "serde-1")]
+impl TryFrom<RawProof> for Proof {
+    type Error = UtreexoError;
+
+    fn try_from(raw: RawProof) -> Result<Self, Self::Error> {
+        Proof::from_parts(raw.leaf_hash, raw.path.directions(), raw.sibling_hashes)
+    }
+}
+
 impl Proof {
+    /// Maximum height of a proof (height of the tallest possible merkle tree)
+    pub const MAX_HEIGHT: usize = (usize::BITS - 1) as usize;
+
+    /// Creates a proof from its raw parts: leaf hash, path (directions of sibling nodes from leaf
+    /// to root, i.e., `Direction::Left` means that the sibling is on the left) and sibling hashes
+    /// (from leaf to root).
+    ///
+    /// Returns an error if the number of sibling hashes does not match the height of path or if
+    /// height of path is greater than [`Proof::MAX_HEIGHT`].
+    pub fn from_parts(
+        leaf_hash: Hash,
+        path: impl IntoIterator<Item = Direction>,
+        sibling_hashes: Vec<Hash>,
+    ) -> Result<Self, UtreexoError> {
+        let path = Path(path.into_iter().map(bool::from).collect::<BitVec>());
+
+        Self::check_height(path.height(), Self::MAX_HEIGHT)?;
+
+        if path.height() != sibling_hashes.len() {
+            return Err(UtreexoError::ProofLengthMismatch {
+                height: path.height(),
+                num_sibling_hashes: sibling_hashes.len(),
+            });
+        }
+
+        Ok(Self {
+            path,
+            leaf_hash,
+            sibling_hashes,
+        })
+    }
+
+    /// Returns path of proof (from leaf to root)
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns leaf hash of proof
+    pub fn leaf_hash(&self) -> &Hash {
+        &self.leaf_hash
+    }
+
+    /// Returns sibling hashes of proof (from leaf to root)
+    pub fn sibling_hashes(&self) -> &[Hash] {
+        &self.sibling_hashes
+    }
+
+    /// Returns canonical binary encoding of proof (see [`Proof::from_bytes`] for layout)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let height = self.path.height();
+        let mut bytes = Vec::with_capacity(1 + height.div_ceil(8) + (height + 1) * Hash::LEN);
+
+        // Height of a proof always fits in a byte because it cannot exceed `Proof::MAX_HEIGHT`
+        bytes.push(height as u8);
+        bytes.extend_from_slice(&self.path.0.to_bytes());
+        bytes.extend_from_slice(self.leaf_hash.as_bytes());
+
+        for sibling_hash in self.sibling_hashes.iter() {
+            bytes.extend_from_slice(sibling_hash.as_bytes());
+        }
+
+        bytes
+    }
+
+    /// Parses proof from its canonical binary encoding
+    ///
+    /// Layout: `height (1 byte) || path (ceil(height / 8) bytes) || leaf hash (32 bytes) ||
+    /// sibling hashes (height * 32 bytes)`. Path contains one bit per level, from leaf to root,
+    /// packed starting from the most significant bit of first byte, where `1` means that the
+    /// sibling is on the left. Unused bits of last byte must be `0`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        Self::from_bytes_with_max_height(bytes, Self::MAX_HEIGHT)
+    }
+
+    /// Parses proof from its canonical binary encoding (see [`Proof::from_bytes`] for layout) and
+    /// rejects proofs higher than given maximum height (capped at [`Proof::MAX_HEIGHT`]) before
+    /// reading any hashes. This is useful for bounding the work done on untrusted input, e.g., to
+    /// the height of tallest tree in an accumulator.
+    pub fn from_bytes_with_max_height(
+        bytes: &[u8],
+        max_height: usize,
+    ) -> Result<Self, UtreexoError> {
+        let max_height = max_height.min(Self::MAX_HEIGHT);
+        let mut reader = Reader::new(bytes);
+
+        let height = usize::from(reader.read_u8()?);
+
+        Self::check_height(height, max_height)?;
+
+        let mut path = BitVec::from_bytes(reader.read_bytes(height.div_ceil(8))?);
+
+        if path.iter().skip(height).any(|bit| bit) {
+            return Err(UtreexoError::InvalidEncoding(
+                "Unused bits of path should be zero",
+            ));
+        }
+
+        path.truncate(height);
+
+        let leaf_hash = reader.read_hash()?;
+        let sibling_hashes = reader.read_hashes(height)?;
+
+        reader.finish()?;
+
+        Ok(Self {
+            path: Path(path),
+            leaf_hash,
+            sibling_hashes,
+        })
+    }
+
+    /// Deserializes a proof (validated in the same way as [`Proof::from_parts`]) and rejects proofs
+    /// higher than given maximum height (capped at [`Proof::MAX_HEIGHT`]), same as
+    /// [`Proof::from_bytes_with_max_height`]
+    #[cfg(feature = "serde-1")]
+    pub fn deserialize_with_max_height<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        max_height: usize,
+    ) -> Result<Self, D::Error> {
+        let proof = Self::deserialize(deserializer)?;
+        Self::check_height(proof.path.height(), max_height.min(Self::MAX_HEIGHT))
+            .map_err(D::Error::custom)?;
+        Ok(proof)
+    }
+
+    /// Returns an error if given height of proof is greater than given maximum height
+    pub(crate) fn check_height(height: usize, max_height: usize) -> Result<(), UtreexoError> {
+        if height > max_height {
+            return Err(UtreexoError::ProofTooHigh { height, max_height });
+        }
+
+        Ok(())
+    }
+
+    /// Extends the proof, which should be valid for root hashes of accumulator before given update,
+    /// so that it is valid after the update, i.e., sibling hashes along the path are rewritten for
+    /// deletions (see [`Proof::update_for_deletion`]) and, when the merkle tree containing the leaf
+    /// is merged with other trees because of new insertions, sibling hashes (and path) of new root
+    /// nodes are appended to the proof. Returns `false` (and leaves the proof unchanged) if the
+    /// proof (or any proof of deleted leaf) is not valid for root hashes before the update or if
+    /// the leaf itself is deleted.
+    pub fn extend<H: Hasher>(&mut self, hasher: &H, update: &UpdateData) -> bool {
+        self.update_with(
+            hasher,
+            &mut update.prev_roots().to_vec(),
+            update.deletions(),
+            update.additions(),
+        )
+    }
+
+    /// Updates the proof, which should be valid for given root hashes, after deletion of leaf with
+    /// given proof, i.e., when the deleted leaf is in the same merkle tree, sibling hashes along
+    /// the path (and path, if the merkle tree is split) are rewritten. Returns `false` (and leaves
+    /// the proof unchanged) if any of the proofs is not valid for given root hashes or if the
+    /// deleted leaf is the leaf of this proof.
+    pub fn update_for_deletion<H: Hasher>(
+        &mut self,
+        hasher: &H,
+        roots: &[Option<Hash>],
+        deleted: &Proof,
+    ) -> bool {
+        self.update_with(hasher, &mut roots.to_vec(), slice::from_ref(deleted), &[])
+    }
+
+    /// Creates a proof of a leaf which is the root of its merkle tree (i.e., a tree of height 0)
+    pub(crate) fn for_root(leaf_hash: Hash) -> Self {
+        Self {
+            path: Path(BitVec::new()),
+            leaf_hash,
+            sibling_hashes: Vec::new(),
+        }
+    }
+
+    /// Adds a sibling hash on top of proof, i.e., when root of merkle tree of proof becomes a child
+    /// of a new root. `direction` is the side of new sibling.
+    pub(crate) fn push(&mut self, direction: Direction, sibling_hash: Hash) {
+        self.path.0.push(direction.into());
+        self.sibling_hashes.push(sibling_hash);
+    }
+
+    /// Removes all the sibling hashes above given height, i.e., when subtree of given height
+    /// containing the leaf becomes a separate merkle tree
+    pub(crate) fn truncate(&mut self, height: usize) {
+        self.path.0.truncate(height);
+        self.sibling_hashes.truncate(height);
+    }
+
+    /// Returns heap memory (in bytes) used by sibling hashes and path of proof
+    pub(crate) fn heap_size(&self) -> usize {
+        self.sibling_hashes.capacity() * size_of::<Hash>() + size_of_val(self.path.0.storage())
+    }
+
+    /// Applies given deletions followed by given insertions to forest with given root hashes and
+    /// updates the proof accordingly (see [`Proof::extend`])
+    fn update_with<H: Hasher>(
+        &mut self,
+        hasher: &H,
+        roots: &mut Vec<Option<Hash>>,
+        deletions: &[Proof],
+        additions: &[Hash],
+    ) -> bool {
+        if !self.verify_roots(hasher, roots) {
+            return false;
+        }
+
+        let mut proofs = vec![self.clone()];
+
+        for deleted in deletions {
+            if !deleted.verify_roots(hasher, roots) {
+                return false;
+            }
+
+            partial::delete_leaf(hasher, roots, &mut proofs, deleted);
+
+            if proofs.is_empty() {
+                return false;
+            }
+        }
+
+        for leaf_hash in additions {
+            partial::insert_leaf(hasher, roots, &mut proofs, *leaf_hash, false);
+        }
+
+        *self = proofs.pop().expect("Expected proof to be present");
+        true
+    }
+
+    /// Verifies current proof with root hash of its height from given root hashes
+    pub(crate) fn verify_roots<H: Hasher>(&self, hasher: &H, roots: &[Option<Hash>]) -> bool {
+        match roots.get(self.path.height()) {
+            Some(Some(root_hash)) => self.verify(hasher, *root_hash),
+            _ => false,
+        }
+    }
+
     /// Verifies current proof with given root hash
-    pub(crate) fn verify(&self, root_hash: Hash) -> bool {
+    pub(crate) fn verify<H: Hasher>(&self, hasher: &H, root_hash: Hash) -> bool {
+        count(Counter::Verify, 1);
+
         // If height of path in proof and number of sibling hashes does not match, return false
         if self.sibling_hashes.len() != self.path.height() {
             return false;
@@ -38,10 +319,10 @@ impl Proof {
 
         for step in path {
             match sibling_hashes.next() {
-                Some(ref sibling_hash) => {
+                Some(sibling_hash) => {
                     hash = match step {
-                        Direction::Left => hash_intermediate(sibling_hash, &hash),
-                        Direction::Right => hash_intermediate(&hash, sibling_hash),
+                        Direction::Left => hasher.hash_intermediate(sibling_hash, &hash),
+                        Direction::Right => hasher.hash_intermediate(&hash, sibling_hash),
                     };
                 }
                 None => return false,
@@ -52,9 +333,29 @@ impl Proof {
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl Zeroize for Proof {
+    fn zeroize(&mut self) {
+        self.path.zeroize();
+        self.leaf_hash.zeroize();
+        self.sibling_hashes.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Proof {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for Proof {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{hash_intermediate, Blake3};
 
     #[test]
     fn check_proof_verify() {
@@ -72,7 +373,299 @@ mod tests {
             sibling_hashes,
         };
 
-        assert!(proof.verify(root_hash));
-        assert!(!proof.verify(intermediate_hash_2));
+        assert!(proof.verify(&Blake3, root_hash));
+        assert!(!proof.verify(&Blake3, intermediate_hash_2));
+    }
+
+    #[test]
+    fn check_proof_from_parts() {
+        let path = Path::for_height_and_num(3, 5);
+        let sibling_hashes = vec![[0; 32].into(), [1; 32].into(), [2; 32].into()];
+        let leaf_hash = [0; 32].into();
+
+        let proof = Proof::from_parts(leaf_hash, path.directions(), sibling_hashes.clone());
+        assert_eq!(
+            Ok(Proof {
+                path: path.clone(),
+                leaf_hash,
+                sibling_hashes: sibling_hashes.clone(),
+            }),
+            proof
+        );
+
+        let proof = Proof::from_parts(leaf_hash, path.directions(), sibling_hashes[1..].to_vec());
+        assert_eq!(
+            Err(UtreexoError::ProofLengthMismatch {
+                height: 3,
+                num_sibling_hashes: 2
+            }),
+            proof
+        );
+
+        let proof = Proof::from_parts(
+            leaf_hash,
+            vec![Direction::Left; Proof::MAX_HEIGHT + 1],
+            vec![leaf_hash; Proof::MAX_HEIGHT + 1],
+        );
+        assert_eq!(
+            Err(UtreexoError::ProofTooHigh {
+                height: Proof::MAX_HEIGHT + 1,
+                max_height: Proof::MAX_HEIGHT
+            }),
+            proof
+        );
+    }
+
+    #[test]
+    fn check_proof_bytes() {
+        let path = Path::for_height_and_num(3, 5);
+        let sibling_hashes = vec![[0; 32].into(), [1; 32].into(), [2; 32].into()];
+        let leaf_hash = [3; 32].into();
+
+        let proof = Proof::from_parts(leaf_hash, path.directions(), sibling_hashes).unwrap();
+        let bytes = proof.to_bytes();
+
+        assert_eq!(1 + 1 + 4 * 32, bytes.len());
+        assert_eq!(&[3, 0b1010_0000], &bytes[..2]);
+        assert_eq!(Ok(proof), Proof::from_bytes(&bytes));
+
+        let mut invalid = bytes.clone();
+        invalid[1] |= 1;
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding(
+                "Unused bits of path should be zero"
+            )),
+            Proof::from_bytes(&invalid)
+        );
+
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unexpected end of input")),
+            Proof::from_bytes(&bytes[..bytes.len() - 1])
+        );
+
+        let mut invalid = bytes;
+        invalid.push(0);
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding(
+                "Trailing bytes after encoded value"
+            )),
+            Proof::from_bytes(&invalid)
+        );
+
+        assert_eq!(
+            Err(UtreexoError::ProofTooHigh {
+                height: 64,
+                max_height: Proof::MAX_HEIGHT
+            }),
+            Proof::from_bytes(&[64])
+        );
+        assert_eq!(
+            Err(UtreexoError::ProofTooHigh {
+                height: 3,
+                max_height: 2
+            }),
+            Proof::from_bytes_with_max_height(&[3], 2)
+        );
+    }
+
+    #[test]
+    fn check_proof_extend() {
+        use crate::{hash_leaf, MemoryForest, Prover, Utreexo};
+
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut proofs: Vec<Proof> = (0..5u8)
+            .map(|value| forest.prove([value; 32]).unwrap().unwrap())
+            .collect();
+
+        let prev_roots = forest.roots();
+        let additions: Vec<Hash> = (5..11u8).map(|value| hash_leaf([value; 32])).collect();
+
+        for value in 5..11u8 {
+            forest.insert([value; 32]);
+        }
+
+        let update = UpdateData::new(prev_roots, Vec::new(), additions);
+
+        for (value, proof) in (0..5u8).zip(proofs.iter_mut()) {
+            assert!(proof.extend(&Blake3, &update));
+            assert_eq!(forest.prove([value; 32]).unwrap().unwrap(), *proof);
+        }
+
+        // Proof is not valid for roots before the update anymore
+        let mut proof = proofs[0].clone();
+        assert!(!proof.extend(&Blake3, &update));
+        assert_eq!(proofs[0], proof);
+    }
+
+    #[test]
+    fn check_proof_update_for_deletion() {
+        use crate::{hash_leaf, MemoryForest, Prover, Utreexo};
+
+        let mut forest = MemoryForest::new();
+
+        for value in 0..13u8 {
+            forest.insert([value; 32]);
+        }
+
+        let mut proofs: Vec<Proof> = (0..13u8)
+            .map(|value| forest.prove([value; 32]).unwrap().unwrap())
+            .collect();
+
+        // Deletions from the tallest tree and from the smallest tree
+        for deleted_value in [5u8, 12].iter() {
+            let roots = forest.roots();
+            let deleted = proofs[usize::from(*deleted_value)].clone();
+            assert!(forest.delete(&deleted));
+
+            for (value, proof) in (0..13u8).zip(proofs.iter_mut()) {
+                if !forest.contains([value; 32]) {
+                    let unchanged = proof.clone();
+                    assert!(!proof.update_for_deletion(&Blake3, &roots, &deleted));
+                    assert_eq!(unchanged, *proof);
+                } else {
+                    assert!(proof.update_for_deletion(&Blake3, &roots, &deleted));
+                    assert_eq!(forest.prove([value; 32]).unwrap().unwrap(), *proof);
+                }
+            }
+        }
+
+        // Update with both deletions and additions
+        let prev_roots = forest.roots();
+        let deletions = vec![proofs[0].clone(), proofs[9].clone()];
+        let additions = vec![hash_leaf([13; 32]), hash_leaf([14; 32])];
+
+        assert!(forest.delete(&deletions[0]));
+        assert!(forest.delete(&forest.prove([9; 32]).unwrap().unwrap()));
+        forest.insert([13; 32]);
+        forest.insert([14; 32]);
+
+        // Proof of second deletion is not valid after the first deletion
+        let update = UpdateData::new(prev_roots.clone(), deletions, additions.clone());
+        let mut proof = proofs[3].clone();
+        assert!(!proof.extend(&Blake3, &update));
+
+        let mut deleted = proofs[9].clone();
+        assert!(deleted.update_for_deletion(&Blake3, &prev_roots, &proofs[0]));
+
+        let update = UpdateData::new(prev_roots, vec![proofs[0].clone(), deleted], additions);
+
+        for value in [1u8, 3, 8, 11].iter() {
+            let mut proof = proofs[usize::from(*value)].clone();
+            assert!(proof.extend(&Blake3, &update));
+            assert_eq!(forest.prove([*value; 32]).unwrap().unwrap(), proof);
+        }
+    }
+
+    #[cfg(feature = "serde-1")]
+    #[test]
+    fn check_proof_deserialize_validation() {
+        use serde_test::{assert_de_tokens_error, assert_tokens, Compact, Configure, Token};
+
+        fn tokens(nbits: u64) -> Vec<Token> {
+            tokens_with_storage(&[1], nbits)
+        }
+
+        fn tokens_with_storage(storage: &[u32], nbits: u64) -> Vec<Token> {
+            let mut tokens = vec![
+                Token::Struct {
+                    name: "Proof",
+                    len: 3,
+                },
+                Token::Str("path"),
+                Token::NewtypeStruct { name: "Path" },
+                Token::Struct {
+                    name: "BitVec",
+                    len: 2,
+                },
+                Token::Str("storage"),
+                Token::Seq {
+                    len: Some(storage.len()),
+                },
+            ];
+            tokens.extend(storage.iter().map(|block| Token::U32(*block)));
+            tokens.extend_from_slice(&[
+                Token::SeqEnd,
+                Token::Str("nbits"),
+                Token::U64(nbits),
+                Token::StructEnd,
+                Token::Str("leaf_hash"),
+                Token::Bytes(&[0; 32]),
+                Token::Str("sibling_hashes"),
+                Token::Seq { len: Some(1) },
+                Token::Bytes(&[1; 32]),
+                Token::SeqEnd,
+                Token::StructEnd,
+            ]);
+            tokens
+        }
+
+        let proof =
+            Proof::from_parts([0; 32].into(), vec![Direction::Left], vec![[1; 32].into()]).unwrap();
+
+        assert_tokens(&proof.clone().compact(), &tokens(1));
+        assert_de_tokens_error::<Compact<Proof>>(
+            &tokens(2),
+            &UtreexoError::ProofLengthMismatch {
+                height: 2,
+                num_sibling_hashes: 1,
+            }
+            .to_string(),
+        );
+
+        // Invalid paths are rejected as soon as they are decoded (i.e., after first 10 tokens and
+        // storage of path). Number of bits exceeding storage used to panic while iterating over
+        // path.
+        assert_de_tokens_error::<Compact<Proof>>(
+            &tokens_with_storage(&[], 3)[..10],
+            &UtreexoError::InvalidEncoding("Path is longer than its storage").to_string(),
+        );
+        assert_de_tokens_error::<Compact<Proof>>(
+            &tokens_with_storage(&[0, 0], 64)[..12],
+            &UtreexoError::ProofTooHigh {
+                height: 64,
+                max_height: Proof::MAX_HEIGHT,
+            }
+            .to_string(),
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn check_proof_deserialize_invalid_path_json() {
+        let json = format!(
+            r#"{{"path":{{"storage":[],"nbits":3}},"leaf_hash":"{}","sibling_hashes":[]}}"#,
+            Hash::from([0; 32])
+        );
+
+        let error = serde_json::from_str::<Proof>(&json).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid encoding: Path is longer than its storage"));
+
+        let proof =
+            Proof::from_parts([0; 32].into(), vec![Direction::Left], vec![[1; 32].into()]).unwrap();
+        let json = serde_json::to_string(&proof).unwrap();
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        assert_eq!(
+            proof,
+            Proof::deserialize_with_max_height(&mut deserializer, 1).unwrap()
+        );
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let error = Proof::deserialize_with_max_height(&mut deserializer, 0).unwrap_err();
+        assert_eq!(
+            UtreexoError::ProofTooHigh {
+                height: 1,
+                max_height: 0
+            }
+            .to_string(),
+            error.to_string()
+        );
     }
 }
@@ -1,7 +1,26 @@
-use crate::Proof;
+use alloc::vec::Vec;
+
+use crate::{Hash, Proof, UtreexoError};
 
 /// Trait for generating inclusion proofs of value in merkle forest
 pub trait Prover {
-    /// Returns proof of a leaf value in merkle forest
-    fn prove<T: AsRef<[u8]>>(&self, leaf_value: T) -> Option<Proof>;
+    /// Returns proof of a leaf value in merkle forest (same as `prove_value()`)
+    fn prove<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        self.prove_value(leaf_value)
+    }
+
+    /// Returns proof of a leaf value in merkle forest (or `None` if the value is not present).
+    /// Leaf hash of value is calculated using hasher of merkle forest. Returns an error if internal
+    /// state of merkle forest is found to be corrupted.
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError>;
+
+    /// Returns proof of an already hashed leaf in merkle forest (or `None` if the leaf is not
+    /// present). Returns an error if internal state of merkle forest is found to be corrupted.
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError>;
+
+    /// Returns proofs of given leaf hashes (in the same order), where proof of a leaf hash is
+    /// `None` if it is not present. This is faster than calling `prove()` for every leaf when
+    /// proofs for many leaves are needed at once. Returns an error if internal state of merkle
+    /// forest is found to be corrupted.
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError>;
 }
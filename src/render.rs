@@ -0,0 +1,179 @@
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::Hash;
+
+/// Writes ASCII rendering of a perfect merkle tree (given as its nodes in post-order), one node per
+/// line. Nodes are labelled with hex encoding of first `hash_len` bytes of their hashes (or `*` if
+/// `hash_len` is zero) and leaves are also labelled with their positions (starting with
+/// `first_leaf`).
+pub(crate) fn render_tree(out: &mut String, nodes: &[Hash], hash_len: usize, first_leaf: u64) {
+    let mut next_leaf = first_leaf;
+    render_subtree(out, nodes, hash_len, "", "", &mut next_leaf);
+}
+
+/// Writes ASCII rendering of a perfect subtree, where `head` is written before root node and
+/// `indent` is written before every other line
+fn render_subtree(
+    out: &mut String,
+    nodes: &[Hash],
+    hash_len: usize,
+    head: &str,
+    indent: &str,
+    next_leaf: &mut u64,
+) {
+    let root_hash = nodes.last().expect("Expected atleast one node in subtree");
+
+    out.push_str(head);
+    write_label(out, root_hash, hash_len);
+
+    if nodes.len() == 1 {
+        let _ = writeln!(out, " (leaf {})", next_leaf);
+        *next_leaf += 1;
+        return;
+    }
+
+    out.push('\n');
+
+    let half = (nodes.len() - 1) / 2;
+
+    render_subtree(
+        out,
+        &nodes[..half],
+        hash_len,
+        &[indent, "+-- "].concat(),
+        &[indent, "|   "].concat(),
+        next_leaf,
+    );
+    render_subtree(
+        out,
+        &nodes[half..(2 * half)],
+        hash_len,
+        &[indent, "`-- "].concat(),
+        &[indent, "    "].concat(),
+        next_leaf,
+    );
+}
+
+/// Writes hex encoding of first `hash_len` bytes of hash (or `*` if `hash_len` is zero)
+fn write_label(out: &mut String, hash: &Hash, hash_len: usize) {
+    if hash_len == 0 {
+        out.push('*');
+    }
+
+    for byte in hash.as_bytes().iter().take(hash_len) {
+        let _ = write!(out, "{:02x}", byte);
+    }
+}
+
+/// Writes DOT statements for nodes and edges of a perfect merkle tree (given as its nodes in
+/// post-order). Node ids are `id_prefix` followed by post-order index of node. If position of a
+/// leaf is given, only nodes on the path from that leaf to root (which are highlighted) and their
+/// siblings are written, i.e., the nodes of its inclusion proof.
+pub(crate) fn write_dot_tree(
+    out: &mut String,
+    indent: &str,
+    id_prefix: &str,
+    nodes: &[Hash],
+    leaf: Option<usize>,
+) {
+    write_dot_node(out, indent, id_prefix, nodes, 0, leaf.is_some());
+    write_dot_subtree(out, indent, id_prefix, nodes, 0, 0, leaf);
+}
+
+/// Writes DOT statements for children of root of a perfect subtree (starting at given offset in
+/// nodes of full tree and containing leaves starting at `first_leaf`) and edges from root to
+/// children, and does the same for subtrees of children (only for the child containing given leaf,
+/// if any)
+fn write_dot_subtree(
+    out: &mut String,
+    indent: &str,
+    id_prefix: &str,
+    nodes: &[Hash],
+    offset: usize,
+    first_leaf: usize,
+    leaf: Option<usize>,
+) {
+    if nodes.len() == 1 {
+        return;
+    }
+
+    let half = (nodes.len() - 1) / 2;
+    let root = offset + nodes.len() - 1;
+    let num_leaves = half.div_ceil(2);
+
+    let children = [
+        (&nodes[..half], offset, first_leaf),
+        (
+            &nodes[half..(2 * half)],
+            offset + half,
+            first_leaf + num_leaves,
+        ),
+    ];
+
+    for (child, child_offset, child_first_leaf) in children.iter() {
+        let on_path =
+            leaf.map(|leaf| *child_first_leaf <= leaf && leaf < *child_first_leaf + num_leaves);
+
+        write_dot_node(
+            out,
+            indent,
+            id_prefix,
+            child,
+            *child_offset,
+            on_path.unwrap_or(false),
+        );
+        let _ = writeln!(
+            out,
+            "{}{}{} -> {}{};",
+            indent,
+            id_prefix,
+            root,
+            id_prefix,
+            child_offset + child.len() - 1
+        );
+
+        if on_path != Some(false) {
+            write_dot_subtree(
+                out,
+                indent,
+                id_prefix,
+                child,
+                *child_offset,
+                *child_first_leaf,
+                leaf,
+            );
+        }
+    }
+}
+
+/// Writes DOT statement for root of a perfect subtree (starting at given offset in nodes of full
+/// tree), labelled with first 4 bytes of its hash
+fn write_dot_node(
+    out: &mut String,
+    indent: &str,
+    id_prefix: &str,
+    nodes: &[Hash],
+    offset: usize,
+    highlighted: bool,
+) {
+    let _ = write!(
+        out,
+        "{}{}{} [label=\"",
+        indent,
+        id_prefix,
+        offset + nodes.len() - 1
+    );
+    write_label(out, &nodes[nodes.len() - 1], 4);
+    out.push('"');
+
+    if nodes.len() == 1 {
+        out.push_str(", shape=box");
+    }
+
+    if highlighted {
+        out.push_str(", style=filled");
+    }
+
+    out.push_str("];\n");
+}
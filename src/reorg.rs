@@ -0,0 +1,139 @@
+use alloc::collections::VecDeque;
+
+use crate::{ApplyBlock, BlockChanges, Checkpoint, Proof, UtreexoError};
+
+/// Wrapper over an accumulator (or forest) which keeps undo data of the last `capacity` applied
+/// blocks, so that chain reorganizations can be handled by disconnecting blocks from the tip and
+/// connecting blocks of the new chain
+///
+/// Undo data of a forest shares trees with the forest (see [`Checkpoint`]), so, keeping undo data
+/// of many blocks is cheap.
+#[derive(Debug, Clone)]
+pub struct ReorgManager<A> {
+    /// Accumulator (or forest) at the tip
+    utreexo: A,
+    /// Undo data of applied blocks (oldest first)
+    undo: VecDeque<Checkpoint>,
+    /// Maximum number of blocks for which undo data is kept
+    capacity: usize,
+}
+
+impl<A: ApplyBlock> ReorgManager<A> {
+    /// Creates a new reorg manager which keeps undo data of last `capacity` blocks applied to given
+    /// accumulator (or forest)
+    pub fn new(utreexo: A, capacity: usize) -> Self {
+        Self {
+            utreexo,
+            undo: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns the accumulator (or forest) at the tip
+    pub fn utreexo(&self) -> &A {
+        &self.utreexo
+    }
+
+    /// Consumes the reorg manager and returns the accumulator (or forest) at the tip
+    pub fn into_inner(self) -> A {
+        self.utreexo
+    }
+
+    /// Returns the maximum number of blocks for which undo data is kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of blocks which can currently be disconnected from the tip
+    pub fn depth(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Connects a block (either a new block or a block of the new chain after a reorg) on top of
+    /// the tip and records its undo data, pruning undo data of the oldest block if more than
+    /// `capacity` blocks are recorded (see [`ApplyBlock::apply_block`]).
+    pub fn reconnect<T: AsRef<[u8]>>(
+        &mut self,
+        adds: &[T],
+        dels: &[Proof],
+    ) -> Result<BlockChanges, UtreexoError> {
+        let changes = self.utreexo.apply_block(adds, dels)?;
+
+        if self.capacity > 0 {
+            if self.undo.len() == self.capacity {
+                self.undo.pop_front();
+            }
+
+            self.undo.push_back(changes.undo.clone());
+        }
+
+        Ok(changes)
+    }
+
+    /// Disconnects the block at the tip by restoring its undo data. Returns `false` (and leaves the
+    /// tip unchanged) if there is no undo data left, i.e., the block is older than the last
+    /// `capacity` blocks.
+    pub fn disconnect_tip(&mut self) -> Result<bool, UtreexoError> {
+        match self.undo.pop_back() {
+            Some(undo) => {
+                self.utreexo.undo_block(undo)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryAccumulator, MemoryForest, Prover};
+
+    #[test]
+    fn check_reorg_manager() {
+        let mut manager = ReorgManager::new(MemoryForest::new(), 2);
+        let mut states = Vec::new();
+
+        for block in 0..4u8 {
+            states.push(manager.utreexo().clone());
+
+            let dels: Vec<Proof> = manager
+                .utreexo()
+                .prove([block.wrapping_sub(1), 0])
+                .unwrap()
+                .into_iter()
+                .collect();
+
+            manager.reconnect(&[[block, 0], [block, 1]], &dels).unwrap();
+        }
+
+        assert_eq!(2, manager.depth());
+
+        assert_eq!(Ok(true), manager.disconnect_tip());
+        assert_eq!(&states[3], manager.utreexo());
+
+        // Blocks of new chain
+        manager.reconnect(&[[9, 0]], &[]).unwrap();
+        assert_eq!(2, manager.depth());
+
+        assert_eq!(Ok(true), manager.disconnect_tip());
+        assert_eq!(Ok(true), manager.disconnect_tip());
+        assert_eq!(&states[2], manager.utreexo());
+
+        // Undo data of older blocks is pruned
+        assert_eq!(Ok(false), manager.disconnect_tip());
+        assert_eq!(&states[2], manager.utreexo());
+
+        // Failed blocks are not recorded
+        let proof = manager.utreexo().prove([0, 1]).unwrap().unwrap();
+        let mut manager = ReorgManager::new(MemoryAccumulator::new(), 0);
+
+        assert_eq!(
+            Err(UtreexoError::InvalidDeletion { index: 0 }),
+            manager.reconnect(&[[0, 0]], &[proof])
+        );
+        manager.reconnect(&[[0, 0]], &[]).unwrap();
+        assert_eq!(0, manager.depth());
+        assert_eq!(Ok(false), manager.disconnect_tip());
+    }
+}
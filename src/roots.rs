@@ -0,0 +1,35 @@
+use alloc::vec::Vec;
+
+use crate::{
+    ArchiveForest, CachePolicy, Hash, Hasher, MemoryAccumulator, MemoryForest, PartialForest,
+};
+
+/// Trait for accumulators (and forests) which can return root hashes of all their merkle trees
+pub trait Roots {
+    /// Returns root hashes of all the merkle trees (indexed by height)
+    fn roots(&self) -> Vec<Option<Hash>>;
+}
+
+impl<H: Hasher> Roots for MemoryAccumulator<H> {
+    fn roots(&self) -> Vec<Option<Hash>> {
+        self.root_hashes().to_vec()
+    }
+}
+
+impl<H: Hasher> Roots for MemoryForest<H> {
+    fn roots(&self) -> Vec<Option<Hash>> {
+        MemoryForest::roots(self)
+    }
+}
+
+impl<H: Hasher, P: CachePolicy> Roots for PartialForest<H, P> {
+    fn roots(&self) -> Vec<Option<Hash>> {
+        self.root_hashes().to_vec()
+    }
+}
+
+impl<H: Hasher + Clone> Roots for ArchiveForest<H> {
+    fn roots(&self) -> Vec<Option<Hash>> {
+        self.forest().roots()
+    }
+}
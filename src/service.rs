@@ -0,0 +1,195 @@
+//! HTTP proof service backed by a [`MemoryForest`] (requires `service` feature)
+//!
+//! Endpoints (proofs use their JSON representation, see [`Proof::to_json`]):
+//!
+//! - `GET /roots`: root hashes of merkle trees in forest as a JSON array of hex strings (indexed by
+//!   height, `null` if there is no tree of that height)
+//! - `GET /prove/{leaf_hash}`: proof of hex encoded leaf hash (`404` if the leaf is not present)
+//! - `POST /verify`: verifies proof in request body against current roots and returns
+//!   `{"valid": true}` or `{"valid": false}` (`400` if the proof is malformed)
+//!
+//! Forest is shared with the service through an `Arc<RwLock<_>>`, so, it can be updated (e.g., on
+//! every block) while the service is running.
+//!
+//! ```no_run
+//! use std::sync::{Arc, RwLock};
+//!
+//! use utreexo::{service, MemoryForest};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let forest = Arc::new(RwLock::new(MemoryForest::new()));
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
+//!
+//! service::serve(listener, forest).await
+//! # }
+//! ```
+use alloc::{string::String, sync::Arc, vec::Vec};
+use std::{io, sync::RwLock};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::{Blake3, Hash, Hasher, MemoryForest, Proof, Prover};
+
+/// Forest shared between the service and the rest of application
+pub type SharedState<H = Blake3> = Arc<RwLock<MemoryForest<H>>>;
+
+/// Returns router serving all the endpoints of proof service for given forest
+pub fn router<H>(forest: SharedState<H>) -> Router
+where
+    H: Hasher + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/roots", get(roots::<H>))
+        .route("/prove/{leaf_hash}", get(prove::<H>))
+        .route("/verify", post(verify::<H>))
+        .with_state(forest)
+}
+
+/// Serves proof service for given forest on given listener
+pub async fn serve<H>(listener: TcpListener, forest: SharedState<H>) -> io::Result<()>
+where
+    H: Hasher + Send + Sync + 'static,
+{
+    axum::serve(listener, router(forest)).await
+}
+
+/// Returns root hashes of merkle trees in forest
+async fn roots<H: Hasher>(State(forest): State<SharedState<H>>) -> Json<Vec<Option<String>>> {
+    let forest = forest.read().expect("Forest lock is poisoned");
+
+    Json(
+        forest
+            .roots()
+            .iter()
+            .map(|root| root.map(|root_hash| root_hash.to_string()))
+            .collect(),
+    )
+}
+
+/// Returns proof of given leaf hash
+async fn prove<H: Hasher>(
+    State(forest): State<SharedState<H>>,
+    Path(leaf_hash): Path<String>,
+) -> Response {
+    let leaf_hash: Hash = match leaf_hash.parse() {
+        Ok(leaf_hash) => leaf_hash,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    let proof = forest
+        .read()
+        .expect("Forest lock is poisoned")
+        .prove_hash(&leaf_hash);
+
+    match proof {
+        Ok(Some(proof)) => (
+            [(header::CONTENT_TYPE, "application/json")],
+            proof.to_json(),
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}
+
+/// Verifies proof in request body against current roots of forest
+async fn verify<H: Hasher>(State(forest): State<SharedState<H>>, body: String) -> Response {
+    let proof = match Proof::from_json(&body) {
+        Ok(proof) => proof,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    let forest = forest.read().expect("Forest lock is poisoned");
+    let valid = proof.verify_roots(forest.hasher(), &forest.roots());
+
+    Json(json!({ "valid": valid })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Utreexo;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// Sends a request to router and returns status and body of response
+    async fn send(forest: &SharedState, request: Request<Body>) -> (StatusCode, String) {
+        let response = router(forest.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Creates a `GET` request for given URI
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn check_proof_service() {
+        let forest: SharedState = Default::default();
+
+        for value in 0..3u8 {
+            forest.write().unwrap().insert([value; 32]);
+        }
+
+        let (status, body) = send(&forest, get("/roots")).await;
+        let root_hashes = forest.read().unwrap().roots();
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            format!(
+                "[\"{}\",\"{}\"]",
+                root_hashes[0].unwrap(),
+                root_hashes[1].unwrap()
+            ),
+            body
+        );
+
+        let leaf_hash = Blake3.hash_leaf(&[1; 32]);
+        let (status, body) = send(&forest, get(&format!("/prove/{}", leaf_hash))).await;
+        assert_eq!(StatusCode::OK, status);
+
+        let proof = Proof::from_json(&body).unwrap();
+        assert_eq!(forest.read().unwrap().prove([1; 32]), Ok(Some(proof)));
+
+        let verify = |body: &str| {
+            Request::post("/verify")
+                .body(Body::from(body.to_owned()))
+                .unwrap()
+        };
+
+        let (status, response) = send(&forest, verify(&body)).await;
+        assert_eq!(
+            (StatusCode::OK, "{\"valid\":true}"),
+            (status, &response[..])
+        );
+
+        // Proof is not valid after roots change
+        forest.write().unwrap().insert([3; 32]);
+        let (status, response) = send(&forest, verify(&body)).await;
+        assert_eq!(
+            (StatusCode::OK, "{\"valid\":false}"),
+            (status, &response[..])
+        );
+
+        let (status, _) = send(&forest, verify("{}")).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+
+        let missing = Blake3.hash_leaf(&[9; 32]);
+        let (status, _) = send(&forest, get(&format!("/prove/{}", missing))).await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+
+        let (status, _) = send(&forest, get("/prove/xyz")).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+}
@@ -0,0 +1,229 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::thread;
+
+use crate::{
+    estimate::MemoryUsage, Blake3, Hash, Hasher, MemoryForest, Proof, Prover, RootWitness, Utreexo,
+    UtreexoError,
+};
+
+/// Merkle forest which splits leaves across independent sub-forests (shards) by prefix of leaf hash
+///
+/// Each shard is an ordinary [`MemoryForest`] with its own roots, so, shards can be updated in
+/// parallel (see `ShardedForest::insert_batch`, with `std` feature). An inclusion proof is only
+/// valid against roots of the shard it belongs to, which can be found using
+/// [`ShardedForest::shard_index`] on leaf hash of proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardedForest<H = Blake3> {
+    /// Independent sub-forests
+    shards: Vec<MemoryForest<H>>,
+    /// Hasher used for calculating leaf hashes
+    hasher: H,
+}
+
+impl ShardedForest {
+    /// Maximum number of shards (leaves are assigned to shards using first byte of their hash)
+    pub const MAX_SHARDS: usize = 256;
+
+    /// Creates a new instance of sharded forest with given number of shards
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `num_shards` is zero or greater than [`ShardedForest::MAX_SHARDS`].
+    pub fn new(num_shards: usize) -> Self {
+        Self::with_hasher(num_shards, Blake3)
+    }
+}
+
+impl<H: Hasher + Clone> ShardedForest<H> {
+    /// Creates a new instance of sharded forest with given number of shards which uses given hasher
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `num_shards` is zero or greater than [`ShardedForest::MAX_SHARDS`].
+    pub fn with_hasher(num_shards: usize, hasher: H) -> Self {
+        assert!(
+            num_shards > 0 && num_shards <= ShardedForest::MAX_SHARDS,
+            "Number of shards should be between 1 and 256"
+        );
+
+        Self {
+            shards: (0..num_shards)
+                .map(|_| MemoryForest::with_hasher(hasher.clone()))
+                .collect(),
+            hasher,
+        }
+    }
+}
+
+impl<H: Hasher> ShardedForest<H> {
+    /// Returns all the shards of forest
+    pub fn shards(&self) -> &[MemoryForest<H>] {
+        &self.shards
+    }
+
+    /// Returns index of shard which contains given leaf hash
+    pub fn shard_index(&self, leaf_hash: &Hash) -> usize {
+        usize::from(leaf_hash.as_bytes()[0]) % self.shards.len()
+    }
+
+    /// Returns heap memory currently used by all the shards (see [`MemoryForest::memory_usage`])
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.shards
+            .iter()
+            .map(MemoryForest::memory_usage)
+            .fold(MemoryUsage::default(), |total, usage| total + usage)
+    }
+
+    /// Compacts all the shards (see [`MemoryForest::compact`])
+    pub fn compact(&mut self) {
+        for shard in self.shards.iter_mut() {
+            shard.compact();
+        }
+    }
+
+    /// Returns root witnesses of all the shards (in order of shard index)
+    pub fn root_witnesses(&self) -> Vec<RootWitness> {
+        self.shards.iter().map(MemoryForest::root_witness).collect()
+    }
+
+    /// Inserts given values in forest. Leaf hashes are calculated and shards are updated in
+    /// parallel (one thread per shard), and the result is same as inserting the values one by one.
+    #[cfg(feature = "std")]
+    pub fn insert_batch<T>(&mut self, leaf_values: &[T])
+    where
+        T: AsRef<[u8]> + Sync,
+        H: Send + Sync,
+    {
+        let num_threads = thread::available_parallelism().map_or(1, usize::from);
+        let chunk_len = leaf_values.len().div_ceil(num_threads).max(1);
+        let hasher = &self.hasher;
+
+        let leaf_hashes: Vec<Vec<Hash>> = thread::scope(|scope| {
+            let handles: Vec<_> = leaf_values
+                .chunks(chunk_len)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|leaf_value| hasher.hash_leaf(leaf_value.as_ref()))
+                            .collect()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Leaf hashing thread panicked"))
+                .collect()
+        });
+
+        let mut buckets: Vec<Vec<Hash>> = self.shards.iter().map(|_| Vec::new()).collect();
+
+        for leaf_hash in leaf_hashes.into_iter().flatten() {
+            buckets[self.shard_index(&leaf_hash)].push(leaf_hash);
+        }
+
+        thread::scope(|scope| {
+            for (shard, bucket) in self.shards.iter_mut().zip(buckets) {
+                if !bucket.is_empty() {
+                    scope.spawn(move || {
+                        for leaf_hash in bucket {
+                            shard.insert_leaf(leaf_hash);
+                        }
+                    });
+                }
+            }
+        });
+    }
+}
+
+impl<H: Hasher> Prover for ShardedForest<H> {
+    fn prove_value<T: AsRef<[u8]>>(&self, leaf_value: T) -> Result<Option<Proof>, UtreexoError> {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.prove_hash(&leaf_hash)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.shards[self.shard_index(leaf_hash)].prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        leaf_hashes
+            .iter()
+            .map(|leaf_hash| self.prove_hash(leaf_hash))
+            .collect()
+    }
+}
+
+impl<H: Hasher> Utreexo for ShardedForest<H> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        let leaf_hash = self.hasher.hash_leaf(leaf_value.as_ref());
+        self.insert_hash(leaf_hash);
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        let index = self.shard_index(&leaf_hash);
+        self.shards[index].insert_leaf(leaf_hash);
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        let index = self.shard_index(&proof.leaf_hash);
+        self.shards[index].delete(proof)
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.shards.iter().map(Utreexo::num_leaves).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_sharded_forest() {
+        let mut forest = ShardedForest::new(4);
+
+        for value in 0..32u8 {
+            forest.insert([value; 32]);
+        }
+
+        let num_leaves: u64 = forest
+            .root_witnesses()
+            .iter()
+            .map(RootWitness::num_leaves)
+            .sum();
+        assert_eq!(32, num_leaves);
+
+        let proof = forest.prove([7; 32]).unwrap().unwrap();
+        let witness = &forest.root_witnesses()[forest.shard_index(proof.leaf_hash())];
+        assert!(witness.verify(&Blake3, &proof));
+
+        assert!(forest.delete(&proof));
+        assert!(!forest.delete(&proof));
+        assert!(forest.prove([7; 32]).unwrap().is_none());
+        assert!(forest.prove([8; 32]).unwrap().is_some());
+
+        let leaf_hashes = [Blake3.hash_leaf(&[8; 32]), Blake3.hash_leaf(&[7; 32])];
+        let proofs = forest.prove_batch(&leaf_hashes).unwrap();
+        assert_eq!(vec![forest.prove([8; 32]).unwrap(), None], proofs);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn check_sharded_forest_insert_batch() {
+        let values: Vec<[u8; 2]> = (0..100u8).map(|value| [value, 1]).collect();
+
+        let mut forest = ShardedForest::new(8);
+        forest.insert_batch(&values);
+
+        let mut expected = ShardedForest::new(8);
+
+        for value in values.iter() {
+            expected.insert(value);
+        }
+
+        assert_eq!(expected, forest);
+    }
+}
@@ -0,0 +1,260 @@
+use alloc::vec::Vec;
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, RwLock};
+
+#[cfg(feature = "std")]
+use crate::{Blake3, MemoryAccumulator, MemoryForest};
+use crate::{Hash, Proof, Prover, Utreexo, UtreexoError};
+
+/// Wrapper over an accumulator (or forest) stored in a `RefCell`, `Mutex` or `RwLock` which
+/// implements [`Utreexo`] and [`Prover`] for shared references, i.e., `&SharedUtreexo<_>`. This is
+/// useful when a framework only hands out shared references to application state.
+///
+/// # Panics
+///
+/// Operations panic if the `RefCell` is already borrowed or if the `Mutex`/`RwLock` is poisoned.
+#[derive(Debug, Default)]
+pub struct SharedUtreexo<L>(L);
+
+/// Thread-safe forest which allows concurrent proof generation and exclusive updates
+#[cfg(feature = "std")]
+pub type SharedForest<H = Blake3> = SharedUtreexo<RwLock<MemoryForest<H>>>;
+
+/// Thread-safe accumulator which allows exclusive updates from multiple threads
+#[cfg(feature = "std")]
+pub type SharedAccumulator<H = Blake3> = SharedUtreexo<RwLock<MemoryAccumulator<H>>>;
+
+impl<L> SharedUtreexo<L> {
+    /// Creates a new shared accumulator from given cell or lock
+    pub fn new(inner: L) -> Self {
+        Self(inner)
+    }
+
+    /// Consumes the wrapper and returns the underlying cell or lock
+    pub fn into_inner(self) -> L {
+        self.0
+    }
+}
+
+impl<T: Utreexo> Utreexo for &SharedUtreexo<RefCell<T>> {
+    fn insert_value<V: AsRef<[u8]>>(&mut self, leaf_value: V) {
+        self.0.borrow_mut().insert_value(leaf_value)
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.0.borrow_mut().insert_hash(leaf_hash)
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        self.0.borrow_mut().delete(proof)
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.0.borrow().num_leaves()
+    }
+}
+
+impl<T: Prover> Prover for &SharedUtreexo<RefCell<T>> {
+    fn prove_value<V: AsRef<[u8]>>(&self, leaf_value: V) -> Result<Option<Proof>, UtreexoError> {
+        self.0.borrow().prove_value(leaf_value)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.0.borrow().prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        self.0.borrow().prove_batch(leaf_hashes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Utreexo> Utreexo for &SharedUtreexo<Mutex<T>> {
+    fn insert_value<V: AsRef<[u8]>>(&mut self, leaf_value: V) {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .insert_value(leaf_value)
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .insert_hash(leaf_hash)
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .delete(proof)
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .num_leaves()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Prover> Prover for &SharedUtreexo<Mutex<T>> {
+    fn prove_value<V: AsRef<[u8]>>(&self, leaf_value: V) -> Result<Option<Proof>, UtreexoError> {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .prove_value(leaf_value)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        self.0
+            .lock()
+            .expect("Accumulator mutex is poisoned")
+            .prove_batch(leaf_hashes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Utreexo> Utreexo for &SharedUtreexo<RwLock<T>> {
+    fn insert_value<V: AsRef<[u8]>>(&mut self, leaf_value: V) {
+        self.0
+            .write()
+            .expect("Accumulator lock is poisoned")
+            .insert_value(leaf_value)
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.0
+            .write()
+            .expect("Accumulator lock is poisoned")
+            .insert_hash(leaf_hash)
+    }
+
+    fn delete(&mut self, proof: &Proof) -> bool {
+        self.0
+            .write()
+            .expect("Accumulator lock is poisoned")
+            .delete(proof)
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.0
+            .read()
+            .expect("Accumulator lock is poisoned")
+            .num_leaves()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Prover> Prover for &SharedUtreexo<RwLock<T>> {
+    fn prove_value<V: AsRef<[u8]>>(&self, leaf_value: V) -> Result<Option<Proof>, UtreexoError> {
+        self.0
+            .read()
+            .expect("Accumulator lock is poisoned")
+            .prove_value(leaf_value)
+    }
+
+    fn prove_hash(&self, leaf_hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        self.0
+            .read()
+            .expect("Accumulator lock is poisoned")
+            .prove_hash(leaf_hash)
+    }
+
+    fn prove_batch(&self, leaf_hashes: &[Hash]) -> Result<Vec<Option<Proof>>, UtreexoError> {
+        self.0
+            .read()
+            .expect("Accumulator lock is poisoned")
+            .prove_batch(leaf_hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryForest;
+
+    fn insert_and_delete<U: Utreexo + Prover>(mut forest: U) {
+        assert!(forest.is_empty());
+
+        forest.insert([0; 32]);
+        forest.insert([1; 32]);
+        assert_eq!(2, forest.num_leaves());
+
+        let proof = forest.prove([0; 32]).unwrap();
+        assert!(proof.is_some());
+        assert!(forest.delete(&proof.unwrap()));
+
+        assert!(forest.prove([0; 32]).unwrap().is_none());
+        assert!(forest.prove([1; 32]).unwrap().is_some());
+    }
+
+    #[test]
+    fn check_shared_ref_cell() {
+        let forest = SharedUtreexo::new(RefCell::new(MemoryForest::new()));
+        insert_and_delete(&forest);
+        assert!(forest
+            .into_inner()
+            .into_inner()
+            .prove([1; 32])
+            .unwrap()
+            .is_some());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn check_shared_locks() {
+        let forest = SharedUtreexo::new(Mutex::new(MemoryForest::new()));
+        insert_and_delete(&forest);
+
+        let forest = SharedUtreexo::new(RwLock::new(MemoryForest::new()));
+        insert_and_delete(&forest);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn check_shared_forest_threads() {
+        use std::{sync::Arc, thread};
+
+        let forest: Arc<SharedForest> =
+            Arc::new(SharedUtreexo::new(RwLock::new(MemoryForest::new())));
+
+        let handles: Vec<_> = (0..4u8)
+            .map(|thread_id| {
+                let forest = forest.clone();
+
+                thread::spawn(move || {
+                    let mut forest = &*forest;
+
+                    for value in 0..8u8 {
+                        forest.insert([thread_id, value]);
+                    }
+
+                    let proof = forest.prove([thread_id, 0]).unwrap().unwrap();
+                    assert!(forest.delete(&proof));
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let forest = &*forest;
+
+        for thread_id in 0..4u8 {
+            assert!(forest.prove([thread_id, 0]).unwrap().is_none());
+            assert!(forest.prove([thread_id, 1]).unwrap().is_some());
+        }
+    }
+}
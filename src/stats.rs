@@ -0,0 +1,89 @@
+use alloc::vec::Vec;
+
+use crate::estimate::forest_nodes;
+
+/// Summary of shape of an accumulator or a forest (see
+/// [`MemoryForest::stats`](crate::MemoryForest::stats))
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ForestStats {
+    /// Number of leaves
+    pub num_leaves: u64,
+    /// Number of merkle trees
+    pub num_trees: usize,
+    /// Occupancy of every height (`occupied_heights[h]` is `true` iff there is a tree of height
+    /// `h`), up to the height of tallest tree
+    pub occupied_heights: Vec<bool>,
+    /// Height of tallest tree, if there are any trees
+    pub tallest_tree: Option<usize>,
+    /// Number of merkle tree nodes (for an accumulator, the number of nodes stored by a forest
+    /// with the same leaves)
+    pub num_nodes: u64,
+}
+
+impl ForestStats {
+    /// Creates statistics from occupancy of heights of merkle trees (indexed by height)
+    pub(crate) fn from_heights(heights: impl IntoIterator<Item = bool>) -> Self {
+        let mut occupied_heights: Vec<bool> = heights.into_iter().collect();
+
+        while occupied_heights.last() == Some(&false) {
+            occupied_heights.pop();
+        }
+
+        let num_leaves = occupied_heights
+            .iter()
+            .enumerate()
+            .filter(|(_, occupied)| **occupied)
+            .map(|(height, _)| 1 << height)
+            .sum();
+
+        Self {
+            num_leaves,
+            num_trees: occupied_heights
+                .iter()
+                .filter(|occupied| **occupied)
+                .count(),
+            tallest_tree: occupied_heights.len().checked_sub(1),
+            num_nodes: forest_nodes(num_leaves),
+            occupied_heights,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryAccumulator, MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_forest_stats() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        assert_eq!(ForestStats::default(), forest.stats());
+
+        for value in 0..11u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        let stats = forest.stats();
+
+        assert_eq!(11, stats.num_leaves);
+        assert_eq!(3, stats.num_trees);
+        assert_eq!(vec![true, true, false, true], stats.occupied_heights);
+        assert_eq!(Some(3), stats.tallest_tree);
+        assert_eq!(19, stats.num_nodes);
+        assert_eq!(stats, accumulator.stats());
+
+        // Deleting the only leaf of smallest tree only empties a height
+        let proof = forest.prove([10; 32]).unwrap().unwrap();
+        assert!(forest.delete(&proof));
+        assert!(accumulator.delete(&proof));
+
+        let stats = forest.stats();
+
+        assert_eq!(vec![false, true, false, true], stats.occupied_heights);
+        assert_eq!(2, stats.num_trees);
+        assert_eq!(stats, accumulator.stats());
+    }
+}
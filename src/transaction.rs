@@ -0,0 +1,127 @@
+use crate::{
+    Hash, Hasher, MemoryAccumulator, MemoryForest, Operation, OperationLog, Proof, Utreexo,
+};
+
+/// Batch of insertions and deletions staged against a forest (see [`MemoryForest::transaction`])
+///
+/// Staged operations are applied to a lightweight overlay which only contains root hashes of
+/// forest, so, the would-be roots can be inspected before committing. Forest itself is only
+/// updated when the transaction is committed, and dropping a transaction without committing it
+/// rolls it back.
+#[derive(Debug)]
+pub struct Transaction<'a, H: Hasher + Clone> {
+    /// Forest on which transaction is committed
+    forest: &'a mut MemoryForest<H>,
+    /// Root hashes of forest after applying staged operations
+    overlay: MemoryAccumulator<H>,
+    /// Staged operations (in order)
+    staged: OperationLog,
+}
+
+impl<'a, H: Hasher + Clone> Transaction<'a, H> {
+    /// Creates a new transaction on given forest
+    pub(crate) fn new(forest: &'a mut MemoryForest<H>) -> Self {
+        let overlay = MemoryAccumulator::from(&*forest);
+
+        Self {
+            forest,
+            overlay,
+            staged: Default::default(),
+        }
+    }
+
+    /// Returns the root hashes of forest after applying all the staged operations
+    pub fn roots(&self) -> &[Option<Hash>] {
+        self.overlay.root_hashes()
+    }
+
+    /// Returns all the staged operations (in order)
+    pub fn staged(&self) -> &OperationLog {
+        &self.staged
+    }
+
+    /// Applies all the staged operations to forest
+    pub fn commit(self) {
+        self.staged
+            .replay(self.forest)
+            .expect("Staged deletions should be valid because they are verified against overlay");
+    }
+
+    /// Discards all the staged operations (same as dropping the transaction)
+    pub fn rollback(self) {}
+}
+
+impl<'a, H: Hasher + Clone> Utreexo for Transaction<'a, H> {
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        let leaf_hash = self.forest.hasher().hash_leaf(leaf_value.as_ref());
+        self.insert_hash(leaf_hash);
+    }
+
+    fn insert_hash(&mut self, leaf_hash: Hash) {
+        self.overlay.insert_hash(leaf_hash);
+        self.staged.push(Operation::Insert(leaf_hash));
+    }
+
+    /// Verifies given proof against root hashes after all the staged operations and stages its
+    /// deletion
+    fn delete(&mut self, proof: &Proof) -> bool {
+        if !self.overlay.delete(proof) {
+            return false;
+        }
+
+        self.staged.push(Operation::Delete(proof.clone()));
+        true
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.overlay.num_leaves()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_transaction() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..6u8 {
+            forest.insert([value; 32]);
+        }
+
+        let before = forest.clone();
+        let mut expected = forest.clone();
+        let proof = forest.prove([2; 32]).unwrap().unwrap();
+
+        let mut transaction = forest.transaction();
+        transaction.insert([6; 32]);
+        assert!(transaction.delete(&proof));
+        assert!(!transaction.delete(&proof));
+        transaction.insert([7; 32]);
+
+        expected.insert([6; 32]);
+        assert!(expected.delete(&proof));
+        expected.insert([7; 32]);
+
+        assert_eq!(&expected.roots()[..], transaction.roots());
+        assert_eq!(expected.num_leaves(), transaction.num_leaves());
+        assert_eq!(3, transaction.staged().len());
+
+        transaction.rollback();
+        assert_eq!(before, forest);
+
+        // Dropping a transaction also rolls it back
+        forest.transaction().insert([6; 32]);
+        assert_eq!(before, forest);
+
+        let mut transaction = forest.transaction();
+        transaction.insert([6; 32]);
+        assert!(transaction.delete(&proof));
+        transaction.insert([7; 32]);
+        transaction.commit();
+
+        assert_eq!(expected, forest);
+        assert!(forest.prove([7; 32]).unwrap().is_some());
+    }
+}
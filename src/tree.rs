@@ -1,13 +1,30 @@
-use alloc::{vec, vec::Vec};
-use core::convert::TryInto;
+use alloc::{string::String, vec, vec::Vec};
+#[cfg(feature = "serde-1")]
+use core::convert::TryFrom;
+use core::{convert::TryInto, mem::size_of};
 
-use indexmap::{indexset, IndexSet};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 #[cfg(feature = "serde-1")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::{hash_intermediate, Direction, Hash, Path, Proof};
+use crate::{
+    bulk,
+    metrics::{count, Counter},
+    render::write_dot_tree,
+    Direction, Hash, Hasher, Path, Proof, UtreexoError,
+};
 
 /// Merkle tree
+///
+/// A tree of height `h` always contains exactly `2^(h + 1) - 1` nodes (with `serde-1` feature,
+/// deserialized trees with any other number of nodes are rejected), so, its root and leaves are
+/// always present.
+///
+/// With `zeroize` feature, nodes of a tree are wiped when it is dropped (or explicitly using
+/// `Zeroize`, which leaves a tree without any nodes that must not be used afterwards).
 //
 // # Tree representation: Numbers are index/position of nodes in vector containing the full tree
 //
@@ -27,14 +44,40 @@ use crate::{hash_intermediate, Direction, Hash, Path, Proof};
 //  / \     / \     / \     / \
 // 0   1   3   4   7   8  10   11 <- Leaves
 //
-// In addition to all the nodes, tree also contains an ordered set of all the leaves
+// Leaves are not stored separately. In post-order, `k`th leaf is preceded by `k` leaves and
+// `k - popcount(k)` intermediate nodes (one for each complete subtree on its left), so, it is
+// located at index `2k - popcount(k)`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[cfg_attr(feature = "serde-1", serde(try_from = "RawTree"))]
 pub struct Tree {
     /// Nodes in tree
     nodes: Vec<Hash>,
-    /// Leaves of the tree (this is only present to increase the efficiency of proof generation)
-    leaves: IndexSet<Hash>,
+}
+
+/// Unvalidated tree used for deserialization
+#[cfg(feature = "serde-1")]
+#[derive(Deserialize)]
+#[serde(rename = "Tree")]
+struct RawTree {
+    nodes: Vec<Hash>,
+}
+
+#[cfg(feature = "serde-1")]
+impl TryFrom<RawTree> for Tree {
+    type Error = UtreexoError;
+
+    fn try_from(raw: RawTree) -> Result<Self, Self::Error> {
+        // A perfect tree contains one node less than a power of two
+        if raw.nodes.is_empty() || !(raw.nodes.len() + 1).is_power_of_two() {
+            return Err(UtreexoError::InvalidEncoding(
+                "Number of nodes does not match any merkle tree",
+            ));
+        }
+
+        Ok(Self { nodes: raw.nodes })
+    }
 }
 
 impl Tree {
@@ -42,13 +85,12 @@ impl Tree {
     pub fn new(leaf_hash: Hash) -> Self {
         Self {
             nodes: vec![leaf_hash],
-            leaves: indexset![leaf_hash],
         }
     }
 
     /// Returns the number of leaves in the tree
     pub fn num_leaves(&self) -> usize {
-        self.leaves.len()
+        self.nodes.len().div_ceil(2)
     }
 
     /// Returns height of the tree
@@ -68,11 +110,199 @@ impl Tree {
             .expect("Expected atleast one element in merkle tree.")
     }
 
-    /// Returns inclusion proof of a leaf hash in the tree, if present
-    pub fn prove(&self, hash: &Hash) -> Option<Proof> {
+    /// Returns all the nodes of tree in post-order
+    pub(crate) fn nodes(&self) -> &[Hash] {
+        &self.nodes
+    }
+
+    /// Returns heap memory (in bytes) used by nodes of tree
+    pub(crate) fn heap_size(&self) -> usize {
+        self.nodes.capacity() * size_of::<Hash>()
+    }
+
+    /// Releases unused capacity of node vector
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    /// Returns mutable reference to nodes of tree (only used for corrupting trees in tests)
+    #[cfg(test)]
+    pub(crate) fn nodes_mut(&mut self) -> &mut Vec<Hash> {
+        &mut self.nodes
+    }
+
+    /// Creates a perfect tree from given leaf hashes (number of leaf hashes must be a power of two)
+    pub(crate) fn from_leaves<H: Hasher + Sync>(hasher: &H, leaf_hashes: &[Hash]) -> Self {
+        let mut nodes = vec![Hash::from([0; 32]); 2 * leaf_hashes.len() - 1];
+        bulk::build_nodes(hasher, leaf_hashes, &mut nodes);
+
+        Self { nodes }
+    }
+
+    /// Creates a tree of given height from its nodes in post-order. Returns `None` if number of
+    /// nodes does not match the height.
+    pub(crate) fn from_nodes(height: usize, nodes: Vec<Hash>) -> Option<Self> {
+        let num_leaves = 1usize.checked_shl(height as u32)?;
+
+        if nodes.len() != (2 * num_leaves).checked_sub(1)? {
+            return None;
+        }
+
+        Some(Self { nodes })
+    }
+
+    /// Creates a tree from given subtrees of same height (in order) by calculating intermediate
+    /// nodes above them (number of subtrees must be a power of two)
+    pub(crate) fn from_subtrees<H: Hasher>(
+        hasher: &H,
+        subtrees: impl IntoIterator<Item = Tree>,
+    ) -> Self {
+        let mut nodes = Vec::new();
+
+        // Stack of root hashes and heights of complete subtrees which are not merged yet
+        let mut stack: Vec<(Hash, usize)> = Vec::new();
+
+        for subtree in subtrees {
+            let mut current = (*subtree.root_hash(), 0);
+            nodes.extend_from_slice(&subtree.nodes);
+
+            while let Some(&(left, left_height)) = stack.last() {
+                if left_height != current.1 {
+                    break;
+                }
+
+                stack.pop();
+
+                let hash = hasher.hash_intermediate(&left, &current.0);
+                nodes.push(hash);
+                current = (hash, left_height + 1);
+            }
+
+            stack.push(current);
+        }
+
+        Self { nodes }
+    }
+
+    /// Checks that the tree is a consistent merkle tree of given height, i.e., it contains exactly
+    /// `2^(height + 1) - 1` nodes and all the intermediate nodes are hashes of their children.
+    /// Returns `CorruptState` error with index of the first inconsistent node otherwise.
+    pub(crate) fn check_consistency<H: Hasher>(
+        &self,
+        hasher: &H,
+        height: usize,
+    ) -> Result<(), UtreexoError> {
+        let corrupt = |index| UtreexoError::CorruptState { index, height };
+
+        let num_leaves = 1usize.checked_shl(height as u32).unwrap_or(0);
+        let num_nodes = 1usize
+            .checked_shl((height + 1) as u32)
+            .map_or(usize::MAX, |num| num - 1);
+
+        if num_leaves == 0 || self.nodes.len() != num_nodes {
+            return Err(corrupt(self.nodes.len().min(num_nodes)));
+        }
+
+        check_subtree(hasher, &self.nodes, 0).map_err(corrupt)
+    }
+
+    /// Recalculates all the intermediate nodes of a tree of given height from its leaf nodes with
+    /// given hasher. Returns `CorruptState` error if the number of nodes does not match the height
+    /// (because positions of leaf nodes can not be determined in that case).
+    pub(crate) fn rebuild<H: Hasher>(
+        &mut self,
+        hasher: &H,
+        height: usize,
+    ) -> Result<(), UtreexoError> {
+        let num_nodes = 1usize.checked_shl((height + 1) as u32).map(|num| num - 1);
+
+        if num_nodes != Some(self.nodes.len()) {
+            return Err(UtreexoError::CorruptState {
+                index: self.nodes.len(),
+                height,
+            });
+        }
+
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+
+        // Stack of root hashes and heights of complete subtrees which are not merged yet
+        let mut stack: Vec<(Hash, usize)> = Vec::with_capacity(height + 1);
+
+        for leaf in self.leaves() {
+            let mut current = (*leaf, 0);
+            nodes.push(*leaf);
+
+            while let Some(&(left, left_height)) = stack.last() {
+                if left_height != current.1 {
+                    break;
+                }
+
+                stack.pop();
+
+                let hash = hasher.hash_intermediate(&left, &current.0);
+                nodes.push(hash);
+                current = (hash, left_height + 1);
+            }
+
+            stack.push(current);
+        }
+
+        // Replacing whole tree (instead of only its nodes) drops old tree, so, its nodes are wiped
+        // with `zeroize` feature
+        *self = Self { nodes };
+
+        Ok(())
+    }
+
+    /// Returns node at given index or `CorruptState` error if the node does not exist
+    fn node(&self, index: usize) -> Result<&Hash, UtreexoError> {
+        self.nodes.get(index).ok_or(UtreexoError::CorruptState {
+            index,
+            height: self.height(),
+        })
+    }
+
+    /// Returns all the leaves of the tree (in order)
+    pub(crate) fn leaves(&self) -> impl Iterator<Item = &Hash> {
+        (0..self.num_leaves()).map(move |k| &self.nodes[2 * k - k.count_ones() as usize])
+    }
+
+    /// Returns leaf at given position in the tree, if present
+    pub(crate) fn leaf(&self, position: usize) -> Option<&Hash> {
+        if position < self.num_leaves() {
+            self.nodes
+                .get(2 * position - position.count_ones() as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns inclusion proof of a leaf hash in the tree, if present. This searches all the
+    /// leaves of the tree (forests use their index of leaves instead).
+    pub fn prove(&self, hash: &Hash) -> Result<Option<Proof>, UtreexoError> {
+        match self.leaves().position(|leaf| leaf == hash) {
+            Some(position) => self.prove_at(hash, position).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns inclusion proof of given leaf hash at given position in the tree
+    pub(crate) fn prove_at(&self, hash: &Hash, position: usize) -> Result<Proof, UtreexoError> {
         let height = self.height();
 
-        let position = self.leaves.get_index_of(hash)?;
+        // A tree of height `h` contains exactly `2^(h + 1) - 1` nodes. Otherwise, the indices
+        // calculated below do not point to the expected nodes.
+        let num_nodes = 1usize
+            .checked_shl((height + 1) as u32)
+            .map_or(usize::MAX, |num| num - 1);
+
+        if self.nodes.len() != num_nodes {
+            return Err(UtreexoError::CorruptState {
+                index: self.nodes.len().min(num_nodes),
+                height,
+            });
+        }
+
         let path = Path::for_height_and_num(height, position);
 
         let mut sibling_hashes = Vec::with_capacity(height);
@@ -84,14 +314,14 @@ impl Tree {
             match direction {
                 Direction::Right => {
                     let sibling_index = current_root_index - 1;
-                    sibling_hashes.push(self.nodes[sibling_index]);
+                    sibling_hashes.push(*self.node(sibling_index)?);
                     current_root_index =
                         ((current_root_index - current_base_index) / 2) - 1 + current_base_index;
                 }
                 Direction::Left => {
                     let sibling_index =
                         ((current_root_index - current_base_index) / 2) - 1 + current_base_index;
-                    sibling_hashes.push(self.nodes[sibling_index]);
+                    sibling_hashes.push(*self.node(sibling_index)?);
                     current_root_index -= 1;
                     current_base_index = sibling_index + 1;
                 }
@@ -100,95 +330,197 @@ impl Tree {
 
         sibling_hashes.reverse();
 
-        Some(Proof {
+        Ok(Proof {
             path,
             leaf_hash: *hash,
             sibling_hashes,
         })
     }
 
-    /// Splits a tree and returns both subtrees. If there is only one node in the tree, the right
-    /// subtree will be returned as `None`.
-    pub fn split(&self) -> (Self, Option<Self>) {
-        if self.num_leaves() == 1 {
-            return (self.clone(), None);
-        }
+    /// Returns sibling subtrees along given path (from leaf to root), i.e., the subtrees which
+    /// remain after removing the leaf at given path.
+    ///
+    /// Every subtree of a tree occupies a contiguous range of nodes in post-order, so, positions of
+    /// siblings are calculated directly and each sibling is copied exactly once (instead of cloning
+    /// the whole tree and splitting it at every level).
+    ///
+    /// # Panics
+    ///
+    /// This function panics if height of path is not equal to height of tree.
+    pub(crate) fn sibling_subtrees(&self, path: &Path) -> Vec<Tree> {
+        let height = self.height();
 
-        // First, we equally split nodes of merkle tree into two parts (excluding root node)
-        assert!(
-            self.nodes.len() % 2 == 1,
-            "Merkle tree should contain odd number of nodes."
+        assert_eq!(
+            height,
+            path.height(),
+            "Height of path should be equal to height of merkle tree."
         );
 
-        let nodes_to_take = (self.nodes.len() - 1) / 2;
+        // Position of first node of current subtree (which contains the path)
+        let mut node_start = 0;
 
-        let left_tree_nodes = self.nodes[0..nodes_to_take].to_vec();
-        let right_tree_nodes = self.nodes[nodes_to_take..(nodes_to_take * 2)].to_vec();
+        let mut sibling_subtrees = Vec::with_capacity(height);
 
-        // Next, we split leaves into two equal parts
-        assert!(
-            self.num_leaves() % 2 == 0,
-            "Merkle tree should contain even number of leaves."
-        );
+        for (level, direction) in path.directions().rev().enumerate() {
+            // Both the children of current subtree have this height
+            let child_height = height - level - 1;
+            let child_num_nodes = (2 << child_height) - 1;
 
-        let leaves_to_take = self.num_leaves() / 2;
+            match direction {
+                Direction::Right => {
+                    sibling_subtrees.push(self.subtree(node_start + child_num_nodes, child_height))
+                }
+                Direction::Left => {
+                    sibling_subtrees.push(self.subtree(node_start, child_height));
+                    node_start += child_num_nodes;
+                }
+            }
+        }
 
-        let mut leaves_iter = self.leaves.clone().into_iter();
+        sibling_subtrees.reverse();
+        sibling_subtrees
+    }
 
-        let mut left_tree_leaves = IndexSet::with_capacity(leaves_to_take);
-        let mut right_tree_leaves = IndexSet::with_capacity(leaves_to_take);
+    /// Returns a copy of `index`th subtree (from left) of given height, if present
+    pub(crate) fn subtree_at(&self, height: usize, index: usize) -> Option<Tree> {
+        let num_nodes = 1usize.checked_shl((height + 1) as u32)? - 1;
 
-        for _ in 0..leaves_to_take {
-            left_tree_leaves.insert(leaves_iter.next().expect("Expected leaf of merkle tree"));
-        }
+        // Just like leaves, `k`th subtree is preceded by `k` subtrees and `k - popcount(k)` nodes
+        // above them
+        let node_start = index.checked_mul(num_nodes + 1)? - index.count_ones() as usize;
+        let nodes = self
+            .nodes
+            .get(node_start..node_start.checked_add(num_nodes)?)?;
 
-        for _ in 0..leaves_to_take {
-            right_tree_leaves.insert(leaves_iter.next().expect("Expected leaf of merkle tree"));
+        Some(Tree {
+            nodes: nodes.to_vec(),
+        })
+    }
+
+    /// Returns a copy of subtree of given height starting at given node position
+    fn subtree(&self, node_start: usize, height: usize) -> Tree {
+        let num_nodes = (2 << height) - 1;
+
+        Tree {
+            nodes: self.nodes[node_start..(node_start + num_nodes)].to_vec(),
         }
+    }
+}
 
-        assert!(
-            leaves_iter.next().is_none(),
-            "More than expected leaves in merkle tree."
-        );
+/// Read-only view of a merkle tree in a forest (see [`MemoryForest::trees`])
+///
+/// [`MemoryForest::trees`]: crate::MemoryForest::trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeRef<'a>(&'a Tree);
 
-        let left_tree = Tree {
-            nodes: left_tree_nodes,
-            leaves: left_tree_leaves,
-        };
+impl<'a> TreeRef<'a> {
+    /// Returns root hash of merkle tree
+    pub fn root_hash(&self) -> &'a Hash {
+        self.0.root_hash()
+    }
 
-        let right_tree = Tree {
-            nodes: right_tree_nodes,
-            leaves: right_tree_leaves,
-        };
+    /// Returns height of merkle tree
+    pub fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    /// Returns the number of leaves in merkle tree
+    pub fn num_leaves(&self) -> usize {
+        self.0.num_leaves()
+    }
+
+    /// Returns the number of nodes (leaves and intermediate nodes) in merkle tree
+    pub fn num_nodes(&self) -> usize {
+        self.0.nodes.len()
+    }
+
+    /// Returns all the leaf hashes of merkle tree (in order of insertion)
+    pub fn leaves(&self) -> impl Iterator<Item = &'a Hash> {
+        self.0.leaves()
+    }
+
+    /// Returns leaf hash at given position in merkle tree, if present
+    pub fn leaf(&self, position: usize) -> Option<&'a Hash> {
+        self.0.leaf(position)
+    }
 
-        (left_tree, Some(right_tree))
+    /// Returns DOT graph (e.g., for rendering with Graphviz) of merkle tree. Nodes are labelled
+    /// with first 4 bytes of their hashes.
+    ///
+    /// If position of a leaf is given, the graph is limited to the nodes of inclusion proof of that
+    /// leaf, i.e., the path from the leaf to root (highlighted) and siblings of nodes on the path.
+    pub fn to_dot(&self, leaf_position: Option<usize>) -> String {
+        let mut out = String::from("digraph tree {\n    node [fontname=\"monospace\"];\n");
+        write_dot_tree(&mut out, "    ", "n", &self.0.nodes, leaf_position);
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<'a> From<&'a Tree> for TreeRef<'a> {
+    fn from(tree: &'a Tree) -> Self {
+        Self(tree)
+    }
+}
+
+/// Checks that all the intermediate nodes of a perfect subtree (given in post-order, starting at
+/// given offset in full tree) are hashes of their children. Returns index of the first
+/// inconsistent node otherwise.
+fn check_subtree<H: Hasher>(hasher: &H, nodes: &[Hash], offset: usize) -> Result<(), usize> {
+    if nodes.len() == 1 {
+        return Ok(());
+    }
+
+    let half = (nodes.len() - 1) / 2;
+
+    check_subtree(hasher, &nodes[..half], offset)?;
+    check_subtree(hasher, &nodes[half..(2 * half)], offset + half)?;
+
+    let root_hash = hasher.hash_intermediate(&nodes[half - 1], &nodes[2 * half - 1]);
+
+    if root_hash == nodes[2 * half] {
+        Ok(())
+    } else {
+        Err(offset + 2 * half)
     }
 }
 
 /// Merges two merkle trees into one
-pub fn merge(left: &Tree, right: &Tree) -> Tree {
+pub fn merge<H: Hasher>(hasher: &H, left: &Tree, right: &Tree) -> Tree {
+    count(Counter::Merge, 1);
+
     // Firstly, we merge all the nodes and add the new root node
     let mut new_nodes = Vec::with_capacity(left.nodes.len() + right.nodes.len() + 1);
-    let new_root_hash = hash_intermediate(left.root_hash(), right.root_hash());
+    let new_root_hash = hasher.hash_intermediate(left.root_hash(), right.root_hash());
 
     new_nodes.extend_from_slice(&left.nodes);
     new_nodes.extend_from_slice(&right.nodes);
     new_nodes.push(new_root_hash);
 
-    // Next, we merge leaves
-    let mut new_leaves = IndexSet::with_capacity(left.num_leaves() + right.num_leaves());
-    new_leaves.extend(left.leaves.iter());
-    new_leaves.extend(right.leaves.iter());
+    Tree { nodes: new_nodes }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for Tree {
+    fn zeroize(&mut self) {
+        self.nodes.zeroize();
+    }
+}
 
-    Tree {
-        nodes: new_nodes,
-        leaves: new_leaves,
+#[cfg(feature = "zeroize")]
+impl Drop for Tree {
+    fn drop(&mut self) {
+        self.zeroize();
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for Tree {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{hash_intermediate, Blake3};
 
     #[test]
     fn check_tree_new() {
@@ -208,7 +540,7 @@ mod tests {
         let left_tree = Tree::new(left_leaf_hash);
         let right_tree = Tree::new(right_leaf_hash);
 
-        let tree = merge(&left_tree, &right_tree);
+        let tree = merge(&Blake3, &left_tree, &right_tree);
         let root_hash = hash_intermediate(&left_leaf_hash, &right_leaf_hash);
 
         assert_eq!(1, tree.height());
@@ -218,26 +550,21 @@ mod tests {
     }
 
     #[test]
-    fn check_tree_split() {
-        let left_leaf_hash = [0; 32].into();
-        let right_leaf_hash = [1; 32].into();
+    fn check_tree_sibling_subtrees() {
+        let leaves: Vec<Tree> = (0..4u8)
+            .map(|value| Tree::new([value; 32].into()))
+            .collect();
 
-        let left_tree = Tree::new(left_leaf_hash);
-        let right_tree = Tree::new(right_leaf_hash);
+        let left_tree = merge(&Blake3, &leaves[0], &leaves[1]);
+        let right_tree = merge(&Blake3, &leaves[2], &leaves[3]);
+        let tree = merge(&Blake3, &left_tree, &right_tree);
 
-        let tree = merge(&left_tree, &right_tree);
+        let proof = tree.prove(&[2; 32].into()).unwrap().unwrap();
 
-        let (new_left_tree, new_right_tree) = tree.split();
-
-        assert_eq!(new_left_tree, left_tree);
-        assert!(new_right_tree.is_some());
-        assert_eq!(new_right_tree.unwrap(), right_tree);
-
-        assert_eq!(new_left_tree.num_leaves(), 1);
-        assert_eq!(new_left_tree.height(), 0);
-
-        let (_, more_split) = new_left_tree.split();
-        assert!(more_split.is_none());
+        assert_eq!(
+            vec![leaves[3].clone(), left_tree],
+            tree.sibling_subtrees(&proof.path)
+        );
     }
 
     #[test]
@@ -252,43 +579,199 @@ mod tests {
         let leaf_8 = Tree::new([7; 32].into());
 
         let tree = merge(
-            &merge(&merge(&leaf_1, &leaf_2), &merge(&leaf_3, &leaf_4)),
-            &merge(&merge(&leaf_5, &leaf_6), &merge(&leaf_7, &leaf_8)),
+            &Blake3,
+            &merge(
+                &Blake3,
+                &merge(&Blake3, &leaf_1, &leaf_2),
+                &merge(&Blake3, &leaf_3, &leaf_4),
+            ),
+            &merge(
+                &Blake3,
+                &merge(&Blake3, &leaf_5, &leaf_6),
+                &merge(&Blake3, &leaf_7, &leaf_8),
+            ),
         );
 
-        let proof = tree.prove(&[0; 32].into());
+        let proof = tree.prove(&[0; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[1; 32].into());
+        let proof = tree.prove(&[1; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[2; 32].into());
+        let proof = tree.prove(&[2; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[3; 32].into());
+        let proof = tree.prove(&[3; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[4; 32].into());
+        let proof = tree.prove(&[4; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[5; 32].into());
+        let proof = tree.prove(&[5; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[6; 32].into());
+        let proof = tree.prove(&[6; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[7; 32].into());
+        let proof = tree.prove(&[7; 32].into()).unwrap();
         assert!(proof.is_some());
-        assert!(proof.unwrap().verify(*tree.root_hash()));
+        assert!(proof.unwrap().verify(&Blake3, *tree.root_hash()));
 
-        let proof = tree.prove(&[8; 32].into());
+        let proof = tree.prove(&[8; 32].into()).unwrap();
         assert!(proof.is_none());
     }
+
+    #[test]
+    fn check_tree_prove_corrupt_state() {
+        let mut tree = merge(
+            &Blake3,
+            &Tree::new([0; 32].into()),
+            &Tree::new([1; 32].into()),
+        );
+        tree.nodes.pop();
+
+        // Number of leaves (and height) is derived from number of nodes
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 1,
+                height: 0
+            }),
+            tree.prove(&[0; 32].into())
+        );
+        assert_eq!(Ok(None), tree.prove(&[2; 32].into()));
+    }
+
+    #[test]
+    fn check_tree_from_nodes() {
+        let tree = merge(
+            &Blake3,
+            &merge(
+                &Blake3,
+                &Tree::new([0; 32].into()),
+                &Tree::new([1; 32].into()),
+            ),
+            &merge(
+                &Blake3,
+                &Tree::new([2; 32].into()),
+                &Tree::new([3; 32].into()),
+            ),
+        );
+
+        assert_eq!(Some(tree.clone()), Tree::from_nodes(2, tree.nodes.clone()));
+        assert_eq!(None, Tree::from_nodes(1, tree.nodes.clone()));
+        assert_eq!(
+            vec![Hash::from([2; 32]), Hash::from([3; 32])],
+            tree.leaves().skip(2).copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn check_tree_rebuild() {
+        let tree = merge(
+            &Blake3,
+            &merge(
+                &Blake3,
+                &Tree::new([0; 32].into()),
+                &Tree::new([1; 32].into()),
+            ),
+            &merge(
+                &Blake3,
+                &Tree::new([2; 32].into()),
+                &Tree::new([3; 32].into()),
+            ),
+        );
+
+        let mut corrupt_tree = tree.clone();
+        corrupt_tree.nodes[5] = [9; 32].into();
+        corrupt_tree.nodes[6] = [9; 32].into();
+
+        assert!(corrupt_tree.rebuild(&Blake3, 2).is_ok());
+        assert_eq!(tree, corrupt_tree);
+
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 7,
+                height: 1
+            }),
+            corrupt_tree.rebuild(&Blake3, 1)
+        );
+    }
+
+    #[test]
+    fn check_tree_consistency() {
+        let tree = merge(
+            &Blake3,
+            &merge(
+                &Blake3,
+                &Tree::new([0; 32].into()),
+                &Tree::new([1; 32].into()),
+            ),
+            &merge(
+                &Blake3,
+                &Tree::new([2; 32].into()),
+                &Tree::new([3; 32].into()),
+            ),
+        );
+
+        assert_eq!(Ok(()), tree.check_consistency(&Blake3, 2));
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 7,
+                height: 3
+            }),
+            tree.check_consistency(&Blake3, 3)
+        );
+
+        // Leaf does not match intermediate node
+        let mut corrupt_tree = tree.clone();
+        corrupt_tree.nodes[3] = [9; 32].into();
+        assert_eq!(
+            Err(UtreexoError::CorruptState {
+                index: 5,
+                height: 2
+            }),
+            corrupt_tree.check_consistency(&Blake3, 2)
+        );
+    }
+
+    #[cfg(feature = "serde-1")]
+    #[test]
+    fn check_tree_serde() {
+        use serde_test::{assert_de_tokens, assert_de_tokens_error, Configure, Token};
+
+        let hash = "0000000000000000000000000000000000000000000000000000000000000000";
+        let tokens = |num_nodes: usize| {
+            let mut tokens = vec![
+                Token::Struct {
+                    name: "Tree",
+                    len: 1,
+                },
+                Token::Str("nodes"),
+                Token::Seq {
+                    len: Some(num_nodes),
+                },
+            ];
+            tokens.extend(vec![Token::Str(hash); num_nodes]);
+            tokens.extend(vec![Token::SeqEnd, Token::StructEnd]);
+            tokens
+        };
+
+        assert_de_tokens(&Tree::new([0; 32].into()).readable(), &tokens(1));
+
+        // Trees without nodes (or with a number of nodes which does not match any height) would
+        // not have a root or leaves at expected positions
+        for num_nodes in [0, 2] {
+            assert_de_tokens_error::<serde_test::Readable<Tree>>(
+                &tokens(num_nodes),
+                "Invalid encoding: Number of nodes does not match any merkle tree",
+            );
+        }
+    }
 }
@@ -0,0 +1,180 @@
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use crate::{Blake3, Hash, Hasher, LeafEncode, MemoryForest, Proof, Prover, Utreexo, UtreexoError};
+
+/// Merkle forest of typed values (e.g., UTXOs, certificates or keys), where leaf value of every
+/// value is its canonical encoding (see [`LeafEncode`])
+///
+/// Values are encoded by the forest itself (instead of being encoded by callers before every
+/// insertion, proof generation and deletion), so, the leaf hash of a value is always calculated
+/// from the same bytes.
+pub struct TypedForest<T: ?Sized, H = Blake3> {
+    /// Merkle forest of encoded values
+    forest: MemoryForest<H>,
+    /// Type of values in forest
+    values: PhantomData<fn(&T)>,
+}
+
+impl<T: LeafEncode + ?Sized> TypedForest<T> {
+    /// Creates a new instance of typed forest
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<T: LeafEncode + ?Sized, H: Hasher> TypedForest<T, H> {
+    /// Creates a new instance of typed forest which uses given hasher
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::from_forest(MemoryForest::with_hasher(hasher))
+    }
+
+    /// Creates a typed forest from a merkle forest whose leaf values are canonical encodings of
+    /// values of type `T`
+    pub fn from_forest(forest: MemoryForest<H>) -> Self {
+        Self {
+            forest,
+            values: PhantomData,
+        }
+    }
+
+    /// Returns underlying merkle forest
+    pub fn forest(&self) -> &MemoryForest<H> {
+        &self.forest
+    }
+
+    /// Returns underlying merkle forest (consuming typed forest)
+    pub fn into_forest(self) -> MemoryForest<H> {
+        self.forest
+    }
+
+    /// Returns leaf hash of given value
+    pub fn leaf_hash(&self, value: &T) -> Hash {
+        self.forest.hasher().hash_leaf(&value.to_leaf_bytes())
+    }
+
+    /// Inserts a value in forest
+    pub fn insert(&mut self, value: &T) {
+        self.forest.insert_value(value.to_leaf_bytes())
+    }
+
+    /// Returns true if given value is present in forest
+    pub fn contains(&self, value: &T) -> bool {
+        self.forest.contains(value.to_leaf_bytes())
+    }
+
+    /// Generates inclusion proof of given value. Returns `None` if the value is not present.
+    pub fn prove(&self, value: &T) -> Result<Option<Proof>, UtreexoError> {
+        self.forest.prove_value(value.to_leaf_bytes())
+    }
+
+    /// Returns true if given proof is an inclusion proof of given value against current roots of
+    /// forest
+    pub fn verify(&self, value: &T, proof: &Proof) -> bool {
+        proof.leaf_hash() == &self.leaf_hash(value)
+            && proof.verify_roots(self.forest.hasher(), &self.forest.roots())
+    }
+
+    /// Deletes given value from forest. Returns `false` if the value is not present.
+    pub fn delete(&mut self, value: &T) -> Result<bool, UtreexoError> {
+        match self.prove(value)? {
+            Some(proof) => Ok(self.forest.delete(&proof)),
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the number of values in forest
+    pub fn num_leaves(&self) -> u64 {
+        self.forest.num_leaves()
+    }
+
+    /// Returns root hashes of all the trees in merkle forest (indexed by height)
+    pub fn roots(&self) -> Vec<Option<Hash>> {
+        self.forest.roots()
+    }
+}
+
+// `Default`, `Clone` and `Debug` are implemented manually because derived implementations require
+// `T` to implement them as well
+impl<T: ?Sized, H: Default> Default for TypedForest<T, H> {
+    fn default() -> Self {
+        Self {
+            forest: Default::default(),
+            values: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, H: Clone> Clone for TypedForest<T, H> {
+    fn clone(&self) -> Self {
+        Self {
+            forest: self.forest.clone(),
+            values: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, H: fmt::Debug> fmt::Debug for TypedForest<T, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedForest")
+            .field("forest", &self.forest)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A value whose canonical encoding is independent of its in-memory representation
+    #[derive(Debug)]
+    struct Certificate {
+        serial: u64,
+        subject: &'static str,
+    }
+
+    impl LeafEncode for Certificate {
+        fn encode_leaf(&self, bytes: &mut Vec<u8>) {
+            bytes.extend_from_slice(&self.serial.to_be_bytes());
+            self.subject.encode_leaf(bytes);
+        }
+    }
+
+    #[test]
+    fn check_typed_forest() {
+        let certificates = (0..5)
+            .map(|serial| Certificate {
+                serial,
+                subject: "utreexo",
+            })
+            .collect::<Vec<_>>();
+
+        let mut forest = TypedForest::new();
+
+        for certificate in certificates.iter() {
+            forest.insert(certificate);
+        }
+
+        assert_eq!(5, forest.num_leaves());
+        assert!(forest.contains(&certificates[3]));
+        assert_eq!(
+            Blake3.hash_leaf(&[&3u64.to_be_bytes()[..], b"utreexo"].concat()),
+            forest.leaf_hash(&certificates[3])
+        );
+
+        let proof = forest.prove(&certificates[3]).unwrap().unwrap();
+        assert!(forest.verify(&certificates[3], &proof));
+        assert!(!forest.verify(&certificates[2], &proof));
+
+        assert_eq!(Ok(true), forest.delete(&certificates[3]));
+        assert_eq!(Ok(false), forest.delete(&certificates[3]));
+        assert!(!forest.contains(&certificates[3]));
+        assert!(!forest.verify(&certificates[3], &proof));
+        assert_eq!(4, forest.num_leaves());
+
+        let mut forest = TypedForest::<str>::from_forest(forest.into_forest());
+        forest.insert("utreexo");
+        assert!(forest.contains("utreexo"));
+        assert!(forest.forest().contains(b"utreexo"));
+    }
+}
@@ -0,0 +1,64 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Checkpoint, Hash, Proof};
+
+/// Changes made to an accumulator by an update (e.g., a block), which can be sent to remote clients
+/// so that they can update the proofs they hold (see [`Proof::extend`](crate::Proof::extend))
+/// without querying a bridge node
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct UpdateData {
+    /// Root hashes of merkle trees before the update (indexed by height)
+    prev_roots: Vec<Option<Hash>>,
+    /// Proofs of leaves deleted by the update (in order of deletion, each proof is valid after all
+    /// the previous deletions)
+    deletions: Vec<Proof>,
+    /// Leaf hashes inserted by the update (in order of insertion)
+    additions: Vec<Hash>,
+}
+
+impl UpdateData {
+    /// Creates update data for deletion of leaves with given proofs followed by insertion of given
+    /// leaf hashes (both in order) in an accumulator with given root hashes
+    pub fn new(prev_roots: Vec<Option<Hash>>, deletions: Vec<Proof>, additions: Vec<Hash>) -> Self {
+        Self {
+            prev_roots,
+            deletions,
+            additions,
+        }
+    }
+
+    /// Returns root hashes of merkle trees before the update (indexed by height)
+    pub fn prev_roots(&self) -> &[Option<Hash>] {
+        &self.prev_roots
+    }
+
+    /// Returns proofs of leaves deleted by the update (in order of deletion)
+    pub fn deletions(&self) -> &[Proof] {
+        &self.deletions
+    }
+
+    /// Returns leaf hashes inserted by the update (in order of insertion)
+    pub fn additions(&self) -> &[Hash] {
+        &self.additions
+    }
+}
+
+/// Changes made to an accumulator or a forest by a block (see
+/// [`MemoryAccumulator::apply_block`](crate::MemoryAccumulator::apply_block))
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockChanges {
+    /// Root hashes of merkle trees after the block (indexed by height)
+    pub roots: Vec<Option<Hash>>,
+    /// Update data which can be sent to remote clients for updating the proofs they hold
+    pub update: UpdateData,
+    /// Checkpoint of state before the block, which can be restored to undo the block (e.g., in a
+    /// reorg)
+    pub undo: Checkpoint,
+}
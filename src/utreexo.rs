@@ -1,11 +1,35 @@
-use crate::Proof;
+use crate::{Hash, Proof};
 
 /// Trait for all the operations of Utreexo accumulator
 pub trait Utreexo {
-    /// Inserts a new value in accumulator
-    fn insert<T: AsRef<[u8]>>(&mut self, leaf_value: T);
+    /// Inserts a new value in accumulator (same as `insert_value()`)
+    fn insert<T: AsRef<[u8]>>(&mut self, leaf_value: T) {
+        self.insert_value(leaf_value)
+    }
+
+    /// Calculates leaf hash of given value (using hasher of accumulator) and inserts it in
+    /// accumulator
+    fn insert_value<T: AsRef<[u8]>>(&mut self, leaf_value: T);
+
+    /// Inserts an already hashed leaf in accumulator. Leaf hash should be calculated using hasher
+    /// of accumulator (i.e., `hasher.hash_leaf(leaf_value)`), so that it matches the leaf hash of
+    /// value inserted using `insert_value()`.
+    fn insert_hash(&mut self, leaf_hash: Hash);
 
     /// Verifies and deletes value corresponding to given proof from accumulator. Returns true if the value was
     /// successfully verified and deleted, false otherwise
     fn delete(&mut self, proof: &Proof) -> bool;
+
+    /// Returns the number of leaves in accumulator
+    fn num_leaves(&self) -> u64;
+
+    /// Returns the number of leaves in accumulator (same as `num_leaves()`)
+    fn len(&self) -> u64 {
+        self.num_leaves()
+    }
+
+    /// Returns true if there are no leaves in accumulator
+    fn is_empty(&self) -> bool {
+        self.num_leaves() == 0
+    }
 }
@@ -0,0 +1,287 @@
+//! Messages exchanged between nodes for syncing accumulator state and fetching proofs
+//!
+//! This module only defines messages and their encodings, and leaves transport (and which peer is
+//! asked for what) to networking layers built on top of it. Every message has a stable binary
+//! encoding (see [`Message::to_bytes`]) and, with `serde-1` feature, can also be serialized using
+//! any serde format.
+//!
+//! Layout: `b"UTRW" || version (1 byte) || message type (1 byte) || payload`, where payload of each
+//! message type is:
+//!
+//! - `0x00` [`Message::GetRoots`]: empty
+//! - `0x01` [`Message::Roots`]: `number of roots (1 byte) || roots`, where each root is either
+//!   `0x00` (no tree of that height) or `0x01 || root hash (32 bytes)`
+//! - `0x02` [`Message::GetProof`]: `leaf hash (32 bytes)`
+//! - `0x03` [`Message::Proof`]: `0x00` (leaf not found) or `0x01 || length (4 bytes, big endian)
+//!   || proof`, where proof is in its canonical binary encoding (see [`Proof::from_bytes`])
+//! - `0x04` [`Message::GetChunk`]: `tree height (1 byte) || chunk height (1 byte) || index (8
+//!   bytes, big endian)`
+//! - `0x05` [`Message::Chunk`]: `0x00` (chunk not found) or `0x01 || length (4 bytes, big endian)
+//!   || chunk`, where chunk is in its canonical binary encoding (see
+//!   [`SnapshotChunk::from_bytes`])
+//! - `0x06` [`Message::UpdateData`]: `previous roots || number of deletions (4 bytes, big endian)
+//!   || deletions || number of additions (4 bytes, big endian) || additions`, where previous roots
+//!   are encoded just like [`Message::Roots`], each deletion is `length (4 bytes, big endian) ||
+//!   proof` and each addition is a leaf hash (32 bytes)
+//!
+//! All the integers are big endian.
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    encoding::{write_header, write_prefixed, write_roots, Reader, ABSENT, PRESENT, WIRE_MAGIC},
+    Hash, Proof, SnapshotChunk, UpdateData, UtreexoError,
+};
+
+/// Message type of [`Message::GetRoots`]
+const GET_ROOTS: u8 = 0;
+/// Message type of [`Message::Roots`]
+const ROOTS: u8 = 1;
+/// Message type of [`Message::GetProof`]
+const GET_PROOF: u8 = 2;
+/// Message type of [`Message::Proof`]
+const PROOF: u8 = 3;
+/// Message type of [`Message::GetChunk`]
+const GET_CHUNK: u8 = 4;
+/// Message type of [`Message::Chunk`]
+const CHUNK: u8 = 5;
+/// Message type of [`Message::UpdateData`]
+const UPDATE_DATA: u8 = 6;
+
+/// Request or response exchanged between nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Message {
+    /// Requests current root hashes of peer
+    GetRoots,
+    /// Root hashes of merkle trees in forest (indexed by height)
+    Roots(Vec<Option<Hash>>),
+    /// Requests inclusion proof of a leaf hash
+    GetProof(Hash),
+    /// Inclusion proof of requested leaf hash (`None` if the leaf is not present)
+    Proof(Option<Proof>),
+    /// Requests a snapshot chunk (see [`MemoryForest::chunk`](crate::MemoryForest::chunk))
+    GetChunk {
+        /// Height of merkle tree containing the chunk
+        tree_height: usize,
+        /// Height of chunk
+        chunk_height: usize,
+        /// Index of chunk in merkle tree
+        index: usize,
+    },
+    /// Requested snapshot chunk (`None` if the chunk is not present)
+    Chunk(Option<SnapshotChunk>),
+    /// Changes made to accumulator by an update (e.g., a block)
+    UpdateData(UpdateData),
+}
+
+impl Message {
+    /// Returns stable binary encoding of message (see [module documentation](self) for layout)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, WIRE_MAGIC);
+
+        match self {
+            Message::GetRoots => bytes.push(GET_ROOTS),
+            Message::Roots(roots) => {
+                bytes.push(ROOTS);
+                write_roots(&mut bytes, roots);
+            }
+            Message::GetProof(leaf_hash) => {
+                bytes.push(GET_PROOF);
+                bytes.extend_from_slice(leaf_hash.as_bytes());
+            }
+            Message::Proof(proof) => {
+                bytes.push(PROOF);
+                write_optional(&mut bytes, proof.as_ref().map(Proof::to_bytes));
+            }
+            Message::GetChunk {
+                tree_height,
+                chunk_height,
+                index,
+            } => {
+                bytes.push(GET_CHUNK);
+                // Heights of trees always fit in a byte because they cannot exceed
+                // `Proof::MAX_HEIGHT`
+                bytes.push(*tree_height as u8);
+                bytes.push(*chunk_height as u8);
+                bytes.extend_from_slice(&(*index as u64).to_be_bytes());
+            }
+            Message::Chunk(chunk) => {
+                bytes.push(CHUNK);
+                write_optional(&mut bytes, chunk.as_ref().map(SnapshotChunk::to_bytes));
+            }
+            Message::UpdateData(update) => {
+                bytes.push(UPDATE_DATA);
+                write_roots(&mut bytes, update.prev_roots());
+
+                bytes.extend_from_slice(&(update.deletions().len() as u32).to_be_bytes());
+                for proof in update.deletions() {
+                    write_prefixed(&mut bytes, &proof.to_bytes());
+                }
+
+                bytes.extend_from_slice(&(update.additions().len() as u32).to_be_bytes());
+                for leaf_hash in update.additions() {
+                    bytes.extend_from_slice(leaf_hash.as_bytes());
+                }
+            }
+        }
+
+        bytes
+    }
+
+    /// Parses message from its stable binary encoding (see [module documentation](self) for
+    /// layout). Returns `UnsupportedVersion` error if message was encoded with a different format
+    /// version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        let mut reader = Reader::new(bytes);
+        reader.read_header(WIRE_MAGIC)?;
+
+        let message = match reader.read_u8()? {
+            GET_ROOTS => Message::GetRoots,
+            ROOTS => Message::Roots(reader.read_roots()?),
+            GET_PROOF => Message::GetProof(reader.read_hash()?),
+            PROOF => Message::Proof(read_optional(&mut reader, Proof::from_bytes)?),
+            GET_CHUNK => Message::GetChunk {
+                tree_height: usize::from(reader.read_u8()?),
+                chunk_height: usize::from(reader.read_u8()?),
+                index: usize::try_from(reader.read_u64()?)
+                    .map_err(|_| UtreexoError::InvalidEncoding("Invalid index of chunk"))?,
+            },
+            CHUNK => Message::Chunk(read_optional(&mut reader, SnapshotChunk::from_bytes)?),
+            UPDATE_DATA => {
+                let prev_roots = reader.read_roots()?;
+
+                // Deletions are parsed one by one (without preallocating for number of
+                // deletions) so that malformed input cannot cause huge allocations
+                let num_deletions = reader.read_u32()?;
+                let deletions = (0..num_deletions)
+                    .map(|_| Proof::from_bytes(reader.read_prefixed()?))
+                    .collect::<Result<_, _>>()?;
+
+                let num_additions = reader.read_u32()?;
+                let additions =
+                    reader.read_hashes(usize::try_from(num_additions).unwrap_or(usize::MAX))?;
+
+                Message::UpdateData(UpdateData::new(prev_roots, deletions, additions))
+            }
+            _ => return Err(UtreexoError::InvalidEncoding("Unknown message type")),
+        };
+
+        reader.finish()?;
+
+        Ok(message)
+    }
+}
+
+/// Writes `ABSENT` if value is `None`, otherwise writes `PRESENT` followed by encoded value
+/// prefixed with its length
+fn write_optional(bytes: &mut Vec<u8>, value: Option<Vec<u8>>) {
+    match value {
+        None => bytes.push(ABSENT),
+        Some(value) => {
+            bytes.push(PRESENT);
+            write_prefixed(bytes, &value);
+        }
+    }
+}
+
+/// Reads a value written by [`write_optional`] and parses it using given function
+fn read_optional<T>(
+    reader: &mut Reader<'_>,
+    parse: impl FnOnce(&[u8]) -> Result<T, UtreexoError>,
+) -> Result<Option<T>, UtreexoError> {
+    if reader.read_presence()? {
+        parse(reader.read_prefixed()?).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::slice;
+
+    use crate::{MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_wire_messages() {
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            forest.insert([value; 32]);
+        }
+
+        let proof = forest.prove([1; 32]).unwrap().unwrap();
+        let changes = forest
+            .clone()
+            .apply_block(&[[5; 32], [6; 32]], slice::from_ref(&proof))
+            .unwrap();
+
+        let messages = vec![
+            Message::GetRoots,
+            Message::Roots(forest.roots()),
+            Message::GetProof(*proof.leaf_hash()),
+            Message::Proof(Some(proof)),
+            Message::Proof(None),
+            Message::GetChunk {
+                tree_height: 2,
+                chunk_height: 1,
+                index: 1,
+            },
+            Message::Chunk(forest.chunk(2, 1, 1)),
+            Message::Chunk(None),
+            Message::UpdateData(changes.update),
+        ];
+
+        for message in messages.iter() {
+            let bytes = message.to_bytes();
+            assert_eq!(Ok(message), Message::from_bytes(&bytes).as_ref());
+
+            // Truncated and extended messages are rejected
+            assert!(Message::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+            assert!(Message::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+        }
+
+        assert_eq!(b"UTRW\x01\x00", &Message::GetRoots.to_bytes()[..]);
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unknown message type")),
+            Message::from_bytes(b"UTRW\x01\x07")
+        );
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn check_wire_message_schema() {
+        use schemars::{schema::Schema, schema_for};
+
+        let schema = schema_for!(Message);
+        let object = |name: &str| match &schema.definitions[name] {
+            Schema::Object(object) => object.clone(),
+            Schema::Bool(_) => panic!("Expected schema object for {}", name),
+        };
+
+        for name in ["Path", "SnapshotChunk", "Tree", "UpdateData"].iter() {
+            assert!(schema.definitions.contains_key(*name));
+        }
+
+        let hash = object("Hash");
+        assert_eq!(
+            Some("^[0-9a-fA-F]{64}$"),
+            hash.string.unwrap().pattern.as_deref()
+        );
+
+        let proof = object("Proof").object.unwrap();
+        assert_eq!(
+            vec!["leaf_hash", "path", "sibling_hashes"],
+            proof.required.iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,495 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use crate::{encoding::Reader, Hash, Hasher, Proof, UtreexoError};
+
+/// Order in which root hashes of merkle trees are bagged into a single commitment (see
+/// [`RootWitness::commitment_with_order`])
+///
+/// Implementations differ in the order they bag roots of a forest, so, commitments (and root
+/// proofs) can be calculated in either order to match the target ecosystem.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BaggingOrder {
+    /// Roots of taller trees come first (i.e., roots are in order of their leaves)
+    HighestFirst,
+    /// Roots of smaller trees come first (this is the default order)
+    #[default]
+    LowestFirst,
+}
+
+/// Compact fixed layout snapshot of root hashes of an accumulator, which can be used for verifying
+/// inclusion proofs on devices with limited memory (e.g. hardware wallets)
+///
+/// Encoding: `number of leaves (8 bytes, big-endian) || root hashes (32 bytes each)`. Number of
+/// leaves also acts as a bitmap of heights of merkle trees in forest (bit `h` is set iff there is
+/// a tree of height `h`), so, root hashes are packed in increasing order of height without any
+/// markers. For example, witness of a forest with less than `2^40` leaves is at most 1288 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootWitness {
+    /// Number of leaves in accumulator
+    num_leaves: u64,
+    /// Root hashes in increasing order of height
+    root_hashes: Vec<Hash>,
+}
+
+impl RootWitness {
+    /// Creates root witness from roots of merkle trees in forest (indexed by height)
+    pub(crate) fn from_roots(roots: impl IntoIterator<Item = Option<Hash>>) -> Self {
+        let mut num_leaves = 0;
+        let mut root_hashes = Vec::new();
+
+        for (height, root) in roots.into_iter().enumerate() {
+            if let Some(root_hash) = root {
+                num_leaves |= 1 << height;
+                root_hashes.push(root_hash);
+            }
+        }
+
+        Self {
+            num_leaves,
+            root_hashes,
+        }
+    }
+
+    /// Returns number of leaves in accumulator
+    pub fn num_leaves(&self) -> u64 {
+        self.num_leaves
+    }
+
+    /// Returns root hashes in increasing order of height of merkle trees
+    pub fn root_hashes(&self) -> &[Hash] {
+        &self.root_hashes
+    }
+
+    /// Returns root hash of merkle tree of given height, if present
+    pub fn root_hash(&self, height: usize) -> Option<&Hash> {
+        if height >= 64 || self.num_leaves & (1 << height) == 0 {
+            return None;
+        }
+
+        self.root_hashes.get(root_index(
+            self.num_leaves,
+            height,
+            BaggingOrder::LowestFirst,
+        ))
+    }
+
+    /// Returns a single hash committing to all the roots (and their heights), with roots bagged
+    /// in default order (see [`RootWitness::commitment_with_order`])
+    pub fn commitment<H: Hasher>(&self, hasher: &H) -> Hash {
+        self.commitment_with_order(hasher, BaggingOrder::default())
+    }
+
+    /// Returns a single hash committing to all the roots (and their heights), with roots bagged
+    /// in given order
+    ///
+    /// Root hashes (in given order of height) are hashed into a merkle tree, where the left subtree
+    /// of every node has the largest power of two number of roots smaller than the number of roots
+    /// below the node (merkle root of an empty list is all zeros). Commitment is
+    /// `hash_intermediate(hash_leaf(number of leaves (8 bytes, big-endian)), merkle root)`, so,
+    /// inclusion of a single root can be proved with a [`RootProof`].
+    pub fn commitment_with_order<H: Hasher>(&self, hasher: &H, order: BaggingOrder) -> Hash {
+        hasher.hash_intermediate(
+            &hash_num_leaves(hasher, self.num_leaves),
+            &merkle_root(hasher, &self.ordered_root_hashes(order)),
+        )
+    }
+
+    /// Returns proof of inclusion of root hash of merkle tree of given height in commitment (see
+    /// [`RootWitness::commitment`]), if there is a tree of given height
+    pub fn prove_root<H: Hasher>(&self, hasher: &H, height: usize) -> Option<RootProof> {
+        self.prove_root_with_order(hasher, height, BaggingOrder::default())
+    }
+
+    /// Returns proof of inclusion of root hash of merkle tree of given height in commitment with
+    /// roots bagged in given order (see [`RootWitness::commitment_with_order`]), if there is a
+    /// tree of given height
+    pub fn prove_root_with_order<H: Hasher>(
+        &self,
+        hasher: &H,
+        height: usize,
+        order: BaggingOrder,
+    ) -> Option<RootProof> {
+        let root_hash = *self.root_hash(height)?;
+        let index = root_index(self.num_leaves, height, order);
+
+        Some(RootProof {
+            num_leaves: self.num_leaves,
+            height,
+            root_hash,
+            order,
+            sibling_hashes: merkle_path(hasher, &self.ordered_root_hashes(order), index),
+        })
+    }
+
+    /// Returns root hashes in given order of height
+    fn ordered_root_hashes(&self, order: BaggingOrder) -> Vec<Hash> {
+        match order {
+            BaggingOrder::HighestFirst => self.root_hashes.iter().rev().copied().collect(),
+            BaggingOrder::LowestFirst => self.root_hashes.clone(),
+        }
+    }
+
+    /// Verifies inclusion proof of a value with given hasher
+    pub fn verify<H: Hasher>(&self, hasher: &H, proof: &Proof) -> bool {
+        match self.root_hash(proof.path.height()) {
+            Some(root_hash) => proof.verify(hasher, *root_hash),
+            None => false,
+        }
+    }
+
+    /// Returns encoding of root witness (see [`RootWitness`] for layout)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.root_hashes.len() * Hash::LEN);
+
+        bytes.extend_from_slice(&self.num_leaves.to_be_bytes());
+
+        for root_hash in self.root_hashes.iter() {
+            bytes.extend_from_slice(root_hash.as_bytes());
+        }
+
+        bytes
+    }
+
+    /// Parses root witness from its encoding (see [`RootWitness`] for layout)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtreexoError> {
+        let mut reader = Reader::new(bytes);
+
+        let num_leaves = u64::from_be_bytes(
+            reader
+                .read_bytes(8)?
+                .try_into()
+                .expect("Expected exactly 8 bytes"),
+        );
+        let root_hashes = reader.read_hashes(num_leaves.count_ones() as usize)?;
+
+        reader.finish()?;
+
+        Ok(Self {
+            num_leaves,
+            root_hashes,
+        })
+    }
+}
+
+/// Proof of inclusion of root hash of a merkle tree in commitment of roots of an accumulator (see
+/// [`RootWitness::commitment`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootProof {
+    /// Number of leaves in accumulator
+    num_leaves: u64,
+    /// Height of merkle tree
+    height: usize,
+    /// Root hash of merkle tree
+    root_hash: Hash,
+    /// Order in which roots are bagged in commitment
+    order: BaggingOrder,
+    /// Sibling hashes in merkle tree of roots (from bottom to top)
+    sibling_hashes: Vec<Hash>,
+}
+
+impl RootProof {
+    /// Returns number of leaves in accumulator
+    pub fn num_leaves(&self) -> u64 {
+        self.num_leaves
+    }
+
+    /// Returns height of merkle tree
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns root hash of merkle tree
+    pub fn root_hash(&self) -> &Hash {
+        &self.root_hash
+    }
+
+    /// Returns order in which roots are bagged in commitment
+    pub fn order(&self) -> BaggingOrder {
+        self.order
+    }
+
+    /// Returns sibling hashes in merkle tree of roots (from bottom to top)
+    pub fn sibling_hashes(&self) -> &[Hash] {
+        &self.sibling_hashes
+    }
+
+    /// Verifies the proof against given commitment of roots (with roots bagged in order of the
+    /// proof) with given hasher
+    pub fn verify<H: Hasher>(&self, hasher: &H, commitment: Hash) -> bool {
+        if self.height >= 64 || self.num_leaves & (1 << self.height) == 0 {
+            return false;
+        }
+
+        let num_roots = self.num_leaves.count_ones() as usize;
+        let index = root_index(self.num_leaves, self.height, self.order);
+
+        match merkle_root_from_path(
+            hasher,
+            self.root_hash,
+            index,
+            num_roots,
+            &self.sibling_hashes,
+        ) {
+            Some(merkle_root) => {
+                let expected = hasher
+                    .hash_intermediate(&hash_num_leaves(hasher, self.num_leaves), &merkle_root);
+                expected == commitment
+            }
+            None => false,
+        }
+    }
+}
+
+/// Returns index of root hash of merkle tree of given height in the list of root hashes in given
+/// order (root hashes of all the smaller trees come before it in lowest first order)
+fn root_index(num_leaves: u64, height: usize, order: BaggingOrder) -> usize {
+    let index = (num_leaves & ((1 << height) - 1)).count_ones() as usize;
+
+    match order {
+        BaggingOrder::HighestFirst => num_leaves.count_ones() as usize - 1 - index,
+        BaggingOrder::LowestFirst => index,
+    }
+}
+
+/// Returns hash of number of leaves used in commitment
+fn hash_num_leaves<H: Hasher>(hasher: &H, num_leaves: u64) -> Hash {
+    hasher.hash_leaf(&num_leaves.to_be_bytes())
+}
+
+/// Returns number of hashes in left subtree of a node with given number of hashes (largest power
+/// of two smaller than `len`)
+fn split_point(len: usize) -> usize {
+    (len / 2 + len % 2).next_power_of_two()
+}
+
+/// Returns merkle root of given hashes
+fn merkle_root<H: Hasher>(hasher: &H, hashes: &[Hash]) -> Hash {
+    match hashes.len() {
+        0 => Hash::from([0; Hash::LEN]),
+        1 => hashes[0],
+        len => {
+            let (left, right) = hashes.split_at(split_point(len));
+            hasher.hash_intermediate(&merkle_root(hasher, left), &merkle_root(hasher, right))
+        }
+    }
+}
+
+/// Returns sibling hashes (from bottom to top) of hash at given index in merkle tree of given
+/// hashes
+fn merkle_path<H: Hasher>(hasher: &H, hashes: &[Hash], index: usize) -> Vec<Hash> {
+    if hashes.len() <= 1 {
+        return Vec::new();
+    }
+
+    let (left, right) = hashes.split_at(split_point(hashes.len()));
+
+    let (mut path, sibling) = if index < left.len() {
+        (merkle_path(hasher, left, index), merkle_root(hasher, right))
+    } else {
+        (
+            merkle_path(hasher, right, index - left.len()),
+            merkle_root(hasher, left),
+        )
+    };
+
+    path.push(sibling);
+    path
+}
+
+/// Calculates merkle root of a tree with `len` hashes from hash at given index and its sibling
+/// hashes (from bottom to top). Returns `None` if the number of sibling hashes is incorrect.
+fn merkle_root_from_path<H: Hasher>(
+    hasher: &H,
+    hash: Hash,
+    index: usize,
+    len: usize,
+    sibling_hashes: &[Hash],
+) -> Option<Hash> {
+    if len <= 1 {
+        return if sibling_hashes.is_empty() {
+            Some(hash)
+        } else {
+            None
+        };
+    }
+
+    let (sibling, rest) = sibling_hashes.split_last()?;
+    let split = split_point(len);
+
+    if index < split {
+        let left = merkle_root_from_path(hasher, hash, index, split, rest)?;
+        Some(hasher.hash_intermediate(&left, sibling))
+    } else {
+        let right = merkle_root_from_path(hasher, hash, index - split, len - split, rest)?;
+        Some(hasher.hash_intermediate(sibling, &right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake3, MemoryAccumulator, MemoryForest, Prover, Utreexo};
+
+    #[test]
+    fn check_root_witness() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        for value in 0..5u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        let witness = accumulator.root_witness();
+        assert_eq!(witness, forest.root_witness());
+
+        assert_eq!(5, witness.num_leaves());
+        assert_eq!(2, witness.root_hashes().len());
+        assert_eq!(None, witness.root_hash(1));
+        assert_eq!(accumulator.root_hashes()[2].as_ref(), witness.root_hash(2));
+
+        let bytes = witness.to_bytes();
+
+        assert_eq!(8 + 2 * 32, bytes.len());
+        assert_eq!(Ok(witness.clone()), RootWitness::from_bytes(&bytes));
+        assert_eq!(
+            Err(UtreexoError::InvalidEncoding("Unexpected end of input")),
+            RootWitness::from_bytes(&bytes[..40])
+        );
+
+        assert_eq!(accumulator.root_commitment(), forest.root_commitment());
+        assert_eq!(witness.commitment(&Blake3), accumulator.root_commitment());
+
+        let proof = forest.prove([2; 32]).unwrap().unwrap();
+        assert!(witness.verify(&Blake3, &proof));
+
+        forest.delete(&proof);
+        accumulator.delete(&proof);
+
+        assert!(!accumulator.root_witness().verify(&Blake3, &proof));
+        assert_ne!(witness.commitment(&Blake3), accumulator.root_commitment());
+        assert_eq!(4, accumulator.root_witness().num_leaves());
+    }
+
+    #[test]
+    fn check_root_proof() {
+        let mut accumulator = MemoryAccumulator::new();
+
+        let empty = accumulator.root_witness().commitment(&Blake3);
+        assert_eq!(
+            Blake3.hash_intermediate(&Blake3.hash_leaf(&0u64.to_be_bytes()), &Hash::from([0; 32])),
+            empty
+        );
+
+        for value in 0..0b1011_0111u8 {
+            accumulator.insert([value; 32]);
+        }
+
+        let commitment = accumulator.root_commitment();
+        let witness = accumulator.root_witness();
+
+        for height in 0..8 {
+            let proof = accumulator.prove_root(height);
+
+            if accumulator.root_hashes()[height].is_none() {
+                assert!(proof.is_none());
+                continue;
+            }
+
+            let proof = proof.unwrap();
+
+            assert_eq!(height, proof.height());
+            assert_eq!(witness.root_hash(height), Some(proof.root_hash()));
+            assert_eq!(Some(proof.clone()), witness.prove_root(&Blake3, height));
+            assert!(proof.verify(&Blake3, commitment));
+            assert!(!proof.verify(&Blake3, empty));
+
+            // Proofs with tampered parts should fail
+            let mut tampered = proof.clone();
+            tampered.root_hash = Blake3.hash_leaf(&[0]);
+            assert!(!tampered.verify(&Blake3, commitment));
+
+            let mut tampered = proof.clone();
+            tampered.num_leaves += 1 << 8;
+            assert!(!tampered.verify(&Blake3, commitment));
+
+            let mut tampered = proof.clone();
+            tampered.sibling_hashes.pop();
+            assert!(!tampered.verify(&Blake3, commitment));
+
+            let mut tampered = proof;
+            tampered.height = (height + 1) % 8;
+            assert!(!tampered.verify(&Blake3, commitment));
+        }
+
+        // Left subtree of merkle tree of 6 roots has 4 roots and right subtree has 2 roots
+        assert_eq!(3, accumulator.prove_root(0).unwrap().sibling_hashes().len());
+        assert_eq!(2, accumulator.prove_root(7).unwrap().sibling_hashes().len());
+    }
+
+    #[test]
+    fn check_bagging_order() {
+        let mut accumulator = MemoryAccumulator::new();
+        let mut forest = MemoryForest::new();
+
+        for value in 0..0b1011u8 {
+            accumulator.insert([value; 32]);
+            forest.insert([value; 32]);
+        }
+
+        let witness = accumulator.root_witness();
+
+        let lowest_first = witness.commitment_with_order(&Blake3, BaggingOrder::LowestFirst);
+        let highest_first = witness.commitment_with_order(&Blake3, BaggingOrder::HighestFirst);
+
+        assert_ne!(lowest_first, highest_first);
+        assert_eq!(lowest_first, witness.commitment(&Blake3));
+        assert_eq!(
+            highest_first,
+            accumulator.root_commitment_with_order(BaggingOrder::HighestFirst)
+        );
+        assert_eq!(
+            highest_first,
+            forest.root_commitment_with_order(BaggingOrder::HighestFirst)
+        );
+
+        // Roots are bagged from the root of the tallest tree
+        let roots: Vec<Hash> = accumulator
+            .root_hashes()
+            .iter()
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        assert_eq!(
+            Blake3.hash_intermediate(
+                &Blake3.hash_leaf(&11u64.to_be_bytes()),
+                &Blake3
+                    .hash_intermediate(&Blake3.hash_intermediate(&roots[0], &roots[1]), &roots[2])
+            ),
+            highest_first
+        );
+
+        for height in [0, 1, 3] {
+            let proof = forest
+                .prove_root_with_order(height, BaggingOrder::HighestFirst)
+                .unwrap();
+
+            assert_eq!(BaggingOrder::HighestFirst, proof.order());
+            assert_eq!(
+                Some(proof.clone()),
+                accumulator.prove_root_with_order(height, BaggingOrder::HighestFirst)
+            );
+            assert!(proof.verify(&Blake3, highest_first));
+            assert!(!proof.verify(&Blake3, lowest_first));
+        }
+
+        // Both orders are same for a single root
+        let witness = MemoryAccumulator::from_leaves(&[[0; 32], [1; 32]]).root_witness();
+        assert_eq!(
+            witness.commitment_with_order(&Blake3, BaggingOrder::LowestFirst),
+            witness.commitment_with_order(&Blake3, BaggingOrder::HighestFirst)
+        );
+    }
+}